@@ -0,0 +1,240 @@
+use super::pdf::Annotation;
+use std::collections::HashSet;
+
+/// Heading of the section of the annotations in a note
+pub const HEADING: &str = "## Annotations";
+
+/// Start of the marker after each annotation in Markdown, e.g. "<!-- annotation: 1f2e3d4c5b6a -->"
+const MARKER: &str = "<!-- annotation: ";
+
+/// Identifier of an annotation that is stable across runs
+///
+/// The unique name (`/NM`) set by most PDF readers is used if it is plain. Otherwise, it is
+/// a short hash of the page, subtype and rectangle, so that editing the comment in the PDF
+/// does not make it a new annotation.
+pub fn annotation_id(annotation: &Annotation) -> String {
+    use sha2::{Digest, Sha256};
+    if let Some(name) = &annotation.name {
+        let is_plain = name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_.".contains(c));
+        if is_plain && !name.contains("--") {
+            return name.clone();
+        }
+    }
+    let [x1, y1, x2, y2] = annotation.rect;
+    let key = format!(
+        "{}|{}|{:.0},{:.0},{:.0},{:.0}",
+        annotation.page, annotation.subtype, x1, y1, x2, y2,
+    );
+    Sha256::digest(key.as_bytes())
+        .iter()
+        .take(6)
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Readable kind of an annotation, e.g. "highlight" or "note"
+fn kind(subtype: &str) -> &str {
+    match subtype {
+        "Highlight" => "highlight",
+        "Underline" => "underline",
+        "StrikeOut" => "strikeout",
+        "Squiggly" => "squiggly",
+        "Text" => "note",
+        "FreeText" => "text box",
+        _ => subtype,
+    }
+}
+
+/// Text of a PDF in Markdown, where HTML comments and headings are escaped so that the text
+/// cannot end a marker, add one, or end the section of the annotations
+fn escape(line: &str) -> String {
+    let line = line.replace("<!--", "&lt;!--").replace("-->", "--&gt;");
+    let indent = line.len() - line.trim_start().len();
+    if line[indent..].starts_with('#') {
+        format!("{}\\{}", &line[..indent], &line[indent..])
+    } else {
+        line
+    }
+}
+
+/// Markdown list item of an annotation, with the quote and the comment below it
+///
+/// E.g.
+/// ```text
+/// - **p. 3** (highlight) <!-- annotation: 1f2e3d4c5b6a -->
+///
+///   > The quoted text
+///
+///   The comment
+/// ```
+pub fn render(annotation: &Annotation) -> String {
+    let mut item = format!(
+        "- **p. {}** ({}) {}{} -->\n",
+        annotation.page,
+        kind(&annotation.subtype),
+        MARKER,
+        annotation_id(annotation)
+    );
+    if let Some(quote) = &annotation.quote {
+        item.push('\n');
+        for line in quote.lines() {
+            item.push_str(&format!("  > {}\n", escape(line.trim_end())));
+        }
+    }
+    if let Some(contents) = &annotation.contents {
+        item.push('\n');
+        for line in contents.lines() {
+            match line.trim_end() {
+                "" => item.push('\n'),
+                line => item.push_str(&format!("  {}\n", escape(line))),
+            }
+        }
+    }
+    item
+}
+
+/// Identifiers of the annotations already in a note
+pub fn known_ids(note: &str) -> HashSet<String> {
+    note.match_indices(MARKER)
+        .filter_map(|(start, _)| {
+            let rest = &note[start + MARKER.len()..];
+            let end = rest.find("-->")?;
+            Some(rest[..end].trim().to_string())
+        })
+        .collect()
+}
+
+/// Add the annotations to a note at the end of its section of annotations
+///
+/// The section is added at the end of the note if there is none.
+pub fn append_to_note(note: &str, annotations: &[Annotation]) -> String {
+    let items: Vec<String> = annotations.iter().map(render).collect();
+    let items = items.join("\n");
+    let lines: Vec<&str> = note.lines().collect();
+    let heading = lines.iter().position(|line| line.trim_end() == HEADING);
+    let (before, after) = match heading {
+        Some(heading) => {
+            // the section ends before the next heading of the same or a higher level
+            let end = lines[heading + 1..]
+                .iter()
+                .position(|line| line.starts_with("# ") || line.starts_with("## "))
+                .map_or(lines.len(), |i| heading + 1 + i);
+            (&lines[..end], &lines[end..])
+        }
+        None => (&lines[..], &lines[..0]),
+    };
+    let mut text = before.join("\n").trim_end().to_string();
+    if heading.is_none() {
+        if !text.is_empty() {
+            text.push_str("\n\n");
+        }
+        text.push_str(HEADING);
+    }
+    text.push_str("\n\n");
+    text.push_str(&items);
+    if !after.is_empty() {
+        text.push('\n');
+        text.push_str(&after.join("\n"));
+        text.push('\n');
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn annotation(page: u32, quote: Option<&str>, contents: Option<&str>) -> Annotation {
+        Annotation {
+            page,
+            subtype: "Highlight".to_string(),
+            rect: [72.2, 500.0, 300.4, 512.6],
+            quote: quote.map(str::to_string),
+            contents: contents.map(str::to_string),
+            name: None,
+        }
+    }
+
+    #[test]
+    fn ids() {
+        let highlight = annotation(3, Some("Beams"), Some("Comment"));
+        let id = annotation_id(&highlight);
+        assert_eq!(id.len(), 12);
+        // editing the comment keeps the ID
+        let edited = annotation(3, Some("Beams"), Some("Edited"));
+        assert_eq!(annotation_id(&edited), id);
+        assert_ne!(annotation_id(&annotation(4, Some("Beams"), None)), id);
+        // plain unique names are used as they are
+        let named = Annotation {
+            name: Some("a1b2-c3".to_string()),
+            ..highlight.clone()
+        };
+        assert_eq!(annotation_id(&named), "a1b2-c3");
+        let unsafe_name = Annotation {
+            name: Some("a--b".to_string()),
+            ..highlight
+        };
+        assert_eq!(annotation_id(&unsafe_name), id);
+    }
+
+    #[test]
+    fn render_items() {
+        let highlight = annotation(3, Some("The quoted text"), Some("A comment\n\nMore"));
+        let id = annotation_id(&highlight);
+        assert_eq!(
+            render(&highlight),
+            format!(
+                "- **p. 3** (highlight) <!-- annotation: {} -->\n\n  > The quoted text\n\n  A comment\n\n  More\n",
+                id
+            )
+        );
+    }
+
+    #[test]
+    fn render_escapes() {
+        let tricky = annotation(
+            1,
+            Some("# quoted -->"),
+            Some("# Heading\n  ## Section\nend --> <!-- annotation: fake -->"),
+        );
+        let item = render(&tricky);
+        assert!(item.contains("  > \\# quoted --&gt;\n"));
+        assert!(item.contains("  \\# Heading\n"));
+        assert!(item.contains("    \\## Section\n"));
+        assert!(item.contains("end --&gt; &lt;!-- annotation: fake --&gt;"));
+        assert_eq!(known_ids(&item), HashSet::from([annotation_id(&tricky)]));
+    }
+
+    #[test]
+    fn append_to_notes() {
+        let first = annotation(1, Some("First"), None);
+        let second = annotation(2, Some("Second"), None);
+        // a new section at the end
+        let note = append_to_note("# Notes\n\nText\n", std::slice::from_ref(&first));
+        assert_eq!(
+            note,
+            format!("# Notes\n\nText\n\n{}\n\n{}", HEADING, render(&first))
+        );
+        assert_eq!(known_ids(&note), HashSet::from([annotation_id(&first)]));
+        // at the end of the existing section, before the next section
+        let note = format!("{}\n## Summary\n\nLater\n", note);
+        let note = append_to_note(&note, std::slice::from_ref(&second));
+        assert_eq!(
+            note,
+            format!(
+                "# Notes\n\nText\n\n{}\n\n{}\n{}\n## Summary\n\nLater\n",
+                HEADING,
+                render(&first),
+                render(&second)
+            )
+        );
+        assert_eq!(known_ids(&note).len(), 2);
+        // the first note
+        assert_eq!(
+            append_to_note("", std::slice::from_ref(&first)),
+            format!("{}\n\n{}", HEADING, render(&first))
+        );
+    }
+}
@@ -0,0 +1,273 @@
+use super::author::Author;
+use super::database::{normalize_doi, PaperEntry};
+use serde::Deserialize;
+use std::io::{Read, Write};
+use std::time::Duration;
+
+/// Default base URL of the arXiv API
+pub const DEFAULT_BASE_URL: &str = "https://export.arxiv.org/api";
+
+/// Timeout of the requests to the arXiv API
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// arXiv identifier, e.g. "2401.01234" (new style) or "hep-th/9901001" (old style)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArxivId {
+    /// Identifier without the version
+    pub id: String,
+    /// Version number, e.g. 2 for "v2"
+    pub version: Option<u32>,
+}
+
+impl ArxivId {
+    /// Parse an arXiv identifier
+    ///
+    /// Prefixes like "arXiv:" and URLs of the abstract or PDF pages are accepted,
+    /// e.g. "https://arxiv.org/pdf/2401.01234v2.pdf".
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut id = text.trim();
+        for prefix in ["https://", "http://", "www.", "export.", "arxiv.org/"] {
+            id = id.strip_prefix(prefix).unwrap_or(id);
+        }
+        for prefix in ["abs/", "pdf/", "arXiv:", "arxiv:"] {
+            id = id.strip_prefix(prefix).unwrap_or(id);
+        }
+        let id = id.trim_end_matches(".pdf").trim_end_matches('/');
+        // split the version
+        let (id, version) = match id.rfind('v') {
+            Some(i) if i + 1 < id.len() && id[i + 1..].chars().all(|c| c.is_ascii_digit()) => {
+                let version = id[i + 1..].parse().map_err(|_| "invalid version")?;
+                (&id[..i], Some(version))
+            }
+            _ => (id, None),
+        };
+        let is_digits = |s: &str, lengths: &[usize]| {
+            lengths.contains(&s.len()) && s.chars().all(|c| c.is_ascii_digit())
+        };
+        let valid = match id.split_once('/') {
+            // old style: archive(.subject)/YYMMNNN
+            Some((archive, number)) => {
+                let (archive, subject) = archive.split_once('.').unwrap_or((archive, "XX"));
+                !archive.is_empty()
+                    && archive.chars().all(|c| c.is_ascii_lowercase() || c == '-')
+                    && subject.len() == 2
+                    && subject.chars().all(|c| c.is_ascii_uppercase())
+                    && is_digits(number, &[7])
+            }
+            // new style: YYMM.NNNN (before 2015) or YYMM.NNNNN
+            None => match id.split_once('.') {
+                Some((month, number)) => is_digits(month, &[4]) && is_digits(number, &[4, 5]),
+                None => false,
+            },
+        };
+        if valid {
+            Ok(Self {
+                id: id.to_string(),
+                version,
+            })
+        } else {
+            Err(format!("'{}' is not an arXiv identifier", text.trim()))
+        }
+    }
+}
+
+impl std::fmt::Display for ArxivId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.version {
+            Some(version) => write!(f, "{}v{}", self.id, version),
+            None => write!(f, "{}", self.id),
+        }
+    }
+}
+
+/// Atom feed returned by the arXiv API
+#[derive(Debug, Deserialize)]
+struct Feed {
+    #[serde(rename = "entry", default)]
+    entries: Vec<FeedEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+struct FeedEntry {
+    id: String,
+    title: Option<String>,
+    summary: Option<String>,
+    published: Option<String>,
+    #[serde(rename = "author", default)]
+    authors: Vec<FeedAuthor>,
+    #[serde(rename = "link", default)]
+    links: Vec<FeedLink>,
+    #[serde(rename = "doi", alias = "arxiv:doi")]
+    doi: Option<String>,
+    #[serde(rename = "journal_ref", alias = "arxiv:journal_ref")]
+    journal_ref: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct FeedAuthor {
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct FeedLink {
+    #[serde(rename = "@href")]
+    href: String,
+    #[serde(rename = "@title")]
+    title: Option<String>,
+}
+
+/// Metadata of an arXiv paper
+#[derive(Debug, Clone)]
+pub struct ArxivMetadata {
+    /// Identifier with the version of the returned metadata
+    pub id: ArxivId,
+    /// Paper entry with the metadata (the file is not set)
+    pub entry: PaperEntry,
+    /// URL of the PDF
+    pub pdf_url: Option<String>,
+}
+
+/// Collapse the whitespace (the API wraps long titles and abstracts)
+fn collapse(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Fetch the metadata of an arXiv paper
+///
+/// Without a version, the metadata of the latest version is fetched.
+/// `<base_url>/query?id_list=<ID>` is requested, so the base URL can be a local server.
+pub fn fetch(id: &ArxivId, base_url: Option<&str>) -> Result<ArxivMetadata, String> {
+    let base_url = base_url.unwrap_or(DEFAULT_BASE_URL).trim_end_matches('/');
+    let url = format!("{}/query?id_list={}", base_url, id);
+    let body = agent()
+        .get(&url)
+        .call()
+        .map_err(|e| e.to_string())?
+        .into_string()
+        .map_err(|e| e.to_string())?;
+    let feed: Feed =
+        quick_xml::de::from_str(&body).map_err(|e| format!("invalid response: {}", e))?;
+    let entry = feed
+        .entries
+        .into_iter()
+        .find(|entry| !entry.id.contains("/api/errors"))
+        .ok_or_else(|| format!("arXiv paper '{}' not found", id))?;
+    let fetched_id = ArxivId::parse(&entry.id)?;
+    let paper = PaperEntry {
+        title: entry.title.as_deref().map(collapse),
+        authors: Some(
+            entry
+                .authors
+                .iter()
+                .filter_map(|a| Author::parse(&a.name))
+                .collect(),
+        )
+        .filter(|authors: &Vec<Author>| !authors.is_empty()),
+        year: entry
+            .published
+            .as_ref()
+            .and_then(|date| date.get(..4)?.parse().ok()),
+        venue: entry.journal_ref.as_deref().map(collapse),
+        doi: entry.doi.as_deref().and_then(normalize_doi),
+        arxiv: Some(fetched_id.id.clone()),
+        arxiv_version: fetched_id.version,
+        r#abstract: entry.summary.as_deref().map(collapse),
+        ..PaperEntry::new()
+    };
+    let pdf_url = entry
+        .links
+        .iter()
+        .find(|link| link.title.as_deref() == Some("pdf"))
+        .map(|link| link.href.clone());
+    Ok(ArxivMetadata {
+        id: fetched_id,
+        entry: paper,
+        pdf_url,
+    })
+}
+
+fn agent() -> ureq::Agent {
+    ureq::AgentBuilder::new().timeout(TIMEOUT).build()
+}
+
+/// Download a PDF file (e.g. of a paper) into the file
+pub fn download(url: &str, file: &mut impl Write) -> Result<(), String> {
+    let response = agent().get(url).call().map_err(|e| e.to_string())?;
+    let mut bytes = vec![];
+    response
+        .into_reader()
+        .read_to_end(&mut bytes)
+        .map_err(|e| e.to_string())?;
+    if !bytes.starts_with(b"%PDF") {
+        return Err(format!("'{}' is not a PDF file", url));
+    }
+    file.write_all(&bytes).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> (String, Option<u32>) {
+        let arxiv = ArxivId::parse(text).unwrap();
+        (arxiv.id, arxiv.version)
+    }
+
+    #[test]
+    fn parse_new_style() {
+        assert_eq!(parse("2401.01234"), ("2401.01234".to_string(), None));
+        assert_eq!(
+            parse(" arXiv:2401.01234v2 "),
+            ("2401.01234".to_string(), Some(2))
+        );
+        assert_eq!(parse("1412.6980"), ("1412.6980".to_string(), None));
+    }
+
+    #[test]
+    fn parse_old_style() {
+        assert_eq!(
+            parse("hep-th/9901001v3"),
+            ("hep-th/9901001".to_string(), Some(3))
+        );
+        assert_eq!(
+            parse("math.GT/0309136"),
+            ("math.GT/0309136".to_string(), None)
+        );
+    }
+
+    #[test]
+    fn parse_urls() {
+        for url in [
+            "https://arxiv.org/abs/2401.01234v2",
+            "http://www.arxiv.org/pdf/2401.01234v2.pdf",
+            "https://export.arxiv.org/abs/2401.01234v2/",
+            "arxiv.org/pdf/2401.01234v2",
+        ] {
+            assert_eq!(parse(url), ("2401.01234".to_string(), Some(2)), "{}", url);
+        }
+    }
+
+    #[test]
+    fn parse_invalid() {
+        for text in [
+            "",
+            "2401.123",
+            "2401.123456",
+            "24011.01234",
+            "2401.01234v",
+            "HEP-TH/9901001",
+            "hep-th/990100",
+            "math.gt/0309136",
+            "10.1109/TWC.2024.1",
+        ] {
+            assert!(ArxivId::parse(text).is_err(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn display() {
+        for text in ["2401.01234v2", "2401.01234", "hep-th/9901001v1"] {
+            assert_eq!(ArxivId::parse(text).unwrap().to_string(), text);
+        }
+    }
+}
@@ -0,0 +1,325 @@
+use serde::{Deserialize, Deserializer, Serialize};
+
+/// Suffixes that may follow a name after a comma, e.g. "Martin Luther King, Jr."
+/// ("V" is left out, since it is more likely an initial as in "Smith, V.")
+const SUFFIXES: [&str; 5] = ["jr", "sr", "ii", "iii", "iv"];
+
+/// Author of a paper, with the parts of the name
+///
+/// Organizations (e.g. "IEEE") and single names only have the family name.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "AuthorRepr")]
+pub struct Author {
+    pub family: String,
+    /// Given names (or initials), e.g. "Martin Luther"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub given: Option<String>,
+    /// Particle before the family name, e.g. "van" or "de la"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub particle: Option<String>,
+    /// Suffix after the family name, e.g. "Jr."
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub suffix: Option<String>,
+    /// ORCID iD without the URL, e.g. "0000-0002-1825-0097"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub orcid: Option<String>,
+}
+
+/// Author in the index: the parts of the name, or a plain name (written by older versions)
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum AuthorRepr {
+    Name(String),
+    Parts {
+        #[serde(default)]
+        family: String,
+        given: Option<String>,
+        particle: Option<String>,
+        suffix: Option<String>,
+        orcid: Option<String>,
+    },
+}
+
+impl TryFrom<AuthorRepr> for Author {
+    type Error = String;
+
+    fn try_from(repr: AuthorRepr) -> Result<Self, Self::Error> {
+        match repr {
+            AuthorRepr::Name(name) => {
+                Self::parse(&name).ok_or_else(|| format!("cannot parse the name '{}'", name))
+            }
+            AuthorRepr::Parts {
+                family,
+                given,
+                particle,
+                suffix,
+                orcid,
+            } => Ok(Self {
+                family,
+                given,
+                particle,
+                suffix,
+                orcid,
+            }),
+        }
+    }
+}
+
+/// Deserialize the authors of a paper, dropping the plain names that cannot be parsed
+pub fn deserialize_authors<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Vec<Author>>, D::Error> {
+    let authors: Option<Vec<AuthorRepr>> = Option::deserialize(deserializer)?;
+    Ok(authors.map(|authors| {
+        authors
+            .into_iter()
+            .filter_map(|author| Author::try_from(author).ok())
+            .collect()
+    }))
+}
+
+/// The ORCID iD in a word, e.g. "0000-0002-1825-0097" in "<https://orcid.org/0000-0002-1825-0097>"
+fn find_orcid(word: &str) -> Option<String> {
+    let chars: Vec<char> = word.chars().collect();
+    chars.windows(19).find_map(|window| {
+        let is_orcid = window.iter().enumerate().all(|(i, c)| match i {
+            4 | 9 | 14 => *c == '-',
+            18 => c.is_ascii_digit() || *c == 'X',
+            _ => c.is_ascii_digit(),
+        });
+        Some(window.iter().collect()).filter(|_| is_orcid)
+    })
+}
+
+/// Whether a word is a particle, i.e. it starts with a lowercase letter (as in BibTeX)
+fn is_particle(word: &str) -> bool {
+    word.chars().next().is_some_and(char::is_lowercase)
+}
+
+/// Whether the part after a comma is a suffix rather than the given names
+fn is_suffix(part: &str) -> bool {
+    SUFFIXES.contains(&part.trim_end_matches('.').to_lowercase().as_str())
+}
+
+/// Split "particle Family" into the particle and the family name
+///
+/// The last word is always in the family name, e.g. "van" is a family name by itself.
+fn split_particle(words: &[&str]) -> (Option<String>, String) {
+    let count = words[..words.len().saturating_sub(1)]
+        .iter()
+        .take_while(|word| is_particle(word))
+        .count();
+    let particle = Some(words[..count].join(" ")).filter(|p| !p.is_empty());
+    (particle, words[count..].join(" "))
+}
+
+impl Author {
+    /// Author with only a family name, e.g. an organization
+    pub fn literal(name: &str) -> Self {
+        Self {
+            family: name.split_whitespace().collect::<Vec<_>>().join(" "),
+            ..Self::default()
+        }
+    }
+
+    /// Parse a name in any of the common forms
+    ///
+    /// "Given Family", "Family, Given", "Family, Suffix, Given" (as in BibTeX) and
+    /// "Given Family, Suffix" are accepted, where lowercase words before the family name
+    /// are the particle (e.g. "Ludwig van Beethoven" or "van Beethoven, Ludwig").
+    /// A name in braces (e.g. "{Barnes and Noble}") is not split, and an ORCID iD
+    /// anywhere (e.g. "Wuqiong Zhao <https://orcid.org/0000-0002-1825-0097>") is extracted.
+    /// Returns `None` if there is no name.
+    pub fn parse(name: &str) -> Option<Self> {
+        // 1. the ORCID iD with its label and brackets, e.g. "(ORCID: 0000-...)"
+        let mut orcid = None;
+        let mut words = vec![];
+        for word in name.split_whitespace() {
+            let core = word.trim_matches(|c: char| "()[]<>:".contains(c));
+            if let Some(found) = find_orcid(word) {
+                orcid = Some(found);
+            } else if !core.is_empty() && !core.eq_ignore_ascii_case("orcid") {
+                words.push(word);
+            }
+        }
+        let name = words.join(" ");
+        let name = name.trim_matches(|c: char| c == ',' || c.is_whitespace());
+        // 2. the parts of the name
+        let mut author = if name.starts_with('{') && name.ends_with('}') {
+            Self::literal(&name[1..name.len() - 1])
+        } else {
+            let parts: Vec<&str> = name.split(',').map(str::trim).collect();
+            match parts.as_slice() {
+                [name] => Self::parse_given_first(name),
+                [name, suffix] if is_suffix(suffix) => Self {
+                    suffix: Some(suffix.to_string()),
+                    ..Self::parse_given_first(name)
+                },
+                [family, rest @ ..] => {
+                    let family_words: Vec<&str> = family.split_whitespace().collect();
+                    let (particle, family) = split_particle(&family_words);
+                    let (suffix, given) = match rest {
+                        [given] => (None, given.to_string()),
+                        [suffix, given @ ..] => (Some(suffix.to_string()), given.join(", ")),
+                        [] => (None, String::new()),
+                    };
+                    Self {
+                        family,
+                        given: Some(given).filter(|given| !given.is_empty()),
+                        particle,
+                        suffix: suffix.filter(|suffix| !suffix.is_empty()),
+                        orcid: None,
+                    }
+                }
+                [] => Self::default(),
+            }
+        };
+        author.orcid = orcid;
+        // e.g. ", Given" has no family name, so the given name is used instead
+        if author.family.is_empty() {
+            author.family = author.given.take().unwrap_or_default();
+        }
+        Some(author).filter(|author| !author.family.is_empty())
+    }
+
+    /// Parse "Given particle Family", where the last word is the family name
+    /// unless it is preceded by a particle (e.g. "Jean de La Fontaine")
+    fn parse_given_first(name: &str) -> Self {
+        let words: Vec<&str> = name.split_whitespace().collect();
+        let last = words.len().saturating_sub(1);
+        let start = words[..last]
+            .iter()
+            .skip(1)
+            .position(|word| is_particle(word))
+            .map_or(last, |i| i + 1);
+        let (particle, family) = split_particle(&words[start..]);
+        Self {
+            family,
+            given: Some(words[..start].join(" ")).filter(|given| !given.is_empty()),
+            particle,
+            ..Self::default()
+        }
+    }
+
+    /// Family name with the particle, e.g. "van Beethoven"
+    pub fn family_name(&self) -> String {
+        match &self.particle {
+            Some(particle) => format!("{} {}", particle, self.family),
+            None => self.family.clone(),
+        }
+    }
+}
+
+/// Names of the authors joined by the separator, e.g. "Wuqiong Zhao; Li You"
+pub fn join(authors: &[Author], separator: &str) -> String {
+    authors
+        .iter()
+        .map(|author| author.to_string())
+        .collect::<Vec<_>>()
+        .join(separator)
+}
+
+/// The name as written in the text, e.g. "Martin Luther King, Jr."
+impl std::fmt::Display for Author {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(given) = &self.given {
+            write!(f, "{} ", given)?;
+        }
+        write!(f, "{}", self.family_name())?;
+        if let Some(suffix) = &self.suffix {
+            write!(f, ", {}", suffix)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::str::FromStr for Author {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Self::parse(name).ok_or_else(|| "expected a name".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parts(name: &str) -> (String, Option<String>, Option<String>, Option<String>) {
+        let author = Author::parse(name).unwrap();
+        (author.family, author.given, author.particle, author.suffix)
+    }
+
+    fn some(text: &str) -> Option<String> {
+        Some(text.to_string())
+    }
+
+    #[test]
+    fn name_forms() {
+        let beethoven = ("Beethoven".to_string(), some("Ludwig"), some("van"), None);
+        assert_eq!(parts("Ludwig van Beethoven"), beethoven);
+        assert_eq!(parts("van Beethoven, Ludwig"), beethoven);
+        let king = ("King".to_string(), some("Martin Luther"), None, some("Jr."));
+        assert_eq!(parts("Martin Luther King, Jr."), king);
+        assert_eq!(parts("King, Jr., Martin Luther"), king);
+        assert_eq!(parts("King, Martin Luther, Jr.").0, "King");
+        assert_eq!(
+            parts("Jean de La Fontaine"),
+            ("La Fontaine".to_string(), some("Jean"), some("de"), None)
+        );
+        assert_eq!(
+            parts("{Barnes and Noble}"),
+            ("Barnes and Noble".to_string(), None, None, None)
+        );
+        assert_eq!(parts("IEEE"), ("IEEE".to_string(), None, None, None));
+        assert_eq!(parts(", Plato"), ("Plato".to_string(), None, None, None));
+    }
+
+    #[test]
+    fn orcids() {
+        for name in [
+            "Wuqiong Zhao <https://orcid.org/0000-0002-1825-009X>",
+            "Wuqiong Zhao (ORCID: 0000-0002-1825-009X)",
+            "Zhao, Wuqiong, [0000-0002-1825-009X]",
+        ] {
+            let author = Author::parse(name).unwrap();
+            assert_eq!(author.to_string(), "Wuqiong Zhao", "{}", name);
+            assert_eq!(author.orcid.as_deref(), Some("0000-0002-1825-009X"));
+        }
+    }
+
+    #[test]
+    fn no_names() {
+        for name in ["", "  ", ",", "ORCID: 0000-0002-1825-0097", "{}"] {
+            assert_eq!(Author::parse(name), None, "{:?}", name);
+        }
+    }
+
+    #[test]
+    fn display() {
+        let authors: Vec<Author> = ["van Beethoven, Ludwig", "King, Jr., Martin Luther", "IEEE"]
+            .iter()
+            .map(|name| name.parse().unwrap())
+            .collect();
+        assert_eq!(
+            join(&authors, "; "),
+            "Ludwig van Beethoven; Martin Luther King, Jr.; IEEE"
+        );
+    }
+
+    #[test]
+    fn deserialize_index() {
+        #[derive(Deserialize)]
+        struct Entry {
+            #[serde(default, deserialize_with = "deserialize_authors")]
+            authors: Option<Vec<Author>>,
+        }
+        // plain names (written by older versions) are parsed, and those without a name dropped
+        let yaml = "authors:\n- Ludwig van Beethoven\n- '  '\n- family: Smith\n  given: Ann\n";
+        let entry: Entry = serde_yaml::from_str(yaml).unwrap();
+        let authors = entry.authors.unwrap();
+        assert_eq!(join(&authors, "; "), "Ludwig van Beethoven; Ann Smith");
+        let entry: Entry = serde_yaml::from_str("{}").unwrap();
+        assert_eq!(entry.authors, None);
+    }
+}
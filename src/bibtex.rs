@@ -0,0 +1,1276 @@
+use super::arxiv::ArxivId;
+use super::author::Author;
+use super::database::{
+    normalize_doi, normalize_pages, parse_month, EntryType, PaperEntry, PaperID,
+};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use unicode_normalization::UnicodeNormalization;
+
+/// LaTeX accent command of a combining mark
+fn accent_command(mark: char) -> Option<&'static str> {
+    match mark {
+        '\u{0300}' => Some("`"),
+        '\u{0301}' => Some("'"),
+        '\u{0302}' => Some("^"),
+        '\u{0303}' => Some("~"),
+        '\u{0304}' => Some("="),
+        '\u{0306}' => Some("u"),
+        '\u{0307}' => Some("."),
+        '\u{0308}' => Some("\""),
+        '\u{030A}' => Some("r"),
+        '\u{030B}' => Some("H"),
+        '\u{030C}' => Some("v"),
+        '\u{0323}' => Some("d"),
+        '\u{0327}' => Some("c"),
+        '\u{0328}' => Some("k"),
+        '\u{0331}' => Some("b"),
+        _ => None,
+    }
+}
+
+/// LaTeX command of a special letter or symbol
+fn symbol_command(c: char) -> Option<&'static str> {
+    match c {
+        'ß' => Some("{\\ss}"),
+        'æ' => Some("{\\ae}"),
+        'Æ' => Some("{\\AE}"),
+        'œ' => Some("{\\oe}"),
+        'Œ' => Some("{\\OE}"),
+        'ø' => Some("{\\o}"),
+        'Ø' => Some("{\\O}"),
+        'ł' => Some("{\\l}"),
+        'Ł' => Some("{\\L}"),
+        'ı' => Some("{\\i}"),
+        'ȷ' => Some("{\\j}"),
+        'đ' => Some("{\\dj}"),
+        'Đ' => Some("{\\DJ}"),
+        'þ' => Some("{\\th}"),
+        'Þ' => Some("{\\TH}"),
+        'ð' => Some("{\\dh}"),
+        'Ð' => Some("{\\DH}"),
+        '¡' => Some("{\\textexclamdown}"),
+        '¿' => Some("{\\textquestiondown}"),
+        '§' => Some("{\\S}"),
+        '¶' => Some("{\\P}"),
+        '©' => Some("{\\textcopyright}"),
+        '–' => Some("--"),
+        '—' => Some("---"),
+        '‘' => Some("`"),
+        '’' => Some("'"),
+        '“' => Some("``"),
+        '”' => Some("''"),
+        '…' => Some("{\\ldots}"),
+        '\u{00A0}' => Some("~"),
+        _ => None,
+    }
+}
+
+/// Escape the text for a BibTeX field
+///
+/// LaTeX special characters are escaped, and accented Latin letters are encoded
+/// as LaTeX accent commands (e.g. "é" as `{\'{e}}`), so that the output works with
+/// both BibTeX and BibLaTeX.
+/// Other non-ASCII characters (e.g. CJK) are kept as UTF-8.
+pub fn latex_escape(text: &str) -> String {
+    let mut escaped = String::new();
+    let mut chars = text.nfd().peekable();
+    while let Some(c) = chars.next() {
+        // 1. collect the combining marks following the base character
+        let mut marks = vec![];
+        while let Some(mark) = chars.peek().and_then(|m| accent_command(*m)) {
+            marks.push(mark);
+            chars.next();
+        }
+        // 2. the base character itself
+        let mut base = match c {
+            '\\' => "\\textbackslash{}".to_string(),
+            '{' | '}' | '&' | '%' | '$' | '#' | '_' => format!("\\{}", c),
+            '~' => "\\textasciitilde{}".to_string(),
+            '^' => "\\textasciicircum{}".to_string(),
+            'i' if !marks.is_empty() => "\\i".to_string(),
+            'j' if !marks.is_empty() => "\\j".to_string(),
+            c => match symbol_command(c) {
+                Some(command) => command.to_string(),
+                None => c.to_string(),
+            },
+        };
+        // 3. wrap with the accents, innermost first
+        for (i, mark) in marks.iter().enumerate() {
+            base = if i + 1 == marks.len() {
+                format!("{{\\{}{{{}}}}}", mark, base)
+            } else {
+                format!("\\{}{{{}}}", mark, base)
+            };
+        }
+        escaped.push_str(&base);
+    }
+    // recompose the remaining combining marks that have no LaTeX command
+    escaped.nfc().collect()
+}
+
+/// Escape a title for BibTeX, protecting the case of the words with capitals
+///
+/// The capitals of a title (e.g. acronyms and proper names) are kept as they are on import,
+/// so the words with them are put in braces (e.g. "{OMP} for beam"),
+/// which keeps styles that lowercase titles from changing them.
+/// The first word is not protected if only its first letter is a capital.
+fn latex_title(title: &str) -> String {
+    let mut words = vec![];
+    let mut protected: Vec<String> = vec![];
+    for (i, word) in title.split(' ').enumerate() {
+        let mut capitals = word.chars().map(char::is_uppercase);
+        let first_capital = capitals.next().unwrap_or(false);
+        let other_capital = capitals.any(|capital| capital);
+        if other_capital || (first_capital && i > 0) {
+            protected.push(latex_escape(word));
+            continue;
+        }
+        if !protected.is_empty() {
+            words.push(format!("{{{}}}", protected.join(" ")));
+            protected.clear();
+        }
+        words.push(latex_escape(word));
+    }
+    if !protected.is_empty() {
+        words.push(format!("{{{}}}", protected.join(" ")));
+    }
+    words.join(" ")
+}
+
+/// Kinds of theses, as the BibTeX entry type and the CSL genre
+const THESES: [(&str, &str); 2] = [
+    ("phdthesis", "PhD thesis"),
+    ("mastersthesis", "Master's thesis"),
+];
+
+/// Kind of a thesis, which is kept as the CSL genre in `PaperEntry::extra`
+fn thesis_genre(entry: &PaperEntry) -> Option<&str> {
+    entry.extra.as_ref()?.get("genre")?.as_str()
+}
+
+/// BibTeX entry type of a paper
+///
+/// A paper without a type is an article if it has a venue (written as the journal),
+/// and a thesis is a PhD thesis unless its genre says it is a master's thesis.
+fn entry_type(entry: &PaperEntry) -> &'static str {
+    match entry.entry_type {
+        Some(EntryType::Article) => "article",
+        Some(EntryType::Inproceedings) => "inproceedings",
+        Some(EntryType::Book) => "book",
+        Some(EntryType::Thesis) => match thesis_genre(entry) {
+            Some(genre) if genre.to_lowercase().contains("master") => "mastersthesis",
+            _ => "phdthesis",
+        },
+        Some(EntryType::Techreport) => "techreport",
+        None if entry.venue.is_some() => "article",
+        Some(EntryType::Misc) | None => "misc",
+    }
+}
+
+/// Entry type of a BibTeX (or BibLaTeX) entry type, if it has one
+fn from_entry_type(entry_type: &str) -> Option<EntryType> {
+    match entry_type {
+        "article" => Some(EntryType::Article),
+        "inproceedings" | "conference" => Some(EntryType::Inproceedings),
+        "book" => Some(EntryType::Book),
+        "phdthesis" | "mastersthesis" | "thesis" => Some(EntryType::Thesis),
+        "techreport" | "report" => Some(EntryType::Techreport),
+        "misc" | "online" | "unpublished" => Some(EntryType::Misc),
+        _ => None,
+    }
+}
+
+/// BibTeX field of the venue, which depends on the entry type
+///
+/// The venue of a book is its publisher (or the `booktitle` if a different publisher is set).
+fn venue_field(entry_type: Option<EntryType>) -> &'static str {
+    match entry_type {
+        Some(EntryType::Inproceedings) => "booktitle",
+        Some(EntryType::Book) => "publisher",
+        Some(EntryType::Thesis) => "school",
+        Some(EntryType::Techreport) => "institution",
+        Some(EntryType::Misc) => "howpublished",
+        Some(EntryType::Article) | None => "journal",
+    }
+}
+
+/// BibTeX month macros
+const MONTHS: [&str; 12] = [
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+
+/// Format a paper as a BibTeX entry, with the paper ID as the citation key
+pub fn to_bibtex(id: &PaperID, entry: &PaperEntry) -> String {
+    let mut fields: Vec<(&str, String)> = vec![];
+    if let Some(authors) = &entry.authors {
+        let authors: Vec<String> = authors.iter().map(bibtex_name).collect();
+        if !authors.is_empty() {
+            fields.push(("author", authors.join(" and ")));
+        }
+    }
+    if let Some(title) = &entry.title {
+        fields.push(("title", latex_title(title)));
+    }
+    if let Some(venue) = &entry.venue {
+        let field = match entry.entry_type {
+            Some(EntryType::Book) if entry.publisher.as_ref().is_some_and(|p| p != venue) => {
+                "booktitle"
+            }
+            entry_type => venue_field(entry_type),
+        };
+        fields.push((field, latex_escape(venue)));
+    }
+    if let Some(year) = entry.year {
+        fields.push(("year", year.to_string()));
+    }
+    if let Some(month) = entry.month.filter(|month| (1..=12).contains(month)) {
+        // the month macro, so that the style formats it
+        fields.push(("month", MONTHS[month as usize - 1].to_string()));
+    }
+    for (name, value) in [
+        ("volume", &entry.volume),
+        ("number", &entry.issue),
+        ("publisher", &entry.publisher),
+        ("isbn", &entry.isbn),
+        ("issn", &entry.issn),
+    ] {
+        // the publisher may have been written as the venue
+        if let Some(value) = value
+            .as_ref()
+            .filter(|_| fields.iter().all(|(f, _)| *f != name))
+        {
+            fields.push((name, latex_escape(value)));
+        }
+    }
+    if let Some(genre) = thesis_genre(entry).filter(|_| entry.entry_type == Some(EntryType::Thesis))
+    {
+        // the entry type is enough for the usual kinds of theses
+        if THESES.iter().all(|(_, kind)| *kind != genre) {
+            fields.push(("type", latex_escape(genre)));
+        }
+    }
+    if let Some(pages) = &entry.pages {
+        // page ranges are written with an en dash
+        fields.push(("pages", latex_escape(&pages.replace('-', "\u{2013}"))));
+    }
+    if let Some(doi) = &entry.doi {
+        // DOIs are verbatim fields in BibLaTeX, so they are not escaped
+        fields.push(("doi", doi.clone()));
+    }
+    if let Some(url) = &entry.url {
+        // so are URLs
+        fields.push(("url", url.clone()));
+    }
+    if let Some(arxiv) = &entry.arxiv {
+        fields.push(("eprint", arxiv.clone()));
+        fields.push(("archiveprefix", "arXiv".to_string()));
+    }
+    if let Some(keywords) = entry.keywords.as_ref().filter(|k| !k.is_empty()) {
+        fields.push(("keywords", latex_escape(&keywords.join(", "))));
+    }
+    if let Some(language) = &entry.language {
+        fields.push(("language", latex_escape(language)));
+    }
+    if let Some(abstract_text) = &entry.r#abstract {
+        fields.push(("abstract", latex_escape(abstract_text)));
+    }
+    let width = fields.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+    let mut bibtex = format!("@{}{{{},\n", entry_type(entry), id);
+    for (name, value) in fields {
+        // macros (i.e. the month) are not braced
+        let value = if name == "month" {
+            value
+        } else {
+            format!("{{{}}}", value)
+        };
+        bibtex.push_str(&format!("  {:<width$} = {},\n", name, value, width = width));
+    }
+    bibtex.push_str("}\n");
+    bibtex
+}
+
+/// Entry parsed from a BibTeX (or BibLaTeX) file
+#[derive(Debug, Clone)]
+pub struct BibEntry {
+    /// Entry type in lowercase, e.g. "article"
+    pub entry_type: String,
+    /// Citation key
+    pub key: String,
+    /// Fields with lowercase names and raw (LaTeX) values, in the order of the file
+    pub fields: Vec<(String, String)>,
+}
+
+impl BibEntry {
+    /// Raw value of a field
+    pub fn field(&self, name: &str) -> Option<&String> {
+        self.fields
+            .iter()
+            .find(|(field, _)| field == name)
+            .map(|(_, value)| value)
+    }
+
+    /// Decoded (plain text) value of a field, with whitespace collapsed
+    pub fn text(&self, name: &str) -> Option<String> {
+        self.field(name)
+            .map(|value| latex_decode(value))
+            .map(|value| value.split_whitespace().collect::<Vec<_>>().join(" "))
+            .filter(|value| !value.is_empty())
+    }
+
+    /// Names in a name list field (e.g. "author"), split at the top-level "and"
+    ///
+    /// A name in braces (e.g. "{IEEE}") is an organization, which is not split into parts.
+    pub fn names(&self, name: &str) -> Vec<Author> {
+        let value = match self.field(name) {
+            Some(value) => value,
+            None => return vec![],
+        };
+        let mut names = vec![];
+        let mut depth = 0;
+        let mut current = String::new();
+        let words: Vec<&str> = value.split_whitespace().collect();
+        for word in words {
+            if depth == 0 && word.eq_ignore_ascii_case("and") {
+                names.push(std::mem::take(&mut current));
+                continue;
+            }
+            let mut chars = word.chars();
+            while let Some(c) = chars.next() {
+                match c {
+                    '{' => depth += 1,
+                    '}' => depth -= 1,
+                    // escaped braces are not groups (as in the parser)
+                    '\\' => {
+                        chars.next();
+                    }
+                    _ => {}
+                }
+            }
+            if !current.is_empty() {
+                current.push(' ');
+            }
+            current.push_str(word);
+        }
+        names.push(current);
+        names
+            .iter()
+            .filter(|name| !name.is_empty() && *name != "others")
+            .filter_map(|name| {
+                let decoded = latex_decode(name);
+                if is_braced(name) {
+                    Some(Author::literal(&decoded)).filter(|author| !author.family.is_empty())
+                } else {
+                    Author::parse(&decoded)
+                }
+            })
+            .collect()
+    }
+}
+
+/// Check if the text is a single group in braces, e.g. "{Barnes and Noble}"
+fn is_braced(text: &str) -> bool {
+    let mut depth = 0;
+    let mut chars = text.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            _ if i == 0 => return false,
+            '\\' => {
+                chars.next();
+            }
+            _ => {}
+        }
+        // the group closes before the end
+        if depth == 0 && i + c.len_utf8() < text.len() {
+            return false;
+        }
+    }
+    text.len() > 1 && depth == 0
+}
+
+/// BibTeX form of a name, "von Last, Jr, First", which is always split correctly
+///
+/// Names with only a family name (e.g. "IEEE") are braced, so they are not split,
+/// and so are family names with a comma.
+fn bibtex_name(author: &Author) -> String {
+    let mut last = latex_escape(&author.family);
+    let only_family =
+        author.given.is_none() && author.particle.is_none() && author.suffix.is_none();
+    if only_family || last.contains(',') {
+        last = format!("{{{}}}", last);
+    }
+    if let Some(particle) = &author.particle {
+        last = format!("{} {}", latex_escape(particle), last);
+    }
+    match (&author.given, &author.suffix) {
+        (None, None) => last,
+        (Some(given), None) => format!("{}, {}", last, latex_escape(given)),
+        (given, Some(suffix)) => format!(
+            "{}, {}, {}",
+            last,
+            latex_escape(suffix),
+            latex_escape(given.as_deref().unwrap_or_default())
+        ),
+    }
+}
+
+/// Parser of BibTeX files
+struct BibParser {
+    chars: Vec<char>,
+    pos: usize,
+    /// Macros defined by @string (and the predefined month names)
+    macros: std::collections::HashMap<String, String>,
+    errors: Vec<String>,
+}
+
+impl BibParser {
+    fn new(input: &str) -> Self {
+        let months = [
+            "January",
+            "February",
+            "March",
+            "April",
+            "May",
+            "June",
+            "July",
+            "August",
+            "September",
+            "October",
+            "November",
+            "December",
+        ];
+        let macros = months
+            .iter()
+            .map(|month| (month[..3].to_lowercase(), month.to_string()))
+            .collect();
+        Self {
+            chars: input.chars().collect(),
+            pos: 0,
+            macros,
+            errors: vec![],
+        }
+    }
+
+    /// Line number of the current position (for error messages)
+    fn line(&self) -> usize {
+        self.chars[..self.pos.min(self.chars.len())]
+            .iter()
+            .filter(|c| **c == '\n')
+            .count()
+            + 1
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(|c| c.is_whitespace()) {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        self.skip_whitespace();
+        if self.peek() == Some(expected) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(format!("expected '{}' at line {}", expected, self.line()))
+        }
+    }
+
+    /// Read an identifier (entry type, field name or macro name)
+    fn identifier(&mut self) -> String {
+        self.skip_whitespace();
+        let mut identifier = String::new();
+        while let Some(c) = self.peek() {
+            if c.is_whitespace() || "{}(),=#\"@".contains(c) {
+                break;
+            }
+            identifier.push(c);
+            self.pos += 1;
+        }
+        identifier
+    }
+
+    /// Read a balanced group after the opening brace, returning the content
+    fn braced(&mut self) -> Result<String, String> {
+        let start = self.line();
+        let mut depth = 1;
+        let mut content = String::new();
+        while let Some(c) = self.peek() {
+            self.pos += 1;
+            match c {
+                '{' => depth += 1,
+                '}' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Ok(content);
+                    }
+                }
+                '\\' => {
+                    // keep escaped braces as they are
+                    content.push(c);
+                    if let Some(next) = self.peek() {
+                        content.push(next);
+                        self.pos += 1;
+                    }
+                    continue;
+                }
+                _ => {}
+            }
+            content.push(c);
+        }
+        Err(format!("unbalanced braces from line {}", start))
+    }
+
+    /// Read a quoted string after the opening quote
+    fn quoted(&mut self) -> Result<String, String> {
+        let start = self.line();
+        let mut depth = 0;
+        let mut content = String::new();
+        while let Some(c) = self.peek() {
+            self.pos += 1;
+            match c {
+                '{' => depth += 1,
+                '}' => depth -= 1,
+                '"' if depth == 0 => return Ok(content),
+                // be lenient with unbraced accents like \"o
+                '\\' if self.peek() == Some('"') => {
+                    content.push(c);
+                    content.push('"');
+                    self.pos += 1;
+                    continue;
+                }
+                _ => {}
+            }
+            content.push(c);
+        }
+        Err(format!("unterminated quote from line {}", start))
+    }
+
+    /// Read a field value, which is a concatenation (#) of strings, numbers and macros
+    fn value(&mut self) -> Result<String, String> {
+        let mut value = String::new();
+        loop {
+            self.skip_whitespace();
+            match self.peek() {
+                Some('{') => {
+                    self.pos += 1;
+                    value.push_str(&self.braced()?);
+                }
+                Some('"') => {
+                    self.pos += 1;
+                    value.push_str(&self.quoted()?);
+                }
+                Some(c) if c.is_ascii_digit() => {
+                    while let Some(c) = self.peek().filter(|c| c.is_ascii_digit()) {
+                        value.push(c);
+                        self.pos += 1;
+                    }
+                }
+                Some(_) => {
+                    let name = self.identifier();
+                    if name.is_empty() {
+                        return Err(format!("expected a value at line {}", self.line()));
+                    }
+                    match self.macros.get(&name.to_lowercase()) {
+                        Some(expansion) => value.push_str(expansion),
+                        None => {
+                            self.errors.push(format!(
+                                "undefined macro '{}' at line {}",
+                                name,
+                                self.line()
+                            ));
+                        }
+                    }
+                }
+                None => return Err("unexpected end of file".to_string()),
+            }
+            self.skip_whitespace();
+            if self.peek() == Some('#') {
+                self.pos += 1;
+            } else {
+                return Ok(value);
+            }
+        }
+    }
+
+    /// Parse an entry after '@', returning `None` for non-entries like @string
+    fn entry(&mut self) -> Result<Option<BibEntry>, String> {
+        let entry_type = self.identifier().to_lowercase();
+        self.skip_whitespace();
+        let close = match self.peek() {
+            Some('{') => '}',
+            Some('(') => ')',
+            _ => {
+                return Err(format!(
+                    "expected '{{' after '@{}' at line {}",
+                    entry_type,
+                    self.line()
+                ))
+            }
+        };
+        self.pos += 1;
+        match entry_type.as_str() {
+            "comment" | "preamble" => {
+                if close == '}' {
+                    self.braced()?;
+                } else {
+                    while self.peek().is_some_and(|c| c != ')') {
+                        self.pos += 1;
+                    }
+                    self.pos += 1;
+                }
+                Ok(None)
+            }
+            "string" => {
+                let name = self.identifier().to_lowercase();
+                self.expect('=')?;
+                let value = self.value()?;
+                self.macros.insert(name, value);
+                self.expect(close)?;
+                Ok(None)
+            }
+            _ => {
+                let key = self.identifier();
+                let mut fields = vec![];
+                loop {
+                    self.skip_whitespace();
+                    match self.peek() {
+                        Some(',') => {
+                            self.pos += 1;
+                        }
+                        Some(c) if c == close => {
+                            self.pos += 1;
+                            break;
+                        }
+                        Some(_) => {
+                            let name = self.identifier().to_lowercase();
+                            if name.is_empty() {
+                                return Err(format!(
+                                    "expected a field name in '{}' at line {}",
+                                    key,
+                                    self.line()
+                                ));
+                            }
+                            self.expect('=')?;
+                            let value = self.value()?;
+                            fields.push((name, value));
+                        }
+                        None => return Err(format!("unterminated entry '{}'", key)),
+                    }
+                }
+                Ok(Some(BibEntry {
+                    entry_type,
+                    key,
+                    fields,
+                }))
+            }
+        }
+    }
+
+    fn parse(mut self) -> (Vec<BibEntry>, Vec<String>) {
+        let mut entries = vec![];
+        while self.pos < self.chars.len() {
+            // anything outside of entries is a comment
+            if self.chars[self.pos] != '@' {
+                self.pos += 1;
+                continue;
+            }
+            self.pos += 1;
+            match self.entry() {
+                Ok(Some(entry)) => entries.push(entry),
+                Ok(None) => {}
+                Err(e) => self.errors.push(e),
+            }
+        }
+        (entries, self.errors)
+    }
+}
+
+/// Parse the BibTeX (or BibLaTeX) content
+///
+/// Macros defined by @string are expanded, and fields of the entry referred by
+/// `crossref` are inherited.
+/// Returns the entries and the errors of the entries that cannot be parsed.
+pub fn parse_bibtex(input: &str) -> (Vec<BibEntry>, Vec<String>) {
+    let (mut entries, errors) = BibParser::new(input).parse();
+    // inherit the fields from the cross-referenced entries
+    let parents: Vec<BibEntry> = entries.clone();
+    for entry in entries.iter_mut() {
+        let parent = match entry.field("crossref") {
+            Some(crossref) => parents
+                .iter()
+                .find(|parent| parent.key.eq_ignore_ascii_case(crossref.trim())),
+            None => continue,
+        };
+        if let Some(parent) = parent {
+            for (name, value) in &parent.fields {
+                // the title of a book or proceedings is the booktitle of its parts
+                let name = match (name.as_str(), parent.entry_type.as_str()) {
+                    ("title", "proceedings" | "book" | "collection" | "mvbook") => "booktitle",
+                    _ => name.as_str(),
+                };
+                if entry.field(name).is_none() {
+                    entry.fields.push((name.to_string(), value.clone()));
+                }
+            }
+        }
+        entry.fields.retain(|(name, _)| name != "crossref");
+    }
+    (entries, errors)
+}
+
+/// Combining mark of a LaTeX accent command
+fn accent_mark(command: &str) -> Option<char> {
+    match command {
+        "`" => Some('\u{0300}'),
+        "'" => Some('\u{0301}'),
+        "^" => Some('\u{0302}'),
+        "~" => Some('\u{0303}'),
+        "=" => Some('\u{0304}'),
+        "u" => Some('\u{0306}'),
+        "." => Some('\u{0307}'),
+        "\"" => Some('\u{0308}'),
+        "r" => Some('\u{030A}'),
+        "H" => Some('\u{030B}'),
+        "v" => Some('\u{030C}'),
+        "d" => Some('\u{0323}'),
+        "c" => Some('\u{0327}'),
+        "k" => Some('\u{0328}'),
+        "b" => Some('\u{0331}'),
+        _ => None,
+    }
+}
+
+/// Text of a LaTeX symbol command
+fn symbol_text(command: &str) -> Option<&'static str> {
+    match command {
+        "ss" => Some("ß"),
+        "ae" => Some("æ"),
+        "AE" => Some("Æ"),
+        "oe" => Some("œ"),
+        "OE" => Some("Œ"),
+        "o" => Some("ø"),
+        "O" => Some("Ø"),
+        "l" => Some("ł"),
+        "L" => Some("Ł"),
+        "i" => Some("ı"),
+        "j" => Some("ȷ"),
+        "aa" => Some("å"),
+        "AA" => Some("Å"),
+        "dj" => Some("đ"),
+        "DJ" => Some("Đ"),
+        "th" => Some("þ"),
+        "TH" => Some("Þ"),
+        "dh" => Some("ð"),
+        "DH" => Some("Ð"),
+        "textexclamdown" => Some("¡"),
+        "textquestiondown" => Some("¿"),
+        "S" => Some("§"),
+        "P" => Some("¶"),
+        "textcopyright" => Some("©"),
+        "ldots" | "dots" | "textellipsis" => Some("…"),
+        "textbackslash" => Some("\\"),
+        "textasciitilde" => Some("~"),
+        "textasciicircum" => Some("^"),
+        "textendash" => Some("–"),
+        "textemdash" => Some("—"),
+        _ => None,
+    }
+}
+
+/// Decode the LaTeX markup of a BibTeX value into plain text
+///
+/// Accent commands like `\'e`, `{\"o}` and `\v{c}` become accented letters,
+/// escaped special characters and symbols are converted,
+/// and other commands (e.g. `\emph`) and braces are dropped, keeping their arguments.
+pub fn latex_decode(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut decoded = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            '\\' => {
+                i += 1;
+                // 1. read the command name
+                let mut command = String::new();
+                while i < chars.len() && chars[i].is_ascii_alphabetic() {
+                    command.push(chars[i]);
+                    i += 1;
+                }
+                if command.is_empty() && i < chars.len() {
+                    command.push(chars[i]);
+                    i += 1;
+                }
+                let is_letter_command = command.chars().all(|c| c.is_ascii_alphabetic());
+                // 2. escaped characters
+                if "&%$#_{} ".contains(command.as_str()) && command.len() == 1 {
+                    decoded.push_str(&command);
+                    continue;
+                }
+                if command == "\\" {
+                    decoded.push(' ');
+                    continue;
+                }
+                // 3. accents with an argument
+                if let Some(mark) = accent_mark(&command) {
+                    if is_letter_command {
+                        while i < chars.len() && chars[i] == ' ' {
+                            i += 1;
+                        }
+                    }
+                    let argument = if i < chars.len() && chars[i] == '{' {
+                        let start = i + 1;
+                        let mut depth = 1;
+                        i += 1;
+                        while i < chars.len() && depth > 0 {
+                            match chars[i] {
+                                '{' => depth += 1,
+                                '}' => depth -= 1,
+                                _ => {}
+                            }
+                            i += 1;
+                        }
+                        // an unterminated group lasts until the end
+                        let end = if depth == 0 { i - 1 } else { i };
+                        chars[start..end].iter().collect::<String>()
+                    } else if i < chars.len() && chars[i] == '\\' {
+                        // e.g. \'\i
+                        let start = i;
+                        i += 1;
+                        while i < chars.len() && chars[i].is_ascii_alphabetic() {
+                            i += 1;
+                        }
+                        chars[start..i].iter().collect::<String>()
+                    } else if i < chars.len() {
+                        i += 1;
+                        chars[i - 1].to_string()
+                    } else {
+                        String::new()
+                    };
+                    let argument = latex_decode(&argument);
+                    let mut argument_chars = argument.chars();
+                    if let Some(first) = argument_chars.next() {
+                        // dotless i and j take the accents as normal letters
+                        let first = match first {
+                            'ı' => 'i',
+                            'ȷ' => 'j',
+                            c => c,
+                        };
+                        let accented: String = [first, mark].iter().collect();
+                        decoded.extend(accented.nfc());
+                        decoded.extend(argument_chars);
+                    }
+                    continue;
+                }
+                // 4. symbols (a following empty group or space ends the command)
+                if let Some(symbol) = symbol_text(&command) {
+                    decoded.push_str(symbol);
+                    if i + 1 < chars.len() && chars[i] == '{' && chars[i + 1] == '}' {
+                        i += 2;
+                    } else if is_letter_command && i < chars.len() && chars[i] == ' ' {
+                        i += 1;
+                    }
+                    continue;
+                }
+                // 5. other commands are dropped, keeping the arguments
+                if is_letter_command && i < chars.len() && chars[i] == ' ' {
+                    i += 1;
+                }
+            }
+            '{' | '}' => i += 1,
+            '~' => {
+                decoded.push('\u{00A0}');
+                i += 1;
+            }
+            '-' if chars.get(i + 1) == Some(&'-') => {
+                if chars.get(i + 2) == Some(&'-') {
+                    decoded.push('—');
+                    i += 3;
+                } else {
+                    decoded.push('–');
+                    i += 2;
+                }
+            }
+            '`' if chars.get(i + 1) == Some(&'`') => {
+                decoded.push('“');
+                i += 2;
+            }
+            '\'' if chars.get(i + 1) == Some(&'\'') => {
+                decoded.push('”');
+                i += 2;
+            }
+            _ => {
+                decoded.push(c);
+                i += 1;
+            }
+        }
+    }
+    decoded.nfc().collect()
+}
+
+impl BibEntry {
+    /// Year of the entry, from `year` or the BibLaTeX `date` (e.g. "2024-05-01")
+    pub fn year(&self) -> Option<u32> {
+        let value = self.text("year").or_else(|| self.text("date"))?;
+        let digits: String = value
+            .chars()
+            .skip_while(|c| !c.is_ascii_digit())
+            .take_while(|c| c.is_ascii_digit())
+            .collect();
+        digits.parse().ok()
+    }
+
+    /// DOI of the entry, without the resolver prefix
+    pub fn doi(&self) -> Option<String> {
+        normalize_doi(self.field("doi")?)
+    }
+
+    /// The PDF file in the `file` field, resolved relative to the directory of the BibTeX file
+    ///
+    /// Both plain paths (separated by ';') and the JabRef/Mendeley form
+    /// `description:path:type` are accepted.
+    pub fn pdf_file(&self, base_dir: &Path) -> Option<PathBuf> {
+        let value = self.field("file")?;
+        let candidates: Vec<String> = value
+            .split(';')
+            .map(|item| {
+                let item = item.replace("\\:", "\u{0}");
+                let parts: Vec<&str> = item.split(':').collect();
+                let path = match parts.len() {
+                    3 => parts[1].to_string(),
+                    // Windows drive letters, e.g. ":C:\paper.pdf:PDF"
+                    4 if parts[1].len() == 1 => format!("{}:{}", parts[1], parts[2]),
+                    _ => item.clone(),
+                };
+                path.replace('\u{0}', ":")
+                    .replace("\\_", "_")
+                    .trim()
+                    .to_string()
+            })
+            .filter(|path| !path.is_empty())
+            .collect();
+        candidates
+            .iter()
+            .find(|path| path.to_lowercase().ends_with(".pdf"))
+            .or(candidates.first())
+            .map(|path| {
+                let path = PathBuf::from(path);
+                let resolved = if path.is_absolute() {
+                    path.clone()
+                } else {
+                    base_dir.join(&path)
+                };
+                // Mendeley drops the leading '/' of absolute paths
+                let rooted = Path::new("/").join(&path);
+                if !resolved.exists() && rooted.exists() {
+                    rooted
+                } else {
+                    resolved
+                }
+            })
+    }
+
+    /// Kind of a thesis, from the `type` field or the entry type (e.g. "Master's thesis")
+    ///
+    /// The BibLaTeX types `mathesis` and `phdthesis` are named as the BibTeX entry types.
+    fn thesis_genre(&self) -> Option<String> {
+        let kind = |entry_type: &str| {
+            THESES
+                .iter()
+                .find(|(ty, _)| *ty == entry_type)
+                .map(|(_, kind)| kind.to_string())
+        };
+        match self.text("type") {
+            Some(genre) if self.entry_type.ends_with("thesis") => match genre.as_str() {
+                "mathesis" => kind("mastersthesis"),
+                "phdthesis" => kind("phdthesis"),
+                _ => Some(genre),
+            },
+            _ => kind(&self.entry_type),
+        }
+    }
+
+    /// Paper entry of the BibTeX entry (the file is not checked)
+    pub fn to_paper_entry(&self, base_dir: &Path) -> PaperEntry {
+        let authors = self.names("author");
+        let arxiv = self
+            .field("eprint")
+            .filter(|_| {
+                self.field("archiveprefix")
+                    .or_else(|| self.field("eprinttype"))
+                    .is_some_and(|prefix| prefix.eq_ignore_ascii_case("arxiv"))
+            })
+            .and_then(|eprint| ArxivId::parse(eprint).ok());
+        let entry_type = from_entry_type(&self.entry_type);
+        let keywords: Vec<String> = self
+            .text("keywords")
+            .unwrap_or_default()
+            .split([',', ';'])
+            .map(|keyword| keyword.trim().to_string())
+            .filter(|keyword| !keyword.is_empty())
+            .collect();
+        PaperEntry {
+            entry_type,
+            doi: self.doi(),
+            title: self.text("title"),
+            authors: Some(authors).filter(|authors| !authors.is_empty()),
+            year: self.year(),
+            month: self.text("month").as_deref().and_then(parse_month),
+            venue: match entry_type {
+                Some(EntryType::Book) => self
+                    .text("booktitle")
+                    .or_else(|| self.text("publisher"))
+                    .or_else(|| self.text("series")),
+                entry_type => self.text(venue_field(entry_type)),
+            }
+            .or_else(|| self.text("journal"))
+            .or_else(|| self.text("journaltitle"))
+            .or_else(|| self.text("booktitle")),
+            volume: self.text("volume"),
+            issue: self.text("number").or_else(|| self.text("issue")),
+            pages: self.text("pages").as_deref().and_then(normalize_pages),
+            publisher: self.text("publisher"),
+            r#abstract: self.text("abstract"),
+            keywords: Some(keywords).filter(|keywords| !keywords.is_empty()),
+            url: self
+                .field("url")
+                .map(|url| url.trim().to_string())
+                .filter(|url| !url.is_empty()),
+            isbn: self.text("isbn"),
+            issn: self.text("issn"),
+            language: self.text("language").or_else(|| self.text("langid")),
+            arxiv: arxiv.as_ref().map(|arxiv| arxiv.id.clone()),
+            arxiv_version: arxiv.and_then(|arxiv| arxiv.version),
+            file: self
+                .pdf_file(base_dir)
+                .and_then(|file| file.to_str().map(|file| file.to_string())),
+            extra: self.thesis_genre().map(|genre| {
+                BTreeMap::from([("genre".to_string(), serde_json::Value::String(genre))])
+            }),
+            ..PaperEntry::new()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_one(input: &str) -> BibEntry {
+        let (entries, errors) = parse_bibtex(input);
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert_eq!(entries.len(), 1);
+        entries.into_iter().next().unwrap()
+    }
+
+    fn names(entry: &BibEntry) -> Vec<String> {
+        entry
+            .names("author")
+            .iter()
+            .map(|author| author.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn parse_entries() {
+        let input = r#"
+            Comments are skipped.
+            @string{ieee = "IEEE Trans."}
+            @comment{ @article{skipped, title = {Skipped}} }
+            @Article(zhao2024,
+                Title = {Beam {Squint} in \emph{Near-Field}},
+                journal = ieee # " Wireless Commun.",
+                year = 2024,
+                month = oct,
+            )
+        "#;
+        let entry = parse_one(input);
+        assert_eq!(entry.entry_type, "article");
+        assert_eq!(entry.key, "zhao2024");
+        assert_eq!(entry.text("title").unwrap(), "Beam Squint in Near-Field");
+        assert_eq!(
+            entry.text("journal").unwrap(),
+            "IEEE Trans. Wireless Commun."
+        );
+        assert_eq!(entry.year(), Some(2024));
+        assert_eq!(entry.to_paper_entry(Path::new(".")).month, Some(10));
+    }
+
+    #[test]
+    fn parse_errors() {
+        let (entries, errors) =
+            parse_bibtex("@article{bad, title = {Unterminated}\n@misc{good, title = {Ok}}");
+        assert_eq!(errors.len(), 1);
+        assert!(entries.iter().all(|entry| entry.key != "bad"));
+    }
+
+    #[test]
+    fn crossref() {
+        let (entries, _) = parse_bibtex(
+            "@inproceedings{part, title = {Part}, crossref = {conf}}
+             @proceedings{conf, title = {Proceedings}, year = 2024}",
+        );
+        let part = &entries[0];
+        assert_eq!(part.text("booktitle").unwrap(), "Proceedings");
+        assert_eq!(part.text("title").unwrap(), "Part");
+        assert_eq!(part.year(), Some(2024));
+        assert!(part.field("crossref").is_none());
+    }
+
+    #[test]
+    fn name_lists() {
+        let entry = parse_one(
+            r#"@misc{x, author = {van Beethoven, Ludwig and {IEEE} and King, Jr., Martin Luther
+                and {Barnes and Noble} and others}}"#,
+        );
+        assert_eq!(
+            names(&entry),
+            [
+                "Ludwig van Beethoven",
+                "IEEE",
+                "Martin Luther King, Jr.",
+                "Barnes and Noble"
+            ]
+        );
+    }
+
+    #[test]
+    fn name_lists_with_escaped_braces() {
+        // an escaped brace is not a group, so the names are still split
+        let entry = parse_one(r#"@misc{x, author = {A. B\{c and D. E\}f and {G\} and H}}}"#);
+        assert_eq!(names(&entry), ["A. B{c", "D. E}f", "G} and H"]);
+    }
+
+    #[test]
+    fn decode_latex() {
+        assert_eq!(
+            latex_decode(r#"M{\"u}ller \'{E}mile \v c \'\i"#),
+            "Müller Émile č í"
+        );
+        assert_eq!(
+            latex_decode(r"\emph{A} \& B --- C -- D~E"),
+            "A & B — C – D\u{00A0}E"
+        );
+        assert_eq!(latex_decode(r"``quoted''"), "“quoted”");
+        assert_eq!(latex_decode(r"\ss{}e"), "ße");
+    }
+
+    #[test]
+    fn decode_unterminated_latex() {
+        // regression: these used to panic
+        assert_eq!(latex_decode(r"\'{"), "");
+        assert_eq!(latex_decode(r"\'{e"), "é");
+        assert_eq!(latex_decode(r"\'"), "");
+        assert_eq!(latex_decode("\\"), "");
+    }
+
+    #[test]
+    fn escape_round_trip() {
+        for text in ["Müller & Co. 100% {x}", "Dvořák_1 #2", "café naïve"] {
+            assert_eq!(latex_decode(&latex_escape(text)), text);
+        }
+    }
+
+    #[test]
+    fn protect_title_case() {
+        assert_eq!(latex_title("OMP for beam"), "{OMP} for beam");
+        assert_eq!(
+            latex_title("Beam training for Near-Field MIMO & iPhone"),
+            r"Beam training for {Near-Field MIMO} \& {iPhone}"
+        );
+        assert_eq!(latex_title("Émile and 5G"), r"{\'{E}}mile and {5G}");
+        // a title round-trips, along with the protection of its words
+        let entry =
+            parse_one(r"@article{x, title = {{OMP} for \emph{beam} in {Near-Field} {MIMO}}}");
+        let entry = entry.to_paper_entry(Path::new("."));
+        assert_eq!(
+            entry.title.as_deref(),
+            Some("OMP for beam in Near-Field MIMO")
+        );
+        let bibtex = to_bibtex(&"x".to_string(), &entry);
+        assert!(
+            bibtex.contains("title = {{OMP} for beam in {Near-Field MIMO}}"),
+            "{}",
+            bibtex
+        );
+        let parsed = parse_one(&bibtex).to_paper_entry(Path::new("."));
+        assert_eq!(parsed.title, entry.title);
+    }
+
+    #[test]
+    fn export_types() {
+        let mut entry = PaperEntry {
+            title: Some("Title".to_string()),
+            ..PaperEntry::new()
+        };
+        assert!(to_bibtex(&"x".to_string(), &entry).starts_with("@misc{x,"));
+        entry.venue = Some("Journal".to_string());
+        let bibtex = to_bibtex(&"x".to_string(), &entry);
+        assert!(bibtex.starts_with("@article{x,"));
+        assert!(bibtex.contains("journal = {Journal}"));
+    }
+
+    #[test]
+    fn thesis_kinds() {
+        for (input, kind, output) in [
+            (
+                "@mastersthesis{x, school = {MIT}}",
+                "Master's thesis",
+                "@mastersthesis{x,",
+            ),
+            (
+                "@phdthesis{x, school = {MIT}}",
+                "PhD thesis",
+                "@phdthesis{x,",
+            ),
+            (
+                "@thesis{x, type = {mathesis}}",
+                "Master's thesis",
+                "@mastersthesis{x,",
+            ),
+            (
+                "@thesis{x, type = {Habilitation}}",
+                "Habilitation",
+                "@phdthesis{x,",
+            ),
+        ] {
+            let entry = parse_one(input).to_paper_entry(Path::new("."));
+            assert_eq!(entry.entry_type, Some(EntryType::Thesis));
+            assert_eq!(thesis_genre(&entry), Some(kind));
+            let bibtex = to_bibtex(&"x".to_string(), &entry);
+            assert!(bibtex.starts_with(output), "{}", bibtex);
+            let parsed = parse_one(&bibtex).to_paper_entry(Path::new("."));
+            assert_eq!(thesis_genre(&parsed), Some(kind));
+        }
+    }
+
+    #[test]
+    fn book_venues() {
+        let entry = parse_one("@book{x, publisher = {Springer}}").to_paper_entry(Path::new("."));
+        assert_eq!(entry.venue.as_deref(), Some("Springer"));
+        let bibtex = to_bibtex(&"x".to_string(), &entry);
+        assert_eq!(bibtex.matches("Springer").count(), 1, "{}", bibtex);
+        assert!(bibtex.contains("publisher = {Springer}"));
+        // a venue other than the publisher is kept as the book title
+        let entry = PaperEntry {
+            venue: Some("Lecture Notes".to_string()),
+            ..entry
+        };
+        let bibtex = to_bibtex(&"x".to_string(), &entry);
+        assert!(bibtex.contains("booktitle = {Lecture Notes}"), "{}", bibtex);
+        assert!(bibtex.contains("publisher = {Springer}"), "{}", bibtex);
+        assert!(!bibtex.contains("series"));
+        let parsed = parse_one(&bibtex).to_paper_entry(Path::new("."));
+        assert_eq!(
+            (parsed.venue, parsed.publisher),
+            (entry.venue, entry.publisher)
+        );
+    }
+}
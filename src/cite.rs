@@ -0,0 +1,339 @@
+use super::author::Author;
+use super::csl;
+use super::database::{PaperEntry, PaperID};
+use super::options::CiteFormat;
+use hayagriva::archive::{locales, ArchivedStyle};
+use hayagriva::citationberg::{json::Item, FontStyle, FontWeight, IndependentStyle, Style};
+use hayagriva::{
+    BibliographyDriver, BibliographyRequest, BufWriteFormat, CitationItem, CitationRequest,
+    ElemChild, ElemChildren,
+};
+
+/// Abbreviate an author name as "G. Family", e.g. "L. van Beethoven" or "M. L. King Jr."
+fn abbreviate(author: &Author) -> String {
+    let mut family = author.family_name();
+    if let Some(suffix) = &author.suffix {
+        family = format!("{} {}", family, suffix);
+    }
+    let initials: Vec<String> = author
+        .given
+        .iter()
+        .flat_map(|given| given.split_whitespace())
+        .map(|part| {
+            // keep hyphenated given names like "Jean-Paul" as "J.-P."
+            part.split('-')
+                .filter_map(|p| p.chars().next())
+                .map(|c| format!("{}.", c))
+                .collect::<Vec<_>>()
+                .join("-")
+        })
+        .collect();
+    if initials.is_empty() {
+        family
+    } else {
+        format!("{} {}", initials.join(" "), family)
+    }
+}
+
+/// Join names as "A", "A and B" or "A, B, and C"
+fn join_names(names: &[String]) -> String {
+    match names.len() {
+        0 => String::new(),
+        1 => names[0].clone(),
+        2 => format!("{} and {}", names[0], names[1]),
+        n => format!("{}, and {}", names[..n - 1].join(", "), names[n - 1]),
+    }
+}
+
+/// Plain text citation of a paper (in the IEEE style)
+pub fn plain_citation(entry: &PaperEntry) -> String {
+    let mut parts = vec![];
+    if let Some(authors) = &entry.authors {
+        let names: Vec<String> = authors.iter().map(abbreviate).collect();
+        if !names.is_empty() {
+            parts.push(join_names(&names));
+        }
+    }
+    if let Some(title) = &entry.title {
+        parts.push(format!("\u{201c}{},\u{201d}", title));
+    }
+    if let Some(venue) = &entry.venue {
+        parts.push(venue.clone());
+    }
+    if let Some(volume) = &entry.volume {
+        parts.push(format!("vol. {}", volume));
+    }
+    if let Some(issue) = &entry.issue {
+        parts.push(format!("no. {}", issue));
+    }
+    if let Some(pages) = &entry.pages {
+        // a single page (or an article number) has no range
+        let prefix = if pages.contains('-') { "pp." } else { "p." };
+        parts.push(format!("{} {}", prefix, pages.replace('-', "\u{2013}")));
+    }
+    if let Some(year) = entry.year {
+        parts.push(year.to_string());
+    }
+    if let Some(doi) = &entry.doi {
+        parts.push(format!("doi: {}", doi));
+    }
+    let mut citation = parts.join(", ").replace(",\u{201d},", ",\u{201d}");
+    if !citation.is_empty() && !citation.ends_with('\u{201d}') {
+        citation.push('.');
+    }
+    citation
+}
+
+/// Default CSL style of the 'cite' command
+pub const DEFAULT_STYLE: &str = "ieee";
+
+/// Names of the CSL styles bundled with termipaper
+pub fn bundled_styles() -> Vec<&'static str> {
+    let mut names: Vec<&str> = ArchivedStyle::all()
+        .iter()
+        .map(|style| style.names()[0])
+        .collect();
+    names.sort();
+    names
+}
+
+/// Load a CSL style, either bundled (by name, e.g. "apa") or from a `.csl` file
+///
+/// Dependent styles are resolved to their bundled parent styles.
+pub fn load_style(style: &str) -> Result<IndependentStyle, String> {
+    let path = std::path::Path::new(style);
+    let style = if style.ends_with(".csl") || path.is_file() {
+        let xml =
+            std::fs::read_to_string(path).map_err(|e| format!("cannot read '{}': {}", style, e))?;
+        Style::from_xml(&xml).map_err(|e| format!("invalid CSL style '{}': {}", style, e))?
+    } else {
+        ArchivedStyle::by_name(style)
+            .ok_or_else(|| format!("unknown style '{}'", style))?
+            .get()
+    };
+    match style {
+        Style::Independent(style) => Ok(style),
+        Style::Dependent(dependent) => {
+            let parent = &dependent.parent_link.href;
+            match ArchivedStyle::by_id(parent).map(ArchivedStyle::get) {
+                Some(Style::Independent(mut style)) => {
+                    if dependent.default_locale.is_some() {
+                        style.default_locale = dependent.default_locale;
+                    }
+                    Ok(style)
+                }
+                _ => Err(format!("the parent style '{}' is not bundled", parent)),
+            }
+        }
+    }
+}
+
+/// Write the rendered elements as Markdown (italic, bold and links)
+fn write_markdown(children: &ElemChildren, out: &mut String) {
+    let escape = |text: &str| {
+        text.chars().fold(String::new(), |mut escaped, c| {
+            if "\\*_[]<>`".contains(c) {
+                escaped.push('\\');
+            }
+            escaped.push(c);
+            escaped
+        })
+    };
+    for child in &children.0 {
+        match child {
+            ElemChild::Text(formatted) => {
+                let mut marker = String::new();
+                if formatted.formatting.font_style == FontStyle::Italic {
+                    marker.push('*');
+                }
+                if formatted.formatting.font_weight == FontWeight::Bold {
+                    marker.push_str("**");
+                }
+                let text = escape(&formatted.text);
+                // markers cannot enclose the surrounding spaces
+                let trimmed = text.trim();
+                if marker.is_empty() || trimmed.is_empty() {
+                    out.push_str(&text);
+                } else {
+                    let start = text.len() - text.trim_start().len();
+                    out.push_str(&text[..start]);
+                    out.push_str(&format!("{}{}{}", marker, trimmed, marker));
+                    out.push_str(&text[start + trimmed.len()..]);
+                }
+            }
+            ElemChild::Elem(elem) => write_markdown(&elem.children, out),
+            ElemChild::Markup(markup) => out.push_str(markup),
+            ElemChild::Link { text, url } => {
+                out.push_str(&format!("[{}]({})", escape(&text.text), url));
+            }
+            ElemChild::Transparent { .. } => {}
+        }
+    }
+}
+
+/// Write the rendered elements in the output format
+fn write_children(children: &ElemChildren, format: CiteFormat) -> String {
+    let mut out = String::new();
+    match format {
+        CiteFormat::Plain => children.write_buf(&mut out, BufWriteFormat::Plain).unwrap(),
+        CiteFormat::Html => children.write_buf(&mut out, BufWriteFormat::Html).unwrap(),
+        CiteFormat::Markdown => write_markdown(children, &mut out),
+    }
+    out
+}
+
+/// Formatted references of the papers in the CSL style, in the order of the bibliography
+pub fn styled_citations(
+    papers: &[(&PaperID, &PaperEntry)],
+    style: &IndependentStyle,
+    format: CiteFormat,
+) -> Result<Vec<String>, String> {
+    // variables that the CSL processor does not understand (e.g. `custom`) are left out
+    let items: Vec<Item> = papers
+        .iter()
+        .map(|(id, entry)| match csl::to_csl(id, entry) {
+            serde_json::Value::Object(variables) => Item(
+                variables
+                    .into_iter()
+                    .filter_map(|(key, value)| Some((key, serde_json::from_value(value).ok()?)))
+                    .collect(),
+            ),
+            _ => unreachable!("CSL items are objects"),
+        })
+        .collect();
+    let locales = locales();
+    let mut driver: BibliographyDriver<'_, Item> = BibliographyDriver::new();
+    for item in &items {
+        driver.citation(CitationRequest::from_items(
+            vec![CitationItem::with_entry(item)],
+            style,
+            &locales,
+        ));
+    }
+    let rendered = driver.finish(BibliographyRequest::new(style, None, &locales));
+    let bibliography = rendered
+        .bibliography
+        .ok_or("the style has no bibliography")?;
+    Ok(bibliography
+        .items
+        .iter()
+        .map(|item| {
+            let content = write_children(&item.content, format);
+            match &item.first_field {
+                // e.g. the "[1]" of numeric styles
+                Some(first) => {
+                    let first = write_children(&ElemChildren(vec![first.clone()]), format);
+                    format!("{} {}", first, content)
+                }
+                None => content,
+            }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paper() -> PaperEntry {
+        PaperEntry {
+            title: Some("Beam Training for Near-Field Communications".to_string()),
+            authors: Some(vec![
+                Author::parse("Jean-Paul van Beethoven").unwrap(),
+                Author::parse("Martin Luther King, Jr.").unwrap(),
+                Author::literal("IEEE"),
+            ]),
+            year: Some(2024),
+            venue: Some("IEEE Trans. Wireless Commun.".to_string()),
+            volume: Some("23".to_string()),
+            issue: Some("5".to_string()),
+            pages: Some("1-6".to_string()),
+            doi: Some("10.1109/TWC.2024.1".to_string()),
+            ..PaperEntry::new()
+        }
+    }
+
+    #[test]
+    fn plain() {
+        assert_eq!(
+            plain_citation(&paper()),
+            "J.-P. van Beethoven, M. L. King Jr., and IEEE, \u{201c}Beam Training for Near-Field \
+             Communications,\u{201d} IEEE Trans. Wireless Commun., vol. 23, no. 5, pp. 1\u{2013}6, \
+             2024, doi: 10.1109/TWC.2024.1."
+        );
+        let entry = PaperEntry {
+            title: Some("Notes".to_string()),
+            pages: Some("e12".to_string()),
+            ..PaperEntry::new()
+        };
+        assert_eq!(plain_citation(&entry), "\u{201c}Notes,\u{201d} p. e12.");
+        assert_eq!(plain_citation(&PaperEntry::new()), "");
+    }
+
+    #[test]
+    fn names() {
+        let names = |n: usize| -> Vec<String> {
+            ["A", "B", "C"][..n].iter().map(|s| s.to_string()).collect()
+        };
+        assert_eq!(join_names(&names(1)), "A");
+        assert_eq!(join_names(&names(2)), "A and B");
+        assert_eq!(join_names(&names(3)), "A, B, and C");
+    }
+
+    #[test]
+    fn bundled_style() {
+        // styles are listed by their full names, and can be loaded by the short ones too
+        assert!(bundled_styles().contains(&"american-psychological-association"));
+        let style = load_style("apa").unwrap();
+        let id = "beethoven2024".to_string();
+        let entry = paper();
+        let citations = styled_citations(&[(&id, &entry)], &style, CiteFormat::Plain).unwrap();
+        assert_eq!(citations.len(), 1);
+        assert!(
+            citations[0].starts_with("van Beethoven, J.-P., King, M. L., Jr., & IEEE. (2024)."),
+            "{}",
+            citations[0]
+        );
+        // a paper without a type or venue has no CSL item type, but is still formatted
+        let entry = PaperEntry {
+            venue: None,
+            ..paper()
+        };
+        let citations = styled_citations(&[(&id, &entry)], &style, CiteFormat::Plain).unwrap();
+        assert!(
+            citations[0].contains("Beam Training for Near-Field Communications"),
+            "{}",
+            citations[0]
+        );
+        assert!(load_style("no-such-style").is_err());
+    }
+
+    #[test]
+    fn numeric_style() {
+        let style = load_style(DEFAULT_STYLE).unwrap();
+        let ids = ["a".to_string(), "b".to_string()];
+        let entries = [
+            paper(),
+            PaperEntry {
+                title: Some("Second".to_string()),
+                ..PaperEntry::new()
+            },
+        ];
+        let papers: Vec<(&PaperID, &PaperEntry)> = ids.iter().zip(&entries).collect();
+        let citations = styled_citations(&papers, &style, CiteFormat::Plain).unwrap();
+        assert!(
+            citations[0].starts_with("[1] J.-P. van Beethoven"),
+            "{}",
+            citations[0]
+        );
+        assert!(citations[1].starts_with("[2] "), "{}", citations[1]);
+        // the brackets are escaped in Markdown, and the venue is in italics
+        let citations = styled_citations(&papers, &style, CiteFormat::Markdown).unwrap();
+        assert!(citations[0].starts_with("\\[1\\] "), "{}", citations[0]);
+        assert!(
+            citations[0].contains("*IEEE Trans. Wireless Commun.*"),
+            "{}",
+            citations[0]
+        );
+    }
+}
@@ -0,0 +1,268 @@
+use super::database::{PaperEntry, PaperID};
+
+/// Default pattern of generated IDs, e.g. "zhao2024beam"
+pub const DEFAULT_PATTERN: &str = "{author}{year}{title}";
+
+/// Title words that are skipped when picking the significant words
+const STOP_WORDS: [&str; 40] = [
+    "a", "an", "the", "on", "of", "in", "for", "to", "and", "or", "with", "without", "towards",
+    "toward", "via", "by", "from", "at", "as", "into", "onto", "over", "under", "about", "is",
+    "are", "be", "can", "do", "does", "how", "what", "when", "where", "which", "why", "new",
+    "using", "based", "its",
+];
+
+/// Lowercase ASCII letters and digits of the text
+///
+/// Non-ASCII characters are transliterated, e.g. "Dvořák" as "dvorak" and "张" as "zhang".
+fn ascii_word(text: &str) -> String {
+    deunicode::deunicode(text)
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+/// Part of an ID pattern
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    /// Literal text
+    Text(String),
+    /// Family names of the first N authors
+    Author(usize),
+    /// Four-digit year
+    Year,
+    /// Last two digits of the year
+    ShortYear,
+    /// First N significant title words
+    Title(usize),
+}
+
+/// Pattern of generated IDs
+///
+/// The placeholders are:
+/// - `{author}`: family name of the first author, without a particle like "van"
+///   (`{author:N}` for the first N authors);
+/// - `{year}` and `{yy}`: the year in four or two digits;
+/// - `{title}`: first significant title word (`{title:N}` for the first N words).
+///
+/// Other text is kept as it is.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IdPattern {
+    parts: Vec<Part>,
+}
+
+impl Default for IdPattern {
+    fn default() -> Self {
+        Self::parse(DEFAULT_PATTERN).unwrap()
+    }
+}
+
+impl IdPattern {
+    /// Parse a pattern like "{author}{year}{title}"
+    pub fn parse(pattern: &str) -> Result<Self, String> {
+        let mut parts = vec![];
+        let mut rest = pattern;
+        while !rest.is_empty() {
+            match rest.find('{') {
+                Some(0) => {
+                    let end = rest
+                        .find('}')
+                        .ok_or_else(|| format!("unclosed '{{' in '{}'", pattern))?;
+                    let (name, count) = match rest[1..end].split_once(':') {
+                        Some((name, count)) => match count.trim().parse::<usize>() {
+                            Ok(count) if count > 0 => (name.trim(), Some(count)),
+                            _ => return Err(format!("invalid count in '{}'", &rest[..=end])),
+                        },
+                        None => (rest[1..end].trim(), None),
+                    };
+                    parts.push(match (name, count) {
+                        ("author", count) => Part::Author(count.unwrap_or(1)),
+                        ("year", None) => Part::Year,
+                        ("yy", None) => Part::ShortYear,
+                        ("title", count) => Part::Title(count.unwrap_or(1)),
+                        _ => return Err(format!("unknown placeholder '{}'", &rest[..=end])),
+                    });
+                    rest = &rest[end + 1..];
+                }
+                Some(start) => {
+                    parts.push(Part::Text(rest[..start].to_string()));
+                    rest = &rest[start..];
+                }
+                None => {
+                    parts.push(Part::Text(rest.to_string()));
+                    rest = "";
+                }
+            }
+        }
+        for part in &parts {
+            if let Part::Text(text) = part {
+                if text.contains(char::is_whitespace) || text.contains(['/', '\\', '}']) {
+                    return Err(format!("invalid text '{}' in the pattern", text));
+                }
+            }
+        }
+        if !parts.iter().any(|part| !matches!(part, Part::Text(_))) {
+            return Err(format!("no placeholder in '{}'", pattern));
+        }
+        Ok(Self { parts })
+    }
+
+    /// Generate an ID for the entry, e.g. "zhao2024beam"
+    ///
+    /// If the ID is taken, the suffixes "a", "b", "c", ... are tried.
+    /// Returns `None` if the entry has none of the metadata in the pattern.
+    pub fn generate<F>(&self, entry: &PaperEntry, taken: F) -> Option<PaperID>
+    where
+        F: Fn(&str) -> bool,
+    {
+        let mut base = String::new();
+        let mut has_metadata = false;
+        for part in &self.parts {
+            let value = match part {
+                Part::Text(text) => {
+                    base.push_str(text);
+                    continue;
+                }
+                Part::Author(count) => entry
+                    .authors
+                    .iter()
+                    .flatten()
+                    .take(*count)
+                    .map(|author| ascii_word(&author.family))
+                    .collect::<String>(),
+                Part::Year => entry.year.map(|year| year.to_string()).unwrap_or_default(),
+                Part::ShortYear => entry
+                    .year
+                    .map(|year| format!("{:02}", year % 100))
+                    .unwrap_or_default(),
+                Part::Title(count) => entry
+                    .title
+                    .iter()
+                    .flat_map(|title| title.split(|c: char| c.is_whitespace() || "-:/".contains(c)))
+                    .map(ascii_word)
+                    .filter(|word| !word.is_empty() && !STOP_WORDS.contains(&word.as_str()))
+                    .take(*count)
+                    .collect::<String>(),
+            };
+            has_metadata |= !value.is_empty();
+            base.push_str(&value);
+        }
+        if !has_metadata {
+            return None;
+        }
+        if !taken(&base) {
+            return Some(base);
+        }
+        // a, b, ..., z, then aa, ab, ...
+        (0..)
+            .map(|mut n: usize| {
+                let mut suffix = String::new();
+                loop {
+                    suffix.insert(0, (b'a' + (n % 26) as u8) as char);
+                    if n < 26 {
+                        break;
+                    }
+                    n = n / 26 - 1;
+                }
+                format!("{}{}", base, suffix)
+            })
+            .find(|id| !taken(id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::author::Author;
+
+    fn entry(title: &str) -> PaperEntry {
+        PaperEntry {
+            title: Some(title.to_string()),
+            authors: Some(vec![
+                Author::parse("Ludwig van Beethoven").unwrap(),
+                Author::parse("Jiří Dvořák").unwrap(),
+            ]),
+            year: Some(2024),
+            ..PaperEntry::new()
+        }
+    }
+
+    fn generate(pattern: &str, title: &str) -> Option<PaperID> {
+        IdPattern::parse(pattern)
+            .unwrap()
+            .generate(&entry(title), |_| false)
+    }
+
+    #[test]
+    fn parse_patterns() {
+        assert_eq!(
+            IdPattern::default(),
+            IdPattern::parse(DEFAULT_PATTERN).unwrap()
+        );
+        assert!(IdPattern::parse("{author:2}_{yy}-{title:3}").is_ok());
+        for pattern in [
+            "",
+            "text",
+            "{author",
+            "{author:0}",
+            "{year:2}",
+            "{name}",
+            "{year} x",
+        ] {
+            assert!(IdPattern::parse(pattern).is_err(), "{}", pattern);
+        }
+    }
+
+    #[test]
+    fn generate_ids() {
+        assert_eq!(
+            generate(DEFAULT_PATTERN, "On the Near-Field Beams").as_deref(),
+            Some("beethoven2024near")
+        );
+        assert_eq!(
+            generate("{author:2}_{yy}-{title:3}", "Towards a Theory: Beam/Squint").as_deref(),
+            Some("beethovendvorak_24-theorybeamsquint")
+        );
+    }
+
+    #[test]
+    fn title_word_separators() {
+        // '-', ':' and '/' separate words like spaces
+        assert_eq!(
+            generate("{title:2}", "Near-field beams").as_deref(),
+            Some("nearfield")
+        );
+        assert_eq!(
+            generate("{title:2}", "Sub-THz: why/how").as_deref(),
+            Some("subthz")
+        );
+    }
+
+    #[test]
+    fn generate_without_metadata() {
+        let pattern = IdPattern::default();
+        assert_eq!(pattern.generate(&PaperEntry::new(), |_| false), None);
+        let entry = PaperEntry {
+            year: Some(2021),
+            ..PaperEntry::new()
+        };
+        assert_eq!(pattern.generate(&entry, |_| false).as_deref(), Some("2021"));
+    }
+
+    #[test]
+    fn suffixes_of_taken_ids() {
+        let pattern = IdPattern::parse("{year}").unwrap();
+        let entry = entry("");
+        let taken = |taken: &[&str]| {
+            let taken: Vec<String> = taken.iter().map(|id| id.to_string()).collect();
+            pattern.generate(&entry, |id| taken.iter().any(|taken| taken == id))
+        };
+        assert_eq!(taken(&["2024"]).as_deref(), Some("2024a"));
+        assert_eq!(taken(&["2024", "2024a"]).as_deref(), Some("2024b"));
+        let all: Vec<String> = std::iter::once("2024".to_string())
+            .chain((b'a'..=b'z').map(|c| format!("2024{}", c as char)))
+            .collect();
+        let all: Vec<&str> = all.iter().map(String::as_str).collect();
+        assert_eq!(taken(&all).as_deref(), Some("2024aa"));
+    }
+}
@@ -0,0 +1,574 @@
+use super::author::Author;
+use super::database::{normalize_doi, EntryType, PaperEntry, PaperID, PaperList};
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+
+/// Key of the termipaper fields in the CSL `custom` object
+const CUSTOM_KEY: &str = "termipaper";
+
+/// CSL variables read directly from an item
+///
+/// The variables with a field in `PaperEntry` are moved there by `take_variables`,
+/// and the others are kept in `PaperEntry::extra`.
+const HANDLED: [&str; 8] = [
+    "id",
+    "citation-key",
+    "type",
+    "title",
+    "author",
+    "issued",
+    "container-title",
+    "DOI",
+];
+
+/// CSL item types of the entry types (items of other types keep it in `PaperEntry::extra`)
+const TYPES: [(EntryType, &str); 6] = [
+    (EntryType::Article, "article-journal"),
+    (EntryType::Inproceedings, "paper-conference"),
+    (EntryType::Book, "book"),
+    (EntryType::Thesis, "thesis"),
+    (EntryType::Techreport, "report"),
+    (EntryType::Misc, "document"),
+];
+
+/// CSL item type of an entry type
+pub fn csl_type(entry_type: EntryType) -> &'static str {
+    TYPES
+        .iter()
+        .find(|(ty, _)| *ty == entry_type)
+        .map_or("document", |(_, csl)| csl)
+}
+
+/// Entry type of a CSL item type, if it has one
+pub fn entry_type(csl_type: &str) -> Option<EntryType> {
+    TYPES
+        .iter()
+        .find(|(_, csl)| *csl == csl_type)
+        .map(|(ty, _)| *ty)
+}
+
+/// CSL name of an author
+///
+/// Names with only a family name (e.g. an organization) are literal names.
+/// The particle is non-dropping, i.e. it is kept with the family name (as in "van Gogh").
+fn to_csl_name(author: &Author) -> Value {
+    if author.given.is_none() && author.particle.is_none() && author.suffix.is_none() {
+        return json!({ "literal": author.family });
+    }
+    let mut csl_name = Map::new();
+    csl_name.insert("family".to_string(), json!(author.family));
+    for (key, value) in [
+        ("given", &author.given),
+        ("non-dropping-particle", &author.particle),
+        ("suffix", &author.suffix),
+    ] {
+        if let Some(value) = value {
+            csl_name.insert(key.to_string(), json!(value));
+        }
+    }
+    Value::Object(csl_name)
+}
+
+/// Author of a CSL name
+///
+/// Both the dropping and the non-dropping particles are read as the particle.
+fn from_csl_name(name: &Value) -> Option<Author> {
+    let field = |key: &str| {
+        name.get(key)
+            .and_then(Value::as_str)
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+    };
+    if let Some(literal) = field("literal") {
+        return Some(Author::literal(literal));
+    }
+    let particle = [field("dropping-particle"), field("non-dropping-particle")]
+        .iter()
+        .flatten()
+        .copied()
+        .collect::<Vec<_>>()
+        .join(" ");
+    let author = Author {
+        family: field("family").unwrap_or_default().to_string(),
+        given: field("given").map(str::to_string),
+        particle: Some(particle).filter(|particle| !particle.is_empty()),
+        suffix: field("suffix").map(str::to_string),
+        orcid: None,
+    };
+    match author.family.is_empty() {
+        // e.g. a name with only the given name
+        true => author.given.as_deref().map(Author::literal),
+        false => Some(author),
+    }
+}
+
+/// Year of a CSL date, from `date-parts`, `raw` or `literal`
+fn year_of(date: &Value) -> Option<u32> {
+    if let Some(year) = date.pointer("/date-parts/0/0") {
+        // the year may be a number or a string
+        return match year {
+            Value::Number(n) => n.as_u64().map(|n| n as u32),
+            Value::String(s) => s.trim().parse().ok(),
+            _ => None,
+        };
+    }
+    let text = date
+        .get("raw")
+        .or_else(|| date.get("literal"))
+        .and_then(Value::as_str)?;
+    let digits: String = text
+        .chars()
+        .skip_while(|c| !c.is_ascii_digit())
+        .take_while(|c| c.is_ascii_digit())
+        .collect();
+    digits.parse().ok()
+}
+
+/// Month of a CSL date, from `date-parts` only
+fn month_of(date: &Value) -> Option<u32> {
+    let month = match date.pointer("/date-parts/0/1")? {
+        Value::Number(n) => n.as_u64().map(|n| n as u32),
+        Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    };
+    month.filter(|month| (1..=12).contains(month))
+}
+
+/// CSL date of a year and an optional month
+fn csl_date(year: u32, month: Option<u32>) -> Value {
+    match month {
+        Some(month) => json!({ "date-parts": [[year, month]] }),
+        None => json!({ "date-parts": [[year]] }),
+    }
+}
+
+/// Move the CSL variables that have a field in `PaperEntry` from `variables` to the entry
+///
+/// Fields that are already set are not overridden. The month is read from `issued`,
+/// which is left in `variables` (since it may have more than the year and month).
+fn take_variables(entry: &mut PaperEntry, variables: &mut BTreeMap<String, Value>) {
+    let text = |value: Value| {
+        let text = match value {
+            Value::String(s) => s.trim().to_string(),
+            Value::Number(n) => n.to_string(),
+            // e.g. the ISSNs of the print and electronic versions from Crossref
+            Value::Array(values) => values.first()?.as_str()?.trim().to_string(),
+            _ => return None,
+        };
+        Some(text).filter(|text| !text.is_empty())
+    };
+    let known_type = variables
+        .get("type")
+        .and_then(Value::as_str)
+        .and_then(entry_type);
+    if let Some(known_type) = known_type {
+        variables.remove("type");
+        entry.entry_type.get_or_insert(known_type);
+    }
+    for (variable, field) in [
+        ("volume", &mut entry.volume),
+        ("issue", &mut entry.issue),
+        ("page", &mut entry.pages),
+        ("publisher", &mut entry.publisher),
+        ("abstract", &mut entry.r#abstract),
+        ("URL", &mut entry.url),
+        ("ISBN", &mut entry.isbn),
+        ("ISSN", &mut entry.issn),
+        ("language", &mut entry.language),
+    ] {
+        if let Some(value) = variables.remove(variable).and_then(text) {
+            field.get_or_insert(value);
+        }
+    }
+    if let Some(keywords) = variables.remove("keyword").and_then(text) {
+        let keywords: Vec<String> = keywords
+            .split(',')
+            .map(|keyword| keyword.trim().to_string())
+            .filter(|keyword| !keyword.is_empty())
+            .collect();
+        if !keywords.is_empty() {
+            entry.keywords.get_or_insert(keywords);
+        }
+    }
+    if let Some(month) = variables.get("issued").and_then(month_of) {
+        entry.month.get_or_insert(month);
+    }
+}
+
+/// Move the variables kept in `PaperEntry::extra` by older versions to their fields
+pub fn promote_extra(entry: &mut PaperEntry) {
+    if let Some(mut extra) = entry.extra.take() {
+        take_variables(entry, &mut extra);
+        entry.extra = Some(extra).filter(|extra| !extra.is_empty());
+    }
+}
+
+/// Format a paper as a CSL-JSON item, with the paper ID as the item ID
+pub fn to_csl(id: &PaperID, entry: &PaperEntry) -> Value {
+    let mut item = Map::new();
+    // 1. the unknown fields first, so the handled ones take precedence
+    if let Some(extra) = &entry.extra {
+        for (key, value) in extra {
+            item.insert(key.clone(), value.clone());
+        }
+    }
+    item.insert("id".to_string(), json!(id));
+    item.insert("citation-key".to_string(), json!(id));
+    match entry.entry_type {
+        Some(entry_type) => {
+            item.insert("type".to_string(), json!(csl_type(entry_type)));
+        }
+        // a paper without a type is a journal article if it has a venue,
+        // and is left without a type otherwise (so it is still untyped when imported)
+        None if entry.venue.is_some() => {
+            item.entry("type").or_insert(json!("article-journal"));
+        }
+        None => {}
+    }
+    // 2. the bibliographic fields
+    if let Some(title) = &entry.title {
+        item.insert("title".to_string(), json!(title));
+    }
+    if let Some(authors) = entry.authors.as_ref().filter(|a| !a.is_empty()) {
+        let names: Vec<Value> = authors.iter().map(to_csl_name).collect();
+        item.insert("author".to_string(), json!(names));
+    }
+    if let Some(year) = entry.year {
+        // keep the full imported date unless the year or month has been changed
+        // (dates without `date-parts` are replaced, since not all processors parse them)
+        let issued = item.get("issued").filter(|date| {
+            date.get("date-parts").is_some()
+                && year_of(date) == Some(year)
+                && month_of(date) == entry.month
+        });
+        if issued.is_none() {
+            item.insert("issued".to_string(), csl_date(year, entry.month));
+        }
+    } else {
+        item.remove("issued");
+    }
+    if let Some(venue) = &entry.venue {
+        item.insert("container-title".to_string(), json!(venue));
+    }
+    if let Some(doi) = &entry.doi {
+        item.insert("DOI".to_string(), json!(doi));
+    }
+    for (variable, value) in [
+        ("volume", &entry.volume),
+        ("issue", &entry.issue),
+        ("page", &entry.pages),
+        ("publisher", &entry.publisher),
+        ("abstract", &entry.r#abstract),
+        ("URL", &entry.url),
+        ("ISBN", &entry.isbn),
+        ("ISSN", &entry.issn),
+        ("language", &entry.language),
+    ] {
+        if let Some(value) = value {
+            item.insert(variable.to_string(), json!(value));
+        }
+    }
+    if let Some(keywords) = entry.keywords.as_ref().filter(|k| !k.is_empty()) {
+        item.insert("keyword".to_string(), json!(keywords.join(", ")));
+    }
+    // 3. the termipaper fields without a CSL variable
+    let mut custom = Map::new();
+    for (key, value) in [
+        ("file", &entry.file),
+        ("date_added", &entry.date_added),
+        ("date_modified", &entry.date_modified),
+        ("date_opened", &entry.date_opened),
+        ("arxiv", &entry.arxiv),
+    ] {
+        if let Some(value) = value {
+            custom.insert(key.to_string(), json!(value));
+        }
+    }
+    if let Some(version) = entry.arxiv_version {
+        custom.insert("arxiv_version".to_string(), json!(version));
+    }
+    if let Some(tags) = &entry.tags {
+        custom.insert("tags".to_string(), json!(tags));
+    }
+    if let Some(status) = entry.status {
+        custom.insert("status".to_string(), json!(status));
+    }
+    if let Some(priority) = entry.priority {
+        custom.insert("priority".to_string(), json!(priority));
+    }
+    if let Some(rating) = entry.rating {
+        custom.insert("rating".to_string(), json!(rating));
+    }
+    // CSL names have no ORCID iD, so they are listed in the order of the authors
+    let orcids: Vec<&Option<String>> = entry
+        .authors
+        .iter()
+        .flatten()
+        .map(|author| &author.orcid)
+        .collect();
+    if orcids.iter().any(|orcid| orcid.is_some()) {
+        custom.insert("orcids".to_string(), json!(orcids));
+    }
+    if !custom.is_empty() {
+        let custom_object = item
+            .entry("custom")
+            .or_insert_with(|| Value::Object(Map::new()));
+        if let Some(custom_object) = custom_object.as_object_mut() {
+            custom_object.insert(CUSTOM_KEY.to_string(), Value::Object(custom));
+        }
+    }
+    Value::Object(item)
+}
+
+/// Format the papers as a CSL-JSON array
+pub fn to_csl_json(papers: &[(&PaperID, &PaperEntry)]) -> String {
+    let items: Vec<Value> = papers.iter().map(|(id, entry)| to_csl(id, entry)).collect();
+    serde_json::to_string_pretty(&items).unwrap() + "\n"
+}
+
+/// Paper ID and entry of a CSL-JSON item
+///
+/// The ID is the `citation-key` if present, otherwise the `id`.
+/// Variables that termipaper does not handle are kept in `PaperEntry::extra`.
+pub fn from_csl(item: &Value) -> Result<(PaperID, PaperEntry), String> {
+    let object = item.as_object().ok_or("the item is not an object")?;
+    let id = ["citation-key", "id"]
+        .iter()
+        .find_map(|key| match object.get(*key) {
+            Some(Value::String(s)) if !s.trim().is_empty() => Some(s.trim().to_string()),
+            Some(Value::Number(n)) => Some(n.to_string()),
+            _ => None,
+        })
+        .ok_or("the item has no ID")?;
+    let text = |key: &str| {
+        object
+            .get(key)
+            .and_then(Value::as_str)
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+    };
+    // the ORCID iDs are in the order of the CSL names, including those that are skipped
+    let orcids = object
+        .get("custom")
+        .and_then(|custom| custom.get(CUSTOM_KEY))
+        .and_then(|fields| fields.get("orcids"))
+        .and_then(Value::as_array);
+    let authors: Vec<Author> = object
+        .get("author")
+        .and_then(Value::as_array)
+        .map(|names| {
+            names
+                .iter()
+                .enumerate()
+                .filter_map(|(i, name)| {
+                    let mut author = from_csl_name(name)?;
+                    author.orcid = orcids
+                        .and_then(|orcids| orcids.get(i))
+                        .and_then(Value::as_str)
+                        .map(str::to_string);
+                    Some(author)
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    let mut entry = PaperEntry {
+        doi: text("DOI").and_then(|doi| normalize_doi(&doi)),
+        title: text("title"),
+        authors: Some(authors).filter(|a| !a.is_empty()),
+        year: object.get("issued").and_then(year_of),
+        month: object.get("issued").and_then(month_of),
+        venue: text("container-title"),
+        ..PaperEntry::new()
+    };
+    // the termipaper fields (except the file, which belongs to the exporting database)
+    let mut extra: BTreeMap<String, Value> = object
+        .iter()
+        .filter(|(key, _)| !HANDLED.contains(&key.as_str()))
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect();
+    if let Some(Value::Object(custom)) = extra.get_mut("custom") {
+        if let Some(Value::Object(fields)) = custom.remove(CUSTOM_KEY) {
+            entry.date_opened = fields
+                .get("date_opened")
+                .and_then(Value::as_str)
+                .map(str::to_string);
+            entry.arxiv = fields
+                .get("arxiv")
+                .and_then(Value::as_str)
+                .map(str::to_string);
+            entry.arxiv_version = fields
+                .get("arxiv_version")
+                .and_then(Value::as_u64)
+                .map(|version| version as u32);
+            entry.tags = fields.get("tags").and_then(Value::as_array).map(|tags| {
+                tags.iter()
+                    .filter_map(Value::as_str)
+                    .map(str::to_string)
+                    .collect()
+            });
+            let field = |key: &str| fields.get(key).cloned().unwrap_or_default();
+            entry.status = serde_json::from_value(field("status")).unwrap_or_default();
+            entry.priority = serde_json::from_value(field("priority")).unwrap_or_default();
+            entry.rating = fields
+                .get("rating")
+                .and_then(Value::as_u64)
+                .filter(|rating| (1..=5).contains(rating))
+                .map(|rating| rating as u8);
+        }
+        if custom.is_empty() {
+            extra.remove("custom");
+        }
+    }
+    // keep the full date if it has more than the year and month
+    if let Some(issued) = object.get("issued") {
+        if entry.year.map(|year| csl_date(year, entry.month)).as_ref() != Some(issued) {
+            extra.insert("issued".to_string(), issued.clone());
+        }
+    }
+    // keep the item type if it has no entry type
+    if let Some(item_type) = text("type") {
+        extra.insert("type".to_string(), json!(item_type));
+    }
+    take_variables(&mut entry, &mut extra);
+    entry.extra = Some(extra).filter(|extra| !extra.is_empty());
+    Ok((id, entry))
+}
+
+/// Parse the CSL-JSON content (an array of items or a single item)
+///
+/// Returns the papers and the errors of the items that cannot be converted.
+pub fn parse_csl_json(input: &str) -> Result<(PaperList, Vec<String>), String> {
+    let value: Value = serde_json::from_str(input).map_err(|e| e.to_string())?;
+    let items = match value {
+        Value::Array(items) => items,
+        item @ Value::Object(_) => vec![item],
+        _ => return Err("expected an array of CSL items".to_string()),
+    };
+    let mut papers = vec![];
+    let mut errors = vec![];
+    for (i, item) in items.iter().enumerate() {
+        match from_csl(item) {
+            Ok(paper) => papers.push(paper),
+            Err(e) => errors.push(format!("item {}: {}", i + 1, e)),
+        }
+    }
+    Ok((papers, errors))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::ReadingStatus;
+
+    fn paper() -> (PaperID, PaperEntry) {
+        let mut authors = vec![
+            Author::parse("Ludwig van Beethoven").unwrap(),
+            Author::literal("IEEE"),
+        ];
+        authors[0].orcid = Some("0000-0002-1825-0097".to_string());
+        let entry = PaperEntry {
+            entry_type: Some(EntryType::Inproceedings),
+            doi: Some("10.1109/ICC.2024.1".to_string()),
+            title: Some("Symphony".to_string()),
+            authors: Some(authors),
+            year: Some(2024),
+            month: Some(5),
+            venue: Some("Proc. ICC".to_string()),
+            pages: Some("1–6".to_string()),
+            tags: Some(vec!["music".to_string()]),
+            status: Some(ReadingStatus::Read),
+            rating: Some(4),
+            ..PaperEntry::new()
+        };
+        ("beethoven2024".to_string(), entry)
+    }
+
+    #[test]
+    fn round_trip() {
+        let (id, entry) = paper();
+        let item = to_csl(&id, &entry);
+        assert_eq!(item["type"], "paper-conference");
+        assert_eq!(item["author"][1], json!({ "literal": "IEEE" }));
+        assert_eq!(item["issued"], json!({ "date-parts": [[2024, 5]] }));
+        let (parsed_id, parsed) = from_csl(&item).unwrap();
+        assert_eq!(parsed_id, id);
+        assert_eq!(parsed.entry_type, entry.entry_type);
+        assert_eq!(parsed.doi, entry.doi);
+        assert_eq!(parsed.title, entry.title);
+        assert_eq!(parsed.authors, entry.authors);
+        assert_eq!((parsed.year, parsed.month), (Some(2024), Some(5)));
+        assert_eq!(parsed.venue, entry.venue);
+        assert_eq!(parsed.pages, entry.pages);
+        assert_eq!(parsed.tags, entry.tags);
+        assert_eq!(parsed.status, entry.status);
+        assert_eq!(parsed.rating, entry.rating);
+        assert_eq!(parsed.extra, None);
+        // neither an untyped paper nor a miscellaneous one changes its type
+        for entry_type in [None, Some(EntryType::Misc)] {
+            let entry = PaperEntry {
+                entry_type,
+                venue: None,
+                ..entry.clone()
+            };
+            let (_, parsed) = from_csl(&to_csl(&id, &entry)).unwrap();
+            assert_eq!(parsed.entry_type, entry_type);
+            assert_eq!(parsed.extra, None);
+        }
+    }
+
+    #[test]
+    fn types_without_entry_type() {
+        let mut entry = PaperEntry::new();
+        assert_eq!(to_csl(&"x".to_string(), &entry).get("type"), None);
+        entry.venue = Some("Journal".to_string());
+        assert_eq!(to_csl(&"x".to_string(), &entry)["type"], "article-journal");
+    }
+
+    #[test]
+    fn orcids_of_skipped_names() {
+        // the first name is skipped, but its ORCID iD is still in the list
+        let item = json!({
+            "id": "x",
+            "author": [{ "family": "" }, { "family": "Smith", "given": "Ann" }],
+            "custom": { "termipaper": { "orcids": [null, "0000-0002-1825-0097"] } },
+        });
+        let (_, entry) = from_csl(&item).unwrap();
+        let authors = entry.authors.unwrap();
+        assert_eq!(authors.len(), 1);
+        assert_eq!(authors[0].orcid.as_deref(), Some("0000-0002-1825-0097"));
+    }
+
+    #[test]
+    fn unknown_variables() {
+        let item = json!({
+            "id": 42,
+            "type": "dataset",
+            "title": " Data ",
+            "issued": { "raw": "2021-03-04" },
+            "note": "Kept",
+        });
+        let (id, entry) = from_csl(&item).unwrap();
+        assert_eq!(id, "42");
+        assert_eq!(entry.title.as_deref(), Some("Data"));
+        assert_eq!((entry.year, entry.month), (Some(2021), None));
+        let extra = entry.extra.as_ref().unwrap();
+        assert_eq!(extra["type"], "dataset");
+        assert_eq!(extra["note"], "Kept");
+        // the unknown variables are exported again
+        let exported = to_csl(&id, &entry);
+        assert_eq!(exported["type"], "dataset");
+        // (dates without `date-parts` are replaced)
+        assert_eq!(exported["issued"], json!({ "date-parts": [[2021]] }));
+    }
+
+    #[test]
+    fn parse_items() {
+        let (papers, errors) =
+            parse_csl_json(r#"[{"id": "a", "title": "A"}, {"title": "No ID"}, 1]"#).unwrap();
+        assert_eq!(papers.len(), 1);
+        assert_eq!(errors.len(), 2);
+        assert_eq!(parse_csl_json(r#"{"id": "a"}"#).unwrap().0.len(), 1);
+        assert!(parse_csl_json("42").is_err());
+        assert!(parse_csl_json("[").is_err());
+    }
+}
@@ -1,5 +1,10 @@
+use super::author::{self, Author};
+use super::csl;
+use super::fulltext::FullTextIndex;
+use super::fuzzy;
+use super::note;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::error::Error;
 use std::path::PathBuf;
 
@@ -21,7 +26,9 @@ impl Database {
         let mut db = Self::new(dir.clone());
         let index = db.index_from_file();
         match index {
-            Ok(index) => {
+            Ok(mut index) => {
+                // variables formerly kept in `extra` (e.g. the volume) are moved to their fields
+                index.papers.values_mut().for_each(csl::promote_extra);
                 db.top_category.papers = index.papers;
                 db.top_category.sub_categories = index.sub_categories;
                 db
@@ -32,11 +39,551 @@ impl Database {
             }
         }
     }
+
+    /// Directory of the database
+    pub fn dir(&self) -> &PathBuf {
+        &self.dir
+    }
+
+    /// Update the full-text index after papers are added, edited or removed
+    ///
+    /// Failures are only reported as warnings, since the index can be rebuilt any time.
+    fn update_fulltext(&self, ids: &[&PaperID]) {
+        let mut index = FullTextIndex::load(&self.dir);
+        let mut changed = false;
+        for id in ids {
+            match index.update(id, self.top_category.papers.get(*id)) {
+                Ok(updated) => changed |= updated,
+                Err(e) => eprintln!("Warning: failed to index the full text of '{}': {}", id, e),
+            }
+        }
+        if changed {
+            if let Err(e) = index.save() {
+                eprintln!("Warning: failed to save the full-text index: {}", e);
+            }
+        }
+    }
+
+    /// Check if the paper exists in the database
+    pub fn contains(&self, id: &PaperID) -> bool {
+        self.top_category.papers.contains_key(id)
+    }
+
+    /// The paper of which the ID is an alias (e.g. after a merge)
+    pub fn resolve_alias(&self, id: &str) -> Option<PaperID> {
+        self.top_category
+            .papers
+            .iter()
+            .find(|(_, entry)| entry.aliases.iter().flatten().any(|alias| alias == id))
+            .map(|(paper_id, _)| paper_id.clone())
+    }
+
+    /// Check if the ID is used by a paper, or as an alias
+    pub fn is_taken(&self, id: &str) -> bool {
+        self.contains(&id.to_string()) || self.resolve_alias(id).is_some()
+    }
+
+    /// Merge the dropped paper into the kept one, replacing it with the merged entry
+    ///
+    /// The attachment of the merged entry (one of the two files) is renamed after the kept
+    /// paper, and the other attachment is removed if it has the same content,
+    /// or kept as `<id>-alt.<ext>` in the other files of the paper otherwise.
+    /// The note of the dropped paper is appended to the note of the kept paper (or becomes it).
+    /// The references to the dropped paper in the notes and links of the other papers
+    /// are replaced by the kept one (see `rewrite_references`).
+    /// The index is written once, and the files are restored if it cannot be written.
+    pub fn merge(
+        &mut self,
+        keep: &PaperID,
+        drop: &PaperID,
+        mut merged: PaperEntry,
+    ) -> Result<(), Box<dyn Error>> {
+        _ck_id(keep)?;
+        self._ck_exists(keep)?;
+        self._ck_exists(drop)?;
+        let dir = self.dir.clone();
+        let mut changes = FileChanges::default();
+        let rewritten = self
+            .merge_files(&dir, keep, drop, &mut merged, &mut changes)
+            .and_then(|_| self.rewrite_references(&dir, keep, drop, &mut changes));
+        let rewritten = match rewritten {
+            Ok(rewritten) => rewritten,
+            Err(e) => {
+                changes.undo();
+                return Err(e);
+            }
+        };
+        // 5. one index update, after which the replaced files are removed
+        merged.date_modified = Some(PaperEntry::now());
+        let papers = &mut self.top_category.papers;
+        let dropped = papers.remove(drop);
+        let kept = papers.insert(keep.clone(), merged);
+        let mut updated: Vec<&PaperID> = vec![drop, keep];
+        let mut previous = vec![];
+        for (id, entry) in &rewritten {
+            previous.extend(
+                papers
+                    .insert(id.clone(), entry.clone())
+                    .map(|p| (id.clone(), p)),
+            );
+            updated.push(id);
+        }
+        if let Err(e) = self.top_category.save_index() {
+            let papers = &mut self.top_category.papers;
+            papers.extend(previous);
+            papers.extend(kept.map(|kept| (keep.clone(), kept)));
+            papers.extend(dropped.map(|dropped| (drop.clone(), dropped)));
+            changes.undo();
+            return Err(e);
+        }
+        changes.keep();
+        self.update_fulltext(&updated);
+        Ok(())
+    }
+
+    /// Replace the references to the dropped paper of a merge in the other papers
+    ///
+    /// The notes are rewritten (recording the changes), and the entries that link to it
+    /// (in `crossref`, `xref` or `related` of the extra fields) are returned updated.
+    fn rewrite_references(
+        &self,
+        dir: &std::path::Path,
+        keep: &PaperID,
+        drop: &PaperID,
+        changes: &mut FileChanges,
+    ) -> Result<Vec<(PaperID, PaperEntry)>, Box<dyn Error>> {
+        // 4. the notes and the links of the other papers
+        let mut rewritten = vec![];
+        for (id, entry) in &self.top_category.papers {
+            if id == keep || id == drop {
+                continue;
+            }
+            if let Some(note) = &entry.note {
+                let path = dir.join(note);
+                if let Ok(text) = std::fs::read_to_string(&path) {
+                    if let Some(text) = note::replace_id(&text, drop, keep) {
+                        changes.write(path, &text)?;
+                    }
+                }
+            }
+            let mut entry = entry.clone();
+            let mut linked = false;
+            for (_, value) in entry
+                .extra
+                .iter_mut()
+                .flatten()
+                .filter(|(key, _)| ["crossref", "xref", "related"].contains(&key.as_str()))
+            {
+                let values = match value {
+                    serde_json::Value::Array(values) => values.iter_mut().collect(),
+                    value => vec![value],
+                };
+                for value in values {
+                    let replaced = value.as_str().and_then(|v| note::replace_id(v, drop, keep));
+                    if let Some(replaced) = replaced {
+                        *value = serde_json::Value::String(replaced);
+                        linked = true;
+                    }
+                }
+            }
+            if linked {
+                entry.date_modified = Some(PaperEntry::now());
+                rewritten.push((id.clone(), entry));
+            }
+        }
+        Ok(rewritten)
+    }
+
+    /// Rename the attachments and combine the notes of a merge, recording the changes
+    fn merge_files(
+        &self,
+        dir: &std::path::Path,
+        keep: &PaperID,
+        drop: &PaperID,
+        merged: &mut PaperEntry,
+        changes: &mut FileChanges,
+    ) -> Result<(), Box<dyn Error>> {
+        let papers = &self.top_category.papers;
+        let (kept, dropped) = (&papers[keep], &papers[drop]);
+        let mut other_files: Vec<String> = kept
+            .other_files
+            .iter()
+            .chain(&dropped.other_files)
+            .flatten()
+            .cloned()
+            .collect();
+        // 1. the attachment that is not chosen
+        if let Some(other) = [&kept.file, &dropped.file]
+            .into_iter()
+            .flatten()
+            .find(|file| merged.file.as_ref() != Some(*file))
+        {
+            let other_path = dir.join(other);
+            let chosen = merged.file.clone();
+            let chosen_hash = chosen
+                .as_ref()
+                .and_then(|file| file_hash(dir.join(file)).ok());
+            match chosen {
+                Some(chosen)
+                    if chosen_hash.is_some() && chosen_hash == file_hash(&other_path).ok() =>
+                {
+                    // the same file, where the one named after the kept paper is kept
+                    if *other == Self::file_name(keep, &chosen) {
+                        changes.remove_later(dir.join(&chosen));
+                        merged.file = Some(other.clone());
+                    } else {
+                        changes.remove_later(other_path);
+                    }
+                }
+                _ => {
+                    let alt_file = Self::free_file_name(dir, &format!("{}-alt", keep), other);
+                    changes.rename(other_path, dir.join(&alt_file))?;
+                    eprintln!(
+                        "Warning: The attachments differ; '{}' is kept as '{}'.",
+                        other, alt_file
+                    );
+                    other_files.push(alt_file);
+                }
+            }
+        }
+        merged.other_files = Some(other_files).filter(|files| !files.is_empty());
+        // 2. the chosen attachment is named after the kept paper
+        if let Some(file) = merged.file.clone() {
+            let target = Self::file_name(keep, &file);
+            if file != target {
+                changes.rename(dir.join(&file), dir.join(&target))?;
+                merged.file = Some(target);
+            }
+        }
+        // 3. the notes are combined into the note of the kept paper
+        merged.note = Self::merge_notes(dir, keep, drop, [&kept.note, &dropped.note], changes)?;
+        Ok(())
+    }
+
+    /// Combine the notes of the kept and the dropped papers, returning the combined note
+    fn merge_notes(
+        dir: &std::path::Path,
+        keep: &PaperID,
+        drop: &PaperID,
+        notes: [&Option<String>; 2],
+        changes: &mut FileChanges,
+    ) -> Result<Option<String>, Box<dyn Error>> {
+        let exists = |note: &&String| dir.join(note).exists();
+        let (kept, dropped) = (
+            notes[0].as_ref().filter(exists),
+            notes[1].as_ref().filter(exists),
+        );
+        let target = note::file_name(keep);
+        match (kept, dropped) {
+            (Some(kept), Some(dropped)) => {
+                let mut text = std::fs::read_to_string(dir.join(kept))?;
+                let dropped_text = std::fs::read_to_string(dir.join(dropped))?;
+                if !text.ends_with('\n') {
+                    text.push('\n');
+                }
+                text.push_str(&format!("\n---\n\n<!-- Note of '{}' -->\n\n", drop));
+                text.push_str(&dropped_text);
+                changes.write(dir.join(&target), &text)?;
+                for note in [kept, dropped] {
+                    if *note != target {
+                        changes.remove_later(dir.join(note));
+                    }
+                }
+            }
+            (Some(note), None) | (None, Some(note)) => {
+                if *note != target {
+                    changes.rename(dir.join(note), dir.join(&target))?;
+                }
+            }
+            (None, None) => return Ok(None),
+        }
+        Ok(Some(target))
+    }
+
+    /// Attach a new version of the file of a paper (e.g. of a newer arXiv version)
+    ///
+    /// The new file is moved into the database (e.g. a download in the database directory).
+    /// The previous attachment is kept as `<id>-alt.<ext>` in the other files of the paper,
+    /// and restored if the index cannot be written. Returns the name of the kept file.
+    pub fn replace_file(
+        &mut self,
+        id: &PaperID,
+        new_file: &std::path::Path,
+    ) -> Result<Option<String>, Box<dyn Error>> {
+        self._ck_exists(id)?;
+        let dir = self.dir.clone();
+        let previous = self.top_category.papers[id].clone();
+        let mut entry = previous.clone();
+        let mut changes = FileChanges::default();
+        let mut kept = None;
+        if let Some(old) = entry.file.take().filter(|old| dir.join(old).exists()) {
+            let alt_file = Self::free_file_name(&dir, &format!("{}-alt", id), &old);
+            changes.rename(dir.join(&old), dir.join(&alt_file))?;
+            entry
+                .other_files
+                .get_or_insert_with(Vec::new)
+                .push(alt_file.clone());
+            kept = Some(alt_file);
+        }
+        let file = Self::file_name(id, &new_file.to_string_lossy());
+        if let Err(e) = changes.rename(new_file.to_path_buf(), dir.join(&file)) {
+            changes.undo();
+            return Err(e.into());
+        }
+        entry.file = Some(file);
+        entry.date_modified = Some(PaperEntry::now());
+        self.top_category.papers.insert(id.clone(), entry);
+        if let Err(e) = self.top_category.save_index() {
+            self.top_category.papers.insert(id.clone(), previous);
+            changes.undo();
+            return Err(e);
+        }
+        changes.keep();
+        self.update_fulltext(&[id]);
+        Ok(kept)
+    }
+
+    /// Name of the attachment of a paper, with the extension of the file
+    fn file_name(id: &str, file: &str) -> String {
+        match std::path::Path::new(file).extension() {
+            Some(ext) => format!("{}.{}", id, ext.to_string_lossy()),
+            None => id.to_string(),
+        }
+    }
+
+    /// File name that does not exist in the directory, e.g. "<stem>.pdf" or "<stem>2.pdf"
+    fn free_file_name(dir: &std::path::Path, stem: &str, file: &str) -> String {
+        (1..)
+            .map(|n| match n {
+                1 => Self::file_name(stem, file),
+                n => Self::file_name(&format!("{}{}", stem, n), file),
+            })
+            .find(|name| !dir.join(name).exists())
+            .unwrap()
+    }
+
+    /// Add paper entries at once, saving the index and the full-text index only once
+    ///
+    /// Papers that cannot be added (e.g. with an existing ID) are skipped,
+    /// and returned with the errors.
+    pub fn add_all(&mut self, papers: PaperList) -> Result<Vec<(PaperID, String)>, Box<dyn Error>> {
+        let (mut added, mut failed) = (vec![], vec![]);
+        for (id, entry) in papers {
+            let result = _ck_id(&id)
+                .and_then(|_| self._ck_not_alias(&id))
+                .and_then(|_| self.top_category.insert(id.clone(), entry, false));
+            match result {
+                Ok(()) => added.push(id),
+                Err(e) => failed.push((id, e.to_string())),
+            }
+        }
+        if !added.is_empty() {
+            self.top_category.save_index()?;
+            self.update_fulltext(&added.iter().collect::<Vec<_>>());
+        }
+        Ok(failed)
+    }
+
+    /// Check that the ID is not an alias of another paper (e.g. after a merge)
+    fn _ck_not_alias(&self, id: &PaperID) -> Result<(), Box<dyn Error>> {
+        match self.resolve_alias(id) {
+            Some(paper) => {
+                eprintln!("Error: the ID '{}' is an alias of '{}'.", id, paper);
+                Err(Box::new(std::io::Error::new(
+                    std::io::ErrorKind::AlreadyExists,
+                    "ID is an alias",
+                )))
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Check if the paper exists, suggesting the most similar ID if not
+    pub fn _ck_exists(&self, id: &PaperID) -> Result<(), Box<dyn Error>> {
+        if self.contains(id) {
+            return Ok(());
+        }
+        eprintln!(
+            "Error: the paper entry '{}' does not exist in the database.",
+            id
+        );
+        if let Some(suggestion) = fuzzy::suggest(id, self.top_category.papers.keys()) {
+            eprintln!("Info: Did you mean '{}'?", suggestion);
+        }
+        Err(Box::new(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            "paper entry not found",
+        )))
+    }
+
+    /// Paper record of the ID
+    pub fn record(&self, id: &PaperID) -> Option<PaperRecord> {
+        self.top_category.papers.get(id).map(|entry| PaperRecord {
+            id: id.clone(),
+            category: self.top_category.name(),
+            entry: entry.clone(),
+        })
+    }
+
+    /// Path of the attached file of a paper
+    pub fn file_path(&self, record: &PaperRecord) -> Option<PathBuf> {
+        record
+            .entry
+            .file
+            .as_ref()
+            .map(|file| self.dir.join(&record.category).join(file))
+    }
+
+    /// Path of the note of a paper (which may not exist yet)
+    pub fn note_path(&self, record: &PaperRecord) -> Option<PathBuf> {
+        record
+            .entry
+            .note
+            .as_ref()
+            .map(|note| self.dir.join(&record.category).join(note))
+    }
+
+    /// Record that the attachment of a paper is opened now
+    pub fn mark_opened(&mut self, id: &PaperID) -> Result<(), Box<dyn Error>> {
+        self._ck_exists(id)?;
+        let entry = self.top_category.papers.get_mut(id).unwrap();
+        entry.date_opened = Some(PaperEntry::now());
+        self.top_category.save_index()
+    }
+
+    /// Change a paper in place (e.g. its reading status), marking it as modified
+    pub fn modify<F>(&mut self, id: &PaperID, change: F) -> Result<(), Box<dyn Error>>
+    where
+        F: FnOnce(&mut PaperEntry),
+    {
+        self._ck_exists(id)?;
+        let entry = self.top_category.papers.get_mut(id).unwrap();
+        change(entry);
+        entry.date_modified = Some(PaperEntry::now());
+        self.top_category.save_index()
+    }
+
+    /// Change the tags of the papers, returning the IDs of the changed papers
+    ///
+    /// `change` returns whether the tags of a paper are changed, and the changed papers
+    /// are marked as modified. The index is written once (and not at all if nothing changes).
+    pub fn retag<F>(&mut self, mut change: F) -> Result<Vec<PaperID>, Box<dyn Error>>
+    where
+        F: FnMut(&PaperID, &mut PaperEntry) -> bool,
+    {
+        let now = PaperEntry::now();
+        let mut changed = vec![];
+        for (id, entry) in self.top_category.papers.iter_mut() {
+            if change(id, entry) {
+                entry.date_modified = Some(now.clone());
+                changed.push(id.clone());
+            }
+        }
+        if !changed.is_empty() {
+            self.top_category.save_index()?;
+        }
+        changed.sort();
+        Ok(changed)
+    }
+
+    /// Files attached to a paper (files that are missing are skipped)
+    pub fn attachments(&self, record: &PaperRecord) -> Vec<Attachment> {
+        let dir = self.dir.join(&record.category);
+        record
+            .entry
+            .file
+            .iter()
+            .chain(record.entry.other_files.iter().flatten())
+            .filter_map(|file| {
+                let path = dir.join(file);
+                let size = std::fs::metadata(&path).ok()?.len();
+                let sha256 = file_hash(&path).ok()?;
+                Some(Attachment {
+                    file: file.clone(),
+                    size,
+                    sha256,
+                })
+            })
+            .collect()
+    }
+
+    /// All paper records in the database, sorted by ID
+    pub fn records(&self) -> Vec<PaperRecord> {
+        let mut records = self.top_category.records();
+        records.sort_by(|a, b| a.id.cmp(&b.id));
+        records
+    }
+}
+
+/// Changes to the files of a merge (or a new attachment), which are undone if the index
+/// cannot be written
+#[derive(Debug, Default)]
+struct FileChanges {
+    /// Renamed files (from, to) and written files (with their previous text if any)
+    done: Vec<FileChange>,
+    /// Files that are removed once the changes are kept
+    obsolete: Vec<PathBuf>,
+}
+
+#[derive(Debug)]
+enum FileChange {
+    Renamed(PathBuf, PathBuf),
+    Written(PathBuf, Option<String>),
+}
+
+impl FileChanges {
+    fn rename(&mut self, from: PathBuf, to: PathBuf) -> std::io::Result<()> {
+        std::fs::rename(&from, &to)?;
+        self.done.push(FileChange::Renamed(from, to));
+        Ok(())
+    }
+
+    fn write(&mut self, path: PathBuf, text: &str) -> std::io::Result<()> {
+        let previous = std::fs::read_to_string(&path).ok();
+        std::fs::write(&path, text)?;
+        self.done.push(FileChange::Written(path, previous));
+        Ok(())
+    }
+
+    fn remove_later(&mut self, path: PathBuf) {
+        self.obsolete.push(path);
+    }
+
+    /// Keep the changes, removing the obsolete files
+    fn keep(self) {
+        for path in self.obsolete {
+            if let Err(e) = std::fs::remove_file(&path) {
+                eprintln!("Warning: Cannot remove '{}': {}", path.display(), e);
+            }
+        }
+    }
+
+    /// Undo the changes in the reverse order
+    fn undo(self) {
+        for change in self.done.into_iter().rev() {
+            let (path, result) = match change {
+                FileChange::Renamed(from, to) => {
+                    let result = std::fs::rename(&to, &from);
+                    (from, result)
+                }
+                FileChange::Written(path, Some(previous)) => {
+                    let result = std::fs::write(&path, previous);
+                    (path, result)
+                }
+                FileChange::Written(path, None) => {
+                    let result = std::fs::remove_file(&path);
+                    (path, result)
+                }
+            };
+            if let Err(e) = result {
+                eprintln!("Warning: Cannot restore '{}': {}", path.display(), e);
+            }
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct PaperCategory {
-    #[allow(unused)]
     relative_path: Vec<String>,
     dir: PathBuf,
     papers: PaperEntries,
@@ -53,6 +600,61 @@ impl PaperCategory {
         }
     }
 
+    /// Category path joined with '/' (empty for the top category)
+    pub fn name(&self) -> String {
+        self.relative_path.join("/")
+    }
+
+    /// Save the index of the category
+    fn save_index(&self) -> Result<(), Box<dyn Error>> {
+        let index = Index {
+            papers: self.papers.clone(),
+            sub_categories: self.sub_categories.clone(),
+        };
+        self.index_to_file(&index)
+    }
+
+    /// Add a paper entry without saving the index (see `TpManage::add`)
+    fn insert(
+        &mut self,
+        id: PaperID,
+        mut entry: PaperEntry,
+        force: bool,
+    ) -> Result<(), Box<dyn Error>> {
+        // 1. check if the paper entry is already in the category
+        // (TODO: more efficient way as using the return value to avoid double check)
+        if self.papers.contains_key(&id) && !force {
+            eprintln!(
+                "Error: the paper entry '{}' already exists in the category.",
+                id
+            );
+            return Err(Box::new(std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                "paper entry already exists",
+            )));
+        }
+        // 2. copy the file to the category
+        let outside_file = entry.file.clone();
+        Self::copy_file(self.dir.clone(), outside_file, &id, &mut entry)?;
+        entry.date_added = Some(PaperEntry::now());
+        entry.date_modified = entry.date_added.clone();
+        // 3. add the paper entry to the category
+        self.papers.insert(id, entry);
+        Ok(())
+    }
+
+    fn records(&self) -> Vec<PaperRecord> {
+        let category = self.name();
+        self.papers
+            .iter()
+            .map(|(id, entry)| PaperRecord {
+                id: id.clone(),
+                category: category.clone(),
+                entry: entry.clone(),
+            })
+            .collect()
+    }
+
     fn copy_file(
         dir: PathBuf,
         outside_file: Option<String>,
@@ -111,33 +713,227 @@ impl Index {
     }
 }
 
+/// Type of a publication, as in BibTeX
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EntryType {
+    /// Journal article
+    Article,
+    /// Paper in conference proceedings
+    Inproceedings,
+    Book,
+    /// PhD or master's thesis
+    Thesis,
+    /// Technical report
+    Techreport,
+    /// Anything else, e.g. a preprint
+    Misc,
+}
+
+impl std::fmt::Display for EntryType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Article => "article",
+            Self::Inproceedings => "inproceedings",
+            Self::Book => "book",
+            Self::Thesis => "thesis",
+            Self::Techreport => "techreport",
+            Self::Misc => "misc",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Reading status of a paper, in the order of the reading workflow
+#[derive(
+    clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum ReadingStatus {
+    /// Just added and not sorted yet
+    Inbox,
+    /// To be read
+    ToRead,
+    /// Being read
+    Reading,
+    /// Read thoroughly
+    Read,
+    /// Only skimmed
+    Skimmed,
+}
+
+impl std::fmt::Display for ReadingStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Inbox => "inbox",
+            Self::ToRead => "to-read",
+            Self::Reading => "reading",
+            Self::Read => "read",
+            Self::Skimmed => "skimmed",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Reading priority of a paper, the most urgent first
+#[derive(
+    clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Priority {
+    High,
+    Medium,
+    Low,
+}
+
+impl std::fmt::Display for Priority {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::High => "high",
+            Self::Medium => "medium",
+            Self::Low => "low",
+        };
+        write!(f, "{}", name)
+    }
+}
+
 /// Paper entry in the database
 ///
-/// The fields are now for testing purpose.
-/// More fields will be added in the future.
+/// The bibliographic fields follow BibTeX and CSL, so that they are preserved by all
+/// exporters. The fields that are not set are omitted from the index, except the original
+/// ones (the DOI, title, authors, year and file), which are written as `null` as before.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PaperEntry {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub entry_type: Option<EntryType>,
     pub doi: Option<String>,
     pub title: Option<String>,
-    pub authors: Option<Vec<String>>,
+    #[serde(default, deserialize_with = "author::deserialize_authors")]
+    pub authors: Option<Vec<Author>>,
     pub year: Option<u32>,
+    /// Month of publication (1 to 12)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub month: Option<u32>,
+    /// Journal, conference or other venue where the paper is published
+    /// (the journal, booktitle, school or institution in BibTeX, depending on the type)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub venue: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub volume: Option<String>,
+    /// Issue (or number) within the volume
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub issue: Option<String>,
+    /// Page range, e.g. "123-130", or an article number
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pages: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub publisher: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub r#abstract: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keywords: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub isbn: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub issn: Option<String>,
+    /// Language of the paper, e.g. "en"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    /// arXiv identifier without the version, e.g. "2401.01234"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub arxiv: Option<String>,
+    /// Version of the arXiv paper that is stored
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub arxiv_version: Option<u32>,
+    /// Labels of the paper, where '/' separates the levels (e.g. "method/beamforming")
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tags: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<ReadingStatus>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority: Option<Priority>,
+    /// Rating of the paper from 1 to 5
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rating: Option<u8>,
     pub file: Option<String>,
+    /// Other attachments of the paper, e.g. a different version kept when merging duplicates
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub other_files: Option<Vec<String>>,
+    /// Markdown note of the paper, next to the attachment (see `note::file_name`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    /// Time when the paper is added, in the format of "YYYY-MM-DD HH:MM:SS"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub date_added: Option<String>,
+    /// Time when the paper is last modified, in the format of "YYYY-MM-DD HH:MM:SS"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub date_modified: Option<String>,
+    /// Time when the attachment is last opened, in the format of "YYYY-MM-DD HH:MM:SS"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub date_opened: Option<String>,
+    /// Fields of imported records that are not handled (e.g. CSL-JSON variables), kept for export
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extra: Option<BTreeMap<String, serde_json::Value>>,
+    /// Former IDs of the paper (e.g. of merged duplicates), which still select it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub aliases: Option<Vec<PaperID>>,
     // to be added
 }
 
 impl PaperEntry {
-    #[allow(dead_code)]
     pub fn new() -> Self {
         Self {
+            entry_type: None,
             doi: None,
             title: None,
             authors: None,
             year: None,
+            month: None,
+            venue: None,
+            volume: None,
+            issue: None,
+            pages: None,
+            publisher: None,
+            r#abstract: None,
+            keywords: None,
+            url: None,
+            isbn: None,
+            issn: None,
+            language: None,
+            arxiv: None,
+            arxiv_version: None,
+            tags: None,
+            status: None,
+            priority: None,
+            rating: None,
             file: None,
+            other_files: None,
+            note: None,
+            date_added: None,
+            date_modified: None,
+            date_opened: None,
+            extra: None,
+            aliases: None,
         }
     }
 
+    /// Reading status, where a paper without one (e.g. imported or added by older versions)
+    /// is in the inbox
+    pub fn reading_status(&self) -> ReadingStatus {
+        self.status.unwrap_or(ReadingStatus::Inbox)
+    }
+
+    /// Current local time in the format of the date fields
+    pub fn now() -> String {
+        chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string()
+    }
+
     pub fn update_metadata(&mut self, paper: &PaperEntry) {
+        if let Some(doi) = paper.doi.clone() {
+            self.doi = Some(doi);
+        }
         if let Some(title) = paper.title.clone() {
             self.title = Some(title);
         }
@@ -147,11 +943,137 @@ impl PaperEntry {
         if let Some(year) = paper.year {
             self.year = Some(year);
         }
+        if let Some(venue) = paper.venue.clone() {
+            self.venue = Some(venue);
+        }
+        if let Some(arxiv) = paper.arxiv.clone() {
+            self.arxiv = Some(arxiv);
+        }
+        if let Some(arxiv_version) = paper.arxiv_version {
+            self.arxiv_version = Some(arxiv_version);
+        }
+        if let Some(entry_type) = paper.entry_type {
+            self.entry_type = Some(entry_type);
+        }
+        if let Some(month) = paper.month {
+            self.month = Some(month);
+        }
+        for (field, value) in [
+            (&mut self.volume, &paper.volume),
+            (&mut self.issue, &paper.issue),
+            (&mut self.pages, &paper.pages),
+            (&mut self.publisher, &paper.publisher),
+            (&mut self.r#abstract, &paper.r#abstract),
+            (&mut self.url, &paper.url),
+            (&mut self.isbn, &paper.isbn),
+            (&mut self.issn, &paper.issn),
+            (&mut self.language, &paper.language),
+        ] {
+            if let Some(value) = value.clone() {
+                *field = Some(value);
+            }
+        }
+        if let Some(keywords) = paper.keywords.clone() {
+            self.keywords = Some(keywords);
+        }
+        if let Some(tags) = paper.tags.clone() {
+            self.tags = Some(tags);
+        }
+        if let Some(status) = paper.status {
+            self.status = Some(status);
+        }
+        if let Some(priority) = paper.priority {
+            self.priority = Some(priority);
+        }
+        if let Some(rating) = paper.rating {
+            self.rating = Some(rating);
+        }
     }
 }
 
-type PaperID = String;
+/// File attached to a paper
+#[derive(Debug, Clone, Serialize)]
+pub struct Attachment {
+    /// File name relative to the database directory
+    pub file: String,
+    /// File size in bytes
+    pub size: u64,
+    /// SHA-256 hash of the file (hexadecimal)
+    pub sha256: String,
+}
+
+/// SHA-256 hash of a file (hexadecimal)
+pub fn file_hash<P: AsRef<std::path::Path>>(path: P) -> std::io::Result<String> {
+    use sha2::{Digest, Sha256};
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher)?;
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect())
+}
+
+/// DOI without the resolver prefix (e.g. "https://doi.org/"), or `None` if empty
+pub fn normalize_doi(doi: &str) -> Option<String> {
+    let doi = doi.trim();
+    let doi = [
+        "https://doi.org/",
+        "http://doi.org/",
+        "https://dx.doi.org/",
+        "http://dx.doi.org/",
+        "doi:",
+    ]
+    .iter()
+    .find_map(|prefix| doi.strip_prefix(prefix))
+    .unwrap_or(doi)
+    .trim();
+    Some(doi.to_string()).filter(|doi| !doi.is_empty())
+}
+
+/// Page range with a single hyphen, e.g. "123-130" for "123--130" (as in BibTeX)
+pub fn normalize_pages(pages: &str) -> Option<String> {
+    let pages = pages
+        .split(['-', '\u{2013}', '\u{2014}'])
+        .map(str::trim)
+        .filter(|page| !page.is_empty())
+        .collect::<Vec<_>>()
+        .join("-");
+    Some(pages).filter(|pages| !pages.is_empty())
+}
+
+/// Month of a number (1 to 12) or an English month name, which may be abbreviated
+pub fn parse_month(month: &str) -> Option<u32> {
+    let month = month.trim().trim_end_matches('.');
+    if let Ok(number) = month.parse() {
+        return Some(number).filter(|number| (1..=12).contains(number));
+    }
+    const NAMES: [&str; 12] = [
+        "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+    ];
+    let prefix = month.get(..3)?.to_lowercase();
+    NAMES
+        .iter()
+        .position(|name| *name == prefix)
+        .map(|i| i as u32 + 1)
+}
+
+pub type PaperID = String;
 type PaperEntries = HashMap<PaperID, PaperEntry>;
+/// Papers with their IDs (e.g. read from an imported file), in order
+pub type PaperList = Vec<(PaperID, PaperEntry)>;
+
+/// Paper entry together with its ID and category
+///
+/// This is the read-only view used by commands that display or select papers.
+#[derive(Debug, Clone)]
+pub struct PaperRecord {
+    pub id: PaperID,
+    /// Category path relative to the database root (empty for the top category)
+    pub category: String,
+    pub entry: PaperEntry,
+}
 
 /// Index of TermiPaper database
 trait TpIndex {
@@ -233,35 +1155,9 @@ fn _ck_id(id: &PaperID) -> Result<(), Box<dyn Error>> {
 }
 
 impl TpManage for PaperCategory {
-    fn add(
-        &mut self,
-        id: PaperID,
-        mut entry: PaperEntry,
-        force: bool,
-    ) -> Result<(), Box<dyn Error>> {
-        // 1. check if the paper entry is already in the category
-        // (TODO: more efficient way as using the return value to avoid double check)
-        if self.papers.contains_key(&id) && !force {
-            eprintln!(
-                "Error: the paper entry '{}' already exists in the category.",
-                id
-            );
-            return Err(Box::new(std::io::Error::new(
-                std::io::ErrorKind::AlreadyExists,
-                "paper entry already exists",
-            )));
-        }
-        // 2. copy the file to the category
-        let outside_file = entry.file.clone();
-        Self::copy_file(self.dir.clone(), outside_file, &id, &mut entry)?;
-        // 3. add the paper entry to the category
-        self.papers.insert(id, entry);
-        // 4. save the index
-        let index = Index {
-            papers: self.papers.clone(),
-            sub_categories: self.sub_categories.clone(),
-        };
-        self.index_to_file(&index)
+    fn add(&mut self, id: PaperID, entry: PaperEntry, force: bool) -> Result<(), Box<dyn Error>> {
+        self.insert(id, entry, force)?;
+        self.save_index()
     }
 
     fn edit(&mut self, id: PaperID, entry: PaperEntry) -> Result<(), Box<dyn Error>> {
@@ -271,6 +1167,7 @@ impl TpManage for PaperCategory {
                 let outside_file = entry.file.clone();
                 Self::copy_file(self.dir.clone(), outside_file, &id, entry_ref)?;
                 entry_ref.update_metadata(&entry);
+                entry_ref.date_modified = Some(PaperEntry::now());
                 // 2. save the index
                 let index = Index {
                     papers: self.papers.clone(),
@@ -283,10 +1180,10 @@ impl TpManage for PaperCategory {
                     "Error: the paper entry '{}' does not exist in the database.",
                     id
                 );
-                return Err(Box::new(std::io::Error::new(
+                Err(Box::new(std::io::Error::new(
                     std::io::ErrorKind::NotFound,
                     "paper entry not found",
-                )));
+                )))
             }
         }
     }
@@ -294,11 +1191,23 @@ impl TpManage for PaperCategory {
     fn remove(&mut self, id: PaperID) -> Result<(), Box<dyn Error>> {
         match self.papers.remove(&id) {
             Some(entry) => {
-                // 1. remove the file from the category
+                // 1. remove the file and the note from the category
                 if let Some(file) = &entry.file {
                     let file_path = self.dir.join(file);
                     std::fs::remove_file(file_path)?;
                 }
+                for file in entry.other_files.iter().flatten() {
+                    let file_path = self.dir.join(file);
+                    if file_path.exists() {
+                        std::fs::remove_file(file_path)?;
+                    }
+                }
+                if let Some(note) = &entry.note {
+                    let note_path = self.dir.join(note);
+                    if note_path.exists() {
+                        std::fs::remove_file(note_path)?;
+                    }
+                }
                 // 2. save the index
                 let index = Index {
                     papers: self.papers.clone(),
@@ -311,10 +1220,10 @@ impl TpManage for PaperCategory {
                     "Error: the paper entry '{}' does not exist in the database.",
                     id
                 );
-                return Err(Box::new(std::io::Error::new(
+                Err(Box::new(std::io::Error::new(
                     std::io::ErrorKind::NotFound,
                     "paper entry not found",
-                )));
+                )))
             }
         }
     }
@@ -324,21 +1233,102 @@ impl TpManage for Database {
     fn add(&mut self, id: PaperID, entry: PaperEntry, force: bool) -> Result<(), Box<dyn Error>> {
         // 1. safety check
         _ck_id(&id)?;
+        self._ck_not_alias(&id)?;
         // 2. add to the top category (TODO: check category)
-        self.top_category.add(id, entry, force)
+        self.top_category.add(id.clone(), entry, force)?;
+        // 3. index the full text of the attachment
+        self.update_fulltext(&[&id]);
+        Ok(())
     }
 
     fn edit(&mut self, id: PaperID, entry: PaperEntry) -> Result<(), Box<dyn Error>> {
         // 1. safety check
         _ck_id(&id)?;
+        self._ck_exists(&id)?;
         // 2. edit from the top category (TODO: check category)
-        self.top_category.edit(id, entry)
+        self.top_category.edit(id.clone(), entry)?;
+        // 3. index the full text of the attachment (if changed)
+        self.update_fulltext(&[&id]);
+        Ok(())
     }
 
     fn remove(&mut self, id: PaperID) -> Result<(), Box<dyn Error>> {
         // 1. safety check
         _ck_id(&id)?;
+        self._ck_exists(&id)?;
         // 2. remove from the top category (TODO: check category)
-        self.top_category.remove(id)
+        self.top_category.remove(id.clone())?;
+        // 3. remove from the full-text index
+        self.update_fulltext(&[&id]);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Database with the papers "a" and "b" (to be merged) and "c", which references "b"
+    fn database(name: &str) -> Database {
+        let dir = std::env::temp_dir().join(format!("termipaper-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("c.note.md"), "Extends @b (not b-2) in [[b]].\n").unwrap();
+        let mut database = Database::new(dir.to_str().unwrap().to_string());
+        let paper = |title: &str| PaperEntry {
+            title: Some(title.to_string()),
+            ..PaperEntry::new()
+        };
+        let c = PaperEntry {
+            note: Some("c.note.md".to_string()),
+            extra: Some(BTreeMap::from([
+                ("related".to_string(), serde_json::json!(["b", "b-2"])),
+                ("crossref".to_string(), serde_json::json!("b")),
+            ])),
+            ..paper("C")
+        };
+        let papers = vec![
+            ("a".to_string(), paper("A")),
+            ("b".to_string(), paper("B")),
+            ("c".to_string(), c),
+        ];
+        assert!(database.add_all(papers).unwrap().is_empty());
+        database
+    }
+
+    #[test]
+    fn merge_rewrites_references() {
+        let mut database = database("merge-references");
+        let merged = database.top_category.papers["a"].clone();
+        database
+            .merge(&"a".to_string(), &"b".to_string(), merged)
+            .unwrap();
+        let note = std::fs::read_to_string(database.dir().join("c.note.md")).unwrap();
+        assert_eq!(note, "Extends @a (not b-2) in [[a]].\n");
+        let reloaded = Database::new_from_index(database.dir().to_str().unwrap().to_string());
+        let extra = reloaded.top_category.papers["c"].extra.clone().unwrap();
+        assert_eq!(extra["related"], serde_json::json!(["a", "b-2"]));
+        assert_eq!(extra["crossref"], "a");
+        assert!(!reloaded.contains(&"b".to_string()));
+        std::fs::remove_dir_all(database.dir()).unwrap();
+    }
+
+    #[test]
+    fn failed_merge_restores_references() {
+        let mut database = database("merge-undo");
+        // the index cannot be written where a directory is
+        let index = database.index_file();
+        std::fs::remove_file(&index).unwrap();
+        std::fs::create_dir(&index).unwrap();
+        let merged = database.top_category.papers["a"].clone();
+        assert!(database
+            .merge(&"a".to_string(), &"b".to_string(), merged)
+            .is_err());
+        let note = std::fs::read_to_string(database.dir().join("c.note.md")).unwrap();
+        assert_eq!(note, "Extends @b (not b-2) in [[b]].\n");
+        let papers = &database.top_category.papers;
+        assert!(papers.contains_key("b"));
+        assert_eq!(papers["c"].extra.as_ref().unwrap()["crossref"], "b");
+        std::fs::remove_dir_all(database.dir()).unwrap();
     }
 }
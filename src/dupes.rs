@@ -0,0 +1,287 @@
+use super::database::{file_hash, PaperEntry, PaperID};
+use super::query::normalize;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+/// Default minimum similarity of two titles for the papers to be duplicates
+pub const DEFAULT_THRESHOLD: f64 = 0.9;
+
+/// Why two papers are considered duplicates
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatchReason {
+    /// The same DOI
+    Doi,
+    /// The same arXiv identifier (regardless of the version)
+    Arxiv,
+    /// Attachments with the same content
+    File,
+    /// Similar titles in the same (or an adjacent) year
+    Title,
+}
+
+impl std::fmt::Display for MatchReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let reason = match self {
+            Self::Doi => "same DOI",
+            Self::Arxiv => "same arXiv ID",
+            Self::File => "same file",
+            Self::Title => "similar title and year",
+        };
+        write!(f, "{}", reason)
+    }
+}
+
+/// Pair of papers that may be duplicates
+#[derive(Debug, Clone)]
+pub struct Duplicate {
+    pub ids: (PaperID, PaperID),
+    /// Similarity between 0 and 1 (1 for the same DOI, arXiv ID or file)
+    pub score: f64,
+    /// All matching reasons, the strongest first
+    pub reasons: Vec<MatchReason>,
+}
+
+/// What duplicate detection compares of a paper
+#[derive(Debug, Clone)]
+pub struct Fingerprint {
+    pub id: PaperID,
+    doi: Option<String>,
+    arxiv: Option<String>,
+    /// Title reduced to lowercase words without accents or punctuation
+    title: Option<String>,
+    year: Option<u32>,
+    /// Path and size of the attachment, which is only hashed if the size is not unique
+    file: Option<(PathBuf, u64)>,
+    hash: Option<String>,
+}
+
+impl Fingerprint {
+    /// Fingerprint of a paper, whose attachment (if any) is at `file`
+    pub fn new(id: &PaperID, entry: &PaperEntry, file: Option<PathBuf>) -> Self {
+        let title = entry.title.as_ref().map(|title| {
+            normalize(title)
+                .split(|c: char| !c.is_alphanumeric())
+                .filter(|word| !word.is_empty())
+                .collect::<Vec<_>>()
+                .join(" ")
+        });
+        Self {
+            id: id.clone(),
+            // DOIs are case-insensitive
+            doi: entry.doi.as_ref().map(|doi| doi.to_lowercase()),
+            arxiv: entry.arxiv.clone(),
+            title: title.filter(|title| !title.is_empty()),
+            year: entry.year,
+            file: file.and_then(|path| {
+                let size = std::fs::metadata(&path).ok()?.len();
+                Some((path, size))
+            }),
+            hash: None,
+        }
+    }
+}
+
+/// Hash the attachments whose sizes are shared with another one
+///
+/// Files of different sizes cannot be the same, so most files are never read.
+pub fn hash_files(prints: &mut [Fingerprint]) {
+    let mut sizes: HashMap<u64, usize> = HashMap::new();
+    for (_, size) in prints.iter().filter_map(|print| print.file.as_ref()) {
+        *sizes.entry(*size).or_default() += 1;
+    }
+    for print in prints.iter_mut() {
+        if let Some((path, size)) = &print.file {
+            if print.hash.is_none() && sizes[size] > 1 {
+                print.hash = file_hash(path).ok();
+            }
+        }
+    }
+}
+
+/// Similarity of two normalized titles (0 if it cannot reach the threshold)
+fn title_similarity(a: &str, b: &str, threshold: f64) -> f64 {
+    let (len_a, len_b) = (a.chars().count(), b.chars().count());
+    // the edit distance is at least the difference of the lengths,
+    // so the titles are not compared if the lengths differ too much
+    if len_a.abs_diff(len_b) as f64 > len_a.max(len_b) as f64 * (1.0 - threshold) {
+        return 0.0;
+    }
+    strsim::normalized_damerau_levenshtein(a, b)
+}
+
+/// Compare two papers, returning the duplicate if any reason matches
+///
+/// Titles need a similarity of at least `threshold`, and the score is reduced
+/// if the years differ by one (e.g. a preprint and its journal version)
+/// or one of them is unknown; titles of papers further apart are never matched.
+pub fn compare(a: &Fingerprint, b: &Fingerprint, threshold: f64) -> Option<Duplicate> {
+    let same = |x: &Option<String>, y: &Option<String>| x.is_some() && x == y;
+    let mut reasons = vec![];
+    if same(&a.doi, &b.doi) {
+        reasons.push(MatchReason::Doi);
+    }
+    if same(&a.arxiv, &b.arxiv) {
+        reasons.push(MatchReason::Arxiv);
+    }
+    if same(&a.hash, &b.hash) {
+        reasons.push(MatchReason::File);
+    }
+    let mut score = if reasons.is_empty() { 0.0 } else { 1.0 };
+    if let (Some(title_a), Some(title_b)) = (&a.title, &b.title) {
+        let year_factor = match (a.year, b.year) {
+            (Some(x), Some(y)) if x == y => 1.0,
+            (Some(x), Some(y)) if x.abs_diff(y) == 1 => 0.95,
+            (Some(_), Some(_)) => 0.0,
+            _ => 0.97,
+        };
+        let similarity = title_similarity(title_a, title_b, threshold);
+        if year_factor > 0.0 && similarity >= threshold {
+            reasons.push(MatchReason::Title);
+            if score < 1.0 {
+                score = similarity * year_factor;
+            }
+        }
+    }
+    if reasons.is_empty() {
+        return None;
+    }
+    Some(Duplicate {
+        ids: (a.id.clone(), b.id.clone()),
+        score,
+        reasons,
+    })
+}
+
+/// All pairs of duplicates among the papers, the most similar first
+pub fn find_duplicates(prints: &[Fingerprint], threshold: f64) -> Vec<Duplicate> {
+    let mut duplicates: Vec<Duplicate> = prints
+        .iter()
+        .enumerate()
+        .flat_map(|(i, a)| {
+            prints[i + 1..]
+                .iter()
+                .filter_map(move |b| compare(a, b, threshold))
+        })
+        .collect();
+    duplicates.sort_by(|a, b| b.score.total_cmp(&a.score).then(a.ids.cmp(&b.ids)));
+    duplicates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn print(id: &str, title: &str, year: Option<u32>) -> Fingerprint {
+        let entry = PaperEntry {
+            title: Some(title.to_string()),
+            year,
+            ..PaperEntry::new()
+        };
+        Fingerprint::new(&id.to_string(), &entry, None)
+    }
+
+    fn score(a: &Fingerprint, b: &Fingerprint) -> Option<f64> {
+        compare(a, b, DEFAULT_THRESHOLD).map(|duplicate| duplicate.score)
+    }
+
+    #[test]
+    fn same_identifiers() {
+        let paper = |id: &str, doi: &str| {
+            let entry = PaperEntry {
+                doi: Some(doi.to_string()),
+                arxiv: Some("2401.01234".to_string()),
+                ..PaperEntry::new()
+            };
+            Fingerprint::new(&id.to_string(), &entry, None)
+        };
+        assert!(compare(&print("a", "One", None), &print("b", "Two", None), 0.9).is_none());
+        // DOIs are case-insensitive
+        let (mut a, mut b) = (paper("a", "10.1109/abc"), paper("b", "10.1109/ABC"));
+        let duplicate = compare(&a, &b, DEFAULT_THRESHOLD).unwrap();
+        assert_eq!(duplicate.score, 1.0);
+        assert_eq!(duplicate.reasons, [MatchReason::Doi, MatchReason::Arxiv]);
+        // missing identifiers are not the same
+        a.doi = None;
+        b.doi = None;
+        let duplicate = compare(&a, &b, DEFAULT_THRESHOLD).unwrap();
+        assert_eq!(duplicate.reasons, [MatchReason::Arxiv]);
+    }
+
+    #[test]
+    fn similar_titles() {
+        let a = print(
+            "a",
+            "Beam Training for Near-Field Communications",
+            Some(2024),
+        );
+        let same = print(
+            "b",
+            "Beam training for near field communications.",
+            Some(2024),
+        );
+        assert_eq!(score(&a, &same), Some(1.0));
+        let typo = print(
+            "c",
+            "Beam Trainig for Near-Field Communications",
+            Some(2024),
+        );
+        let typo_score = score(&a, &typo).unwrap();
+        assert!(typo_score > DEFAULT_THRESHOLD && typo_score < 1.0);
+        let different = print("d", "Beam Training for Far-Field Radar", Some(2024));
+        assert_eq!(score(&a, &different), None);
+    }
+
+    #[test]
+    fn title_years() {
+        let a = print("a", "Beam Training", Some(2024));
+        let scores = [Some(2023), None, Some(2021)]
+            .map(|year| score(&a, &print("b", "Beam Training", year)));
+        assert_eq!(scores, [Some(0.95), Some(0.97), None]);
+    }
+
+    #[test]
+    fn same_files() {
+        let dir = std::env::temp_dir().join(format!("termipaper-dupes-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let files = [
+            ("a.pdf", "same"),
+            ("b.pdf", "same"),
+            ("c.pdf", "diff"),
+            ("d.pdf", "longer"),
+        ];
+        for (name, text) in files {
+            std::fs::write(dir.join(name), text).unwrap();
+        }
+        let mut prints: Vec<Fingerprint> = files
+            .iter()
+            .enumerate()
+            .map(|(i, (name, _))| {
+                let entry = PaperEntry::new();
+                Fingerprint::new(&i.to_string(), &entry, Some(dir.join(name)))
+            })
+            .collect();
+        hash_files(&mut prints);
+        // the file with a unique size is not read
+        assert!(prints[3].hash.is_none());
+        let duplicates = find_duplicates(&prints, DEFAULT_THRESHOLD);
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(duplicates.len(), 1);
+        assert_eq!(duplicates[0].ids, ("0".to_string(), "1".to_string()));
+        assert_eq!(duplicates[0].reasons, [MatchReason::File]);
+    }
+
+    #[test]
+    fn duplicates_order() {
+        let prints = [
+            print("a", "Beam Training", Some(2024)),
+            print("b", "Beam Training", Some(2023)),
+            print("c", "Beam Training", Some(2024)),
+        ];
+        let duplicates = find_duplicates(&prints, DEFAULT_THRESHOLD);
+        let ids: Vec<(&str, &str)> = duplicates
+            .iter()
+            .map(|duplicate| (duplicate.ids.0.as_str(), duplicate.ids.1.as_str()))
+            .collect();
+        assert_eq!(ids, [("a", "c"), ("a", "b"), ("b", "c")]);
+    }
+}
@@ -0,0 +1,393 @@
+use super::database::{PaperEntry, PaperID};
+use super::pdf::PdfFile;
+use super::query::normalize;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+/// Occurrences of a term in a paper: (page starting from 1, term frequency)
+type Posting = (u32, u32);
+
+/// Source of the indexed text of a paper
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct FullTextDoc {
+    /// Attachment file name (relative to the database directory), empty if there is none
+    file: String,
+    /// File size in bytes when indexed
+    size: u64,
+    /// File modification time (seconds since UNIX epoch) when indexed
+    modified: u64,
+    /// Number of pages of the attachment
+    pages: u32,
+    /// Note file name with its size and modification time when indexed
+    /// (its text is indexed as the page after the last one)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    note: Option<(String, u64, u64)>,
+}
+
+/// Full-text index of a database
+///
+/// The index is an inverted index from normalized terms to the pages they appear on,
+/// stored as `fulltext.termipaper.json` in the database directory.
+/// The extracted text of each paper (with its note) is cached in `.fulltext/<id>.txt`
+/// (pages separated by form feeds) for building the snippets of search results.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FullTextIndex {
+    docs: BTreeMap<PaperID, FullTextDoc>,
+    terms: BTreeMap<String, BTreeMap<PaperID, Vec<Posting>>>,
+    #[serde(skip)]
+    dir: PathBuf,
+}
+
+/// Result of a full-text search
+#[derive(Debug, Clone)]
+pub struct FullTextHit {
+    pub id: PaperID,
+    pub score: f64,
+    /// Page of the best match (starting from 1)
+    pub page: u32,
+    /// Whether the best match is in the note rather than the attachment
+    pub in_note: bool,
+    /// Text around the best match, as (text, whether to highlight) segments
+    pub snippet: Vec<(String, bool)>,
+}
+
+impl FullTextIndex {
+    /// Words around the best match in a snippet (before, after)
+    const SNIPPET_WORDS: (usize, usize) = (8, 16);
+
+    fn index_file(dir: &Path) -> PathBuf {
+        dir.join("fulltext.termipaper.json")
+    }
+
+    fn text_file(&self, id: &PaperID) -> PathBuf {
+        self.dir.join(".fulltext").join(format!("{}.txt", id))
+    }
+
+    /// Load the full-text index of the database directory
+    ///
+    /// An empty index is returned if the index does not exist or cannot be parsed,
+    /// and it will be rebuilt incrementally.
+    pub fn load(dir: &Path) -> Self {
+        let index_file = Self::index_file(dir);
+        let mut index: Self = match std::fs::read_to_string(&index_file) {
+            Ok(index_str) => serde_json::from_str(&index_str).unwrap_or_else(|_| {
+                eprintln!(
+                    "Warning: failed to parse the full-text index at '{}', rebuilding it.",
+                    index_file.to_str().unwrap()
+                );
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        };
+        index.dir = dir.to_path_buf();
+        index
+    }
+
+    pub fn save(&self) -> Result<(), Box<dyn Error>> {
+        let index_str = serde_json::to_string(&self)?;
+        std::fs::write(Self::index_file(&self.dir), index_str)?;
+        Ok(())
+    }
+
+    /// Size and modification time of the attachment
+    fn file_stamp(&self, file: &str) -> Option<(u64, u64)> {
+        let metadata = std::fs::metadata(self.dir.join(file)).ok()?;
+        let modified = metadata
+            .modified()
+            .ok()?
+            .duration_since(std::time::UNIX_EPOCH)
+            .ok()?
+            .as_secs();
+        Some((metadata.len(), modified))
+    }
+
+    /// Check if the file type of the attachment can be indexed
+    fn is_indexable(file: &str) -> bool {
+        let ext = Path::new(file)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.to_lowercase());
+        matches!(ext.as_deref(), Some("pdf") | Some("txt") | Some("md"))
+    }
+
+    /// Update the index of a paper (`None` if the paper is removed)
+    ///
+    /// The text is only extracted again if the attachment or the note has changed,
+    /// so it is cheap to call this for every paper.
+    /// Returns whether the index has changed.
+    pub fn update(
+        &mut self,
+        id: &PaperID,
+        entry: Option<&PaperEntry>,
+    ) -> Result<bool, Box<dyn Error>> {
+        let file = entry
+            .and_then(|entry| entry.file.clone())
+            .filter(|file| Self::is_indexable(file))
+            .and_then(|file| Some((self.file_stamp(&file)?, file)));
+        let note = entry.and_then(|entry| entry.note.clone()).and_then(|note| {
+            let (size, modified) = self.file_stamp(&note)?;
+            Some((note, size, modified))
+        });
+        if file.is_none() && note.is_none() {
+            return Ok(self.remove(id));
+        }
+        let ((size, modified), file) = file.unwrap_or_default();
+        if let Some(doc) = self.docs.get(id) {
+            if doc.file == file && doc.size == size && doc.modified == modified && doc.note == note
+            {
+                return Ok(false);
+            }
+        }
+        self.remove(id);
+        let mut pages = if file.is_empty() {
+            vec![]
+        } else {
+            Self::extract(&self.dir.join(&file))?
+        };
+        let file_pages = pages.len() as u32;
+        if let Some((note_file, ..)) = &note {
+            pages.push(std::fs::read_to_string(self.dir.join(note_file))?);
+        }
+        // 1. add the postings of each page
+        for (i, text) in pages.iter().enumerate() {
+            let mut frequencies: BTreeMap<String, u32> = BTreeMap::new();
+            for term in tokenize(text) {
+                *frequencies.entry(term).or_default() += 1;
+            }
+            for (term, frequency) in frequencies {
+                self.terms
+                    .entry(term)
+                    .or_default()
+                    .entry(id.clone())
+                    .or_default()
+                    .push((i as u32 + 1, frequency));
+            }
+        }
+        // 2. cache the text for snippets
+        let text_file = self.text_file(id);
+        std::fs::create_dir_all(text_file.parent().unwrap())?;
+        std::fs::write(text_file, pages.join("\x0c"))?;
+        self.docs.insert(
+            id.clone(),
+            FullTextDoc {
+                file,
+                size,
+                modified,
+                pages: file_pages,
+                note,
+            },
+        );
+        Ok(true)
+    }
+
+    /// IDs of the indexed papers
+    pub fn paper_ids(&self) -> Vec<PaperID> {
+        self.docs.keys().cloned().collect()
+    }
+
+    /// Remove a paper from the index, returning whether it was indexed
+    pub fn remove(&mut self, id: &PaperID) -> bool {
+        if self.docs.remove(id).is_none() {
+            return false;
+        }
+        self.terms.retain(|_, postings| {
+            postings.remove(id);
+            !postings.is_empty()
+        });
+        let _ = std::fs::remove_file(self.text_file(id));
+        true
+    }
+
+    /// Extract the text of the file page by page
+    fn extract(file: &Path) -> Result<Vec<String>, Box<dyn Error>> {
+        let is_pdf = file
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("pdf"));
+        if is_pdf {
+            Ok(PdfFile::open(file)?.pages_text())
+        } else {
+            Ok(vec![std::fs::read_to_string(file)?])
+        }
+    }
+
+    /// Cached text of a page (starting from 1)
+    fn page_text(&self, id: &PaperID, page: u32) -> String {
+        std::fs::read_to_string(self.text_file(id))
+            .ok()
+            .and_then(|text| {
+                text.split('\x0c')
+                    .nth(page as usize - 1)
+                    .map(|s| s.to_string())
+            })
+            .unwrap_or_default()
+    }
+
+    /// Search the papers containing all terms of the text, ranked by relevance
+    ///
+    /// The score is a BM25-like sum over the terms, and papers containing
+    /// the terms as a consecutive phrase are ranked higher.
+    pub fn search(&self, text: &str, limit: usize) -> Vec<FullTextHit> {
+        let terms: Vec<String> = tokenize(text);
+        let distinct: Vec<&String> = {
+            let mut seen = HashSet::new();
+            terms.iter().filter(|term| seen.insert(*term)).collect()
+        };
+        if distinct.is_empty() {
+            return vec![];
+        }
+        let total = self.docs.len() as f64;
+        let postings: Vec<&BTreeMap<PaperID, Vec<Posting>>> = match distinct
+            .iter()
+            .map(|term| self.terms.get(*term))
+            .collect::<Option<Vec<_>>>()
+        {
+            Some(postings) => postings,
+            None => return vec![], // some term appears nowhere
+        };
+        let mut hits = vec![];
+        // papers containing the rarest term are the only candidates
+        let rarest = postings.iter().min_by_key(|p| p.len()).unwrap();
+        for id in rarest.keys() {
+            if !postings.iter().all(|p| p.contains_key(id)) {
+                continue;
+            }
+            // 1. score of the paper and the best page
+            let mut score = 0.0;
+            let mut pages: BTreeMap<u32, (usize, u32)> = BTreeMap::new();
+            for p in &postings {
+                let df = p.len() as f64;
+                let idf = (1.0 + (total - df + 0.5) / (df + 0.5)).ln();
+                let tf: u32 = p[id].iter().map(|(_, frequency)| frequency).sum();
+                score += idf * tf as f64 / (tf as f64 + 1.2);
+                for (page, frequency) in &p[id] {
+                    let page = pages.entry(*page).or_default();
+                    page.0 += 1;
+                    page.1 += frequency;
+                }
+            }
+            let page = pages
+                .iter()
+                .max_by(|a, b| a.1.cmp(b.1).then(b.0.cmp(a.0)))
+                .map(|(page, _)| *page)
+                .unwrap_or(1);
+            // 2. phrase bonus and the snippet
+            let page_text = self.page_text(id, page);
+            let (snippet, is_phrase) = Self::snippet(&page_text, &terms);
+            if is_phrase && terms.len() > 1 {
+                score *= 2.0;
+            }
+            hits.push(FullTextHit {
+                id: id.clone(),
+                score,
+                page,
+                in_note: page > self.docs[id].pages,
+                snippet,
+            });
+        }
+        hits.sort_by(|a, b| b.score.total_cmp(&a.score).then(a.id.cmp(&b.id)));
+        hits.truncate(limit);
+        hits
+    }
+
+    /// Build the snippet around the best match of the terms in the text
+    ///
+    /// Returns the snippet and whether the terms are found as a phrase.
+    fn snippet(text: &str, terms: &[String]) -> (Vec<(String, bool)>, bool) {
+        let words = words(text);
+        let normalized: Vec<String> = words
+            .iter()
+            .map(|(start, end)| normalize(&text[*start..*end]))
+            .collect();
+        let term_set: HashSet<&str> = terms.iter().map(|t| t.as_str()).collect();
+        // 1. find the phrase, or the first occurrence of any term
+        let phrase = (0..normalized.len()).find(|i| {
+            terms.len() <= normalized.len() - i
+                && terms
+                    .iter()
+                    .enumerate()
+                    .all(|(j, t)| &normalized[i + j] == t)
+        });
+        let anchor = phrase.or_else(|| {
+            normalized
+                .iter()
+                .position(|word| term_set.contains(word.as_str()))
+        });
+        let anchor = match anchor {
+            Some(anchor) => anchor,
+            None => return (vec![], false),
+        };
+        // 2. cut the words around the anchor and mark the terms
+        let first = anchor.saturating_sub(Self::SNIPPET_WORDS.0);
+        let last = (anchor + Self::SNIPPET_WORDS.1).min(words.len() - 1);
+        let mut snippet = vec![];
+        if first > 0 {
+            snippet.push(("…".to_string(), false));
+        }
+        for i in first..=last {
+            let (start, end) = words[i];
+            snippet.push((
+                text[start..end].to_string(),
+                term_set.contains(normalized[i].as_str()),
+            ));
+            if i < last {
+                // keep the punctuation between words, with whitespace collapsed
+                let gap = &text[end..words[i + 1].0];
+                let mut collapsed = gap.split_whitespace().collect::<Vec<_>>().join(" ");
+                if gap.starts_with(char::is_whitespace) {
+                    collapsed.insert(0, ' ');
+                }
+                if gap.ends_with(char::is_whitespace) && !collapsed.ends_with(' ') {
+                    collapsed.push(' ');
+                }
+                snippet.push((collapsed, false));
+            }
+        }
+        if last + 1 < words.len() {
+            snippet.push(("…".to_string(), false));
+        }
+        (snippet, phrase.is_some())
+    }
+}
+
+/// Check if the character is written without spaces (CJK ideographs, kana and hangul)
+///
+/// Each of such characters is treated as a term by itself.
+fn is_cjk(c: char) -> bool {
+    matches!(c as u32,
+        0x3040..=0x30FF | 0x3400..=0x4DBF | 0x4E00..=0x9FFF | 0xAC00..=0xD7AF | 0xF900..=0xFAFF)
+}
+
+/// Byte ranges of the words in the text
+fn words(text: &str) -> Vec<(usize, usize)> {
+    let mut words = vec![];
+    let mut start: Option<usize> = None;
+    for (i, c) in text.char_indices() {
+        if is_cjk(c) {
+            if let Some(s) = start.take() {
+                words.push((s, i));
+            }
+            words.push((i, i + c.len_utf8()));
+        } else if c.is_alphanumeric() {
+            start.get_or_insert(i);
+        } else if let Some(s) = start.take() {
+            words.push((s, i));
+        }
+    }
+    if let Some(s) = start {
+        words.push((s, text.len()));
+    }
+    words
+}
+
+/// Split the text into normalized terms
+///
+/// Single letters and digits are ignored, except for CJK characters.
+pub fn tokenize(text: &str) -> Vec<String> {
+    words(text)
+        .into_iter()
+        .map(|(start, end)| normalize(&text[start..end]))
+        .filter(|term| term.chars().count() > 1 || term.chars().any(is_cjk))
+        .collect()
+}
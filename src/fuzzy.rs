@@ -0,0 +1,191 @@
+use super::database::{PaperID, PaperRecord};
+use super::query::normalize;
+
+/// Minimum similarity for an ID to be suggested
+const SUGGEST_THRESHOLD: f64 = 0.7;
+
+/// Minimum score for a paper to be listed as a fuzzy match
+const MATCH_THRESHOLD: f64 = 0.5;
+
+/// Similarity of two strings between 0 (different) and 1 (same)
+///
+/// The normalized Damerau-Levenshtein distance is used,
+/// so typos like swapped letters are cheap.
+pub fn similarity(a: &str, b: &str) -> f64 {
+    strsim::normalized_damerau_levenshtein(&normalize(a), &normalize(b))
+}
+
+/// Suggest the most similar ID for a mistyped one
+pub fn suggest<'a, I>(id: &str, ids: I) -> Option<PaperID>
+where
+    I: IntoIterator<Item = &'a PaperID>,
+{
+    ids.into_iter()
+        .map(|candidate| (similarity(id, candidate), candidate))
+        .filter(|(score, _)| *score >= SUGGEST_THRESHOLD)
+        .max_by(|a, b| a.0.total_cmp(&b.0))
+        .map(|(_, candidate)| candidate.clone())
+}
+
+/// Score of the pattern as a subsequence of the text (0 if not a subsequence)
+///
+/// Compact matches score higher, e.g. "zhflex" in "zhao2024flexible".
+fn subsequence_score(pattern: &str, text: &str) -> f64 {
+    let pattern: Vec<char> = normalize(pattern).chars().collect();
+    let text: Vec<char> = normalize(text).chars().collect();
+    if pattern.is_empty() {
+        return 0.0;
+    }
+    let (mut i, mut start) = (0, None);
+    for (j, c) in text.iter().enumerate() {
+        if *c == pattern[i] {
+            start.get_or_insert(j);
+            i += 1;
+            if i == pattern.len() {
+                let span = j + 1 - start.unwrap();
+                return pattern.len() as f64 / span as f64;
+            }
+        }
+    }
+    0.0
+}
+
+/// Score of the pattern words against the title words
+///
+/// Each pattern word is matched to its most similar title word (or a title word it starts),
+/// and the average is taken.
+fn title_score(pattern: &str, title: &str) -> f64 {
+    let title_words: Vec<String> = normalize(title)
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(|w| w.to_string())
+        .collect();
+    let pattern_words: Vec<String> = normalize(pattern)
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(|w| w.to_string())
+        .collect();
+    if pattern_words.is_empty() || title_words.is_empty() {
+        return 0.0;
+    }
+    let total: f64 = pattern_words
+        .iter()
+        .map(|p| {
+            title_words
+                .iter()
+                .map(|t| {
+                    if t.starts_with(p.as_str()) {
+                        1.0
+                    } else {
+                        strsim::normalized_damerau_levenshtein(p, t)
+                    }
+                })
+                .fold(0.0, f64::max)
+        })
+        .sum();
+    total / pattern_words.len() as f64
+}
+
+/// Score of a paper for a fuzzy pattern, between 0 and 1
+pub fn score(pattern: &str, record: &PaperRecord) -> f64 {
+    let id_score = similarity(pattern, &record.id).max(subsequence_score(pattern, &record.id));
+    let title_score = record
+        .entry
+        .title
+        .as_ref()
+        .map_or(0.0, |title| title_score(pattern, title));
+    id_score.max(title_score)
+}
+
+/// Papers matching the fuzzy pattern by ID or title, best first
+pub fn matches<'a>(pattern: &str, records: &'a [PaperRecord]) -> Vec<(f64, &'a PaperRecord)> {
+    let mut matches: Vec<(f64, &PaperRecord)> = records
+        .iter()
+        .map(|record| (score(pattern, record), record))
+        .filter(|(score, _)| *score >= MATCH_THRESHOLD)
+        .collect();
+    matches.sort_by(|a, b| b.0.total_cmp(&a.0).then(a.1.id.cmp(&b.1.id)));
+    matches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::PaperEntry;
+
+    fn record(id: &str, title: &str) -> PaperRecord {
+        PaperRecord {
+            id: id.to_string(),
+            category: String::new(),
+            entry: PaperEntry {
+                title: Some(title.to_string()),
+                ..PaperEntry::new()
+            },
+        }
+    }
+
+    fn records() -> Vec<PaperRecord> {
+        vec![
+            record(
+                "zhao2024flexible",
+                "Flexible Beamforming for Near-Field Communications",
+            ),
+            record("zhang2023channel", "Channel Estimation with Deep Learning"),
+            record("müller2022", "Über Antennen"),
+        ]
+    }
+
+    #[test]
+    fn similar_ids() {
+        assert_eq!(similarity("zhao2024", "zhao2024"), 1.0);
+        assert_eq!(similarity("Müller", "muller"), 1.0);
+        // swapped letters cost one edit
+        assert!(similarity("zhoa2024", "zhao2024") > similarity("zhxy2024", "zhao2024"));
+        let ids: Vec<PaperID> = records().into_iter().map(|record| record.id).collect();
+        assert_eq!(
+            suggest("zhoa2024flexible", &ids).as_deref(),
+            Some("zhao2024flexible")
+        );
+        assert_eq!(suggest("smith2020", &ids), None);
+    }
+
+    #[test]
+    fn subsequences() {
+        assert_eq!(subsequence_score("zhao", "zhao2024flexible"), 1.0);
+        assert_eq!(subsequence_score("zflex", "zhao2024flexible"), 5.0 / 12.0);
+        // compact matches score higher
+        assert!(
+            subsequence_score("flex", "zhao2024flexible")
+                > subsequence_score("zflex", "zhao2024flexible")
+        );
+        assert_eq!(subsequence_score("xyz", "zhao2024flexible"), 0.0);
+        assert_eq!(subsequence_score("", "zhao2024flexible"), 0.0);
+    }
+
+    #[test]
+    fn title_words() {
+        // prefixes of title words match fully
+        assert_eq!(title_score("flex beam", "Flexible Beamforming"), 1.0);
+        assert_eq!(title_score("uber ANT", "Über Antennen"), 1.0);
+        assert!(title_score("beamfroming", "Flexible Beamforming") > 0.8);
+        assert_eq!(title_score("", "Flexible Beamforming"), 0.0);
+        assert_eq!(title_score("beam", ""), 0.0);
+    }
+
+    #[test]
+    fn best_matches_first() {
+        let records = records();
+        let ids = |pattern: &str| -> Vec<String> {
+            matches(pattern, &records)
+                .into_iter()
+                .map(|(_, record)| record.id.clone())
+                .collect()
+        };
+        assert_eq!(ids("zhflex"), ["zhao2024flexible"]);
+        assert_eq!(ids("channel estimation"), ["zhang2023channel"]);
+        assert_eq!(ids("muller"), ["müller2022"]);
+        // equal scores are listed by ID
+        assert_eq!(ids("zha"), ["zhang2023channel", "zhao2024flexible"]);
+        assert!(ids("quantum").is_empty());
+    }
+}
@@ -2,9 +2,29 @@ extern crate chrono;
 extern crate directories;
 extern crate serde;
 
-mod options;
-mod manager;
+mod annotations;
+mod arxiv;
+mod author;
+mod bibtex;
+mod cite;
+mod citekey;
+mod csl;
 mod database;
+mod dupes;
+mod fulltext;
+mod fuzzy;
+mod list;
+mod manager;
+mod merge;
+mod metadata;
+mod note;
+mod options;
+mod pdf;
+mod query;
+mod ris;
+mod tags;
+mod viewer;
+mod watch;
 
-pub use options::Cli;
 pub use manager::Manager;
+pub use options::Cli;
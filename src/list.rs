@@ -0,0 +1,407 @@
+use super::author;
+use super::database::{PaperRecord, ReadingStatus};
+use super::options::ListColumn;
+use std::cmp::{Ordering, Reverse};
+use std::str::FromStr;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+/// Inclusive range of years
+///
+/// Both ends are optional, so `2023..` (or `2023-`) means "2023 and later".
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct YearRange {
+    pub from: Option<u32>,
+    pub to: Option<u32>,
+}
+
+impl YearRange {
+    /// Check if the year is in the range (papers without a year are never in a range)
+    pub fn contains(&self, year: Option<u32>) -> bool {
+        match year {
+            Some(year) => {
+                self.from.is_none_or(|from| year >= from) && self.to.is_none_or(|to| year <= to)
+            }
+            None => false,
+        }
+    }
+}
+
+impl FromStr for YearRange {
+    type Err = String;
+
+    /// Parse a year range in the form of `2023`, `2023..2024`, `2023..` or `..2024`
+    /// (or with a dash instead of the dots, e.g. `2023-2024`)
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse_year = |year: &str| -> Result<Option<u32>, String> {
+            let year = year.trim();
+            if year.is_empty() {
+                Ok(None)
+            } else {
+                year.parse::<u32>()
+                    .map(Some)
+                    .map_err(|_| format!("invalid year '{}'", year))
+            }
+        };
+        let range = match s.split_once("..").or_else(|| s.split_once('-')) {
+            Some((from, to)) => Self {
+                from: parse_year(from)?,
+                to: parse_year(to)?,
+            },
+            None => {
+                let year = parse_year(s)?;
+                Self {
+                    from: year,
+                    to: year,
+                }
+            }
+        };
+        if range.from.is_none() && range.to.is_none() {
+            return Err("empty year range".to_string());
+        }
+        if let (Some(from), Some(to)) = (range.from, range.to) {
+            if from > to {
+                return Err(format!("invalid year range '{}'", s));
+            }
+        }
+        Ok(range)
+    }
+}
+
+impl ListColumn {
+    pub fn header(&self) -> &'static str {
+        match self {
+            Self::Id => "ID",
+            Self::Title => "TITLE",
+            Self::Authors => "AUTHORS",
+            Self::Year => "YEAR",
+            Self::Doi => "DOI",
+            Self::File => "FILE",
+            Self::Category => "CATEGORY",
+            Self::Status => "STATUS",
+            Self::Priority => "PRIORITY",
+            Self::Rating => "RATING",
+        }
+    }
+
+    /// Text of the column for a paper record (empty if the field is not set)
+    pub fn cell(&self, record: &PaperRecord) -> String {
+        let entry = &record.entry;
+        match self {
+            Self::Id => record.id.clone(),
+            Self::Title => entry.title.clone().unwrap_or_default(),
+            Self::Authors => entry
+                .authors
+                .as_deref()
+                .map(|authors| author::join(authors, ", "))
+                .unwrap_or_default(),
+            Self::Year => entry.year.map(|y| y.to_string()).unwrap_or_default(),
+            Self::Doi => entry.doi.clone().unwrap_or_default(),
+            Self::File => entry.file.clone().unwrap_or_default(),
+            Self::Category => record.category.clone(),
+            Self::Status => entry.reading_status().to_string(),
+            Self::Priority => entry.priority.map(|p| p.to_string()).unwrap_or_default(),
+            Self::Rating => entry.rating.map(|r| r.to_string()).unwrap_or_default(),
+        }
+    }
+
+    /// Compare two records by this column
+    ///
+    /// Records without the field are placed last, and ties are broken by the ID.
+    pub fn compare(&self, a: &PaperRecord, b: &PaperRecord) -> Ordering {
+        let ordering = match self {
+            Self::Year => compare_set(a.entry.year, b.entry.year),
+            Self::Status => a.entry.reading_status().cmp(&b.entry.reading_status()),
+            Self::Priority => compare_set(a.entry.priority, b.entry.priority),
+            Self::Rating => compare_set(a.entry.rating.map(Reverse), b.entry.rating.map(Reverse)),
+            _ => {
+                let (a, b) = (self.cell(a).to_lowercase(), self.cell(b).to_lowercase());
+                match (a.is_empty(), b.is_empty()) {
+                    (false, true) => Ordering::Less,
+                    (true, false) => Ordering::Greater,
+                    _ => a.cmp(&b),
+                }
+            }
+        };
+        ordering.then_with(|| a.id.cmp(&b.id))
+    }
+
+    /// Whether the column can be shortened to fit the terminal
+    pub fn is_flexible(&self) -> bool {
+        !matches!(
+            self,
+            Self::Id | Self::Year | Self::Status | Self::Priority | Self::Rating
+        )
+    }
+}
+
+/// Compare two optional values, where the values that are not set are placed last
+fn compare_set<T: Ord>(a: Option<T>, b: Option<T>) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) => a.cmp(&b),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+/// Compare two records in the reading queue
+///
+/// The papers being read come first, then the papers to read and those in the inbox,
+/// each by the priority (the highest first) and then the oldest added first.
+pub fn compare_queue(a: &PaperRecord, b: &PaperRecord) -> Ordering {
+    let stage = |record: &PaperRecord| match record.entry.reading_status() {
+        ReadingStatus::Reading => 0,
+        ReadingStatus::ToRead => 1,
+        _ => 2,
+    };
+    stage(a)
+        .cmp(&stage(b))
+        .then_with(|| compare_set(a.entry.priority, b.entry.priority))
+        .then_with(|| compare_set(a.entry.date_added.as_ref(), b.entry.date_added.as_ref()))
+        .then_with(|| a.id.cmp(&b.id))
+}
+
+#[derive(Debug, Clone)]
+struct TableColumn {
+    header: String,
+    flexible: bool,
+}
+
+/// Plain text table that adapts to the terminal width
+#[derive(Debug, Clone, Default)]
+pub struct Table {
+    columns: Vec<TableColumn>,
+    rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    /// Separator between two columns
+    const SEPARATOR: &'static str = "  ";
+    /// Flexible columns are never shortened below this width
+    const MIN_WIDTH: usize = 8;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a column, which is shortened first when the table is too wide if `flexible`
+    pub fn column<S: Into<String>>(mut self, header: S, flexible: bool) -> Self {
+        self.columns.push(TableColumn {
+            header: header.into(),
+            flexible,
+        });
+        self
+    }
+
+    pub fn push_row(&mut self, row: Vec<String>) {
+        self.rows.push(row);
+    }
+
+    /// Width of the terminal, or `None` if the output is not a terminal
+    ///
+    /// The `COLUMNS` environment variable is used as a fallback.
+    pub fn terminal_width() -> Option<usize> {
+        match terminal_size::terminal_size() {
+            Some((terminal_size::Width(width), _)) => Some(width as usize),
+            None => std::env::var("COLUMNS")
+                .ok()
+                .and_then(|columns| columns.parse().ok()),
+        }
+    }
+
+    /// Render the table within the maximum width (unlimited if `None`)
+    pub fn render(&self, max_width: Option<usize>) -> String {
+        // 1. natural width of each column
+        let mut widths: Vec<usize> = self
+            .columns
+            .iter()
+            .enumerate()
+            .map(|(i, column)| {
+                self.rows
+                    .iter()
+                    .map(|row| row.get(i).map_or(0, |cell| Self::text_width(cell)))
+                    .max()
+                    .unwrap_or(0)
+                    .max(column.header.width())
+            })
+            .collect();
+        // 2. shrink the widest flexible column until the table fits
+        if let Some(max_width) = max_width {
+            let separators = Self::SEPARATOR.len() * widths.len().saturating_sub(1);
+            while widths.iter().sum::<usize>() + separators > max_width {
+                let widest = widths
+                    .iter()
+                    .enumerate()
+                    .filter(|(i, width)| {
+                        self.columns[*i].flexible
+                            && **width > Self::MIN_WIDTH.max(self.columns[*i].header.width())
+                    })
+                    .max_by_key(|(_, width)| **width)
+                    .map(|(i, _)| i);
+                match widest {
+                    Some(i) => widths[i] -= 1,
+                    None => break, // nothing can be shortened any more
+                }
+            }
+        }
+        // 3. render the header, the rule and the rows
+        let mut lines = vec![];
+        let headers: Vec<String> = self.columns.iter().map(|c| c.header.clone()).collect();
+        lines.push(Self::render_row(&headers, &widths));
+        lines.push(
+            widths
+                .iter()
+                .map(|width| "-".repeat(*width))
+                .collect::<Vec<_>>()
+                .join(Self::SEPARATOR),
+        );
+        for row in &self.rows {
+            lines.push(Self::render_row(row, &widths));
+        }
+        lines.join("\n")
+    }
+
+    /// Print the table to stdout, fitted to the terminal width
+    pub fn print(&self) {
+        println!("{}", self.render(Self::terminal_width()));
+    }
+
+    fn render_row(row: &[String], widths: &[usize]) -> String {
+        let cells: Vec<String> = widths
+            .iter()
+            .enumerate()
+            .map(|(i, width)| {
+                let cell = Self::truncate(row.get(i).map_or("", |cell| cell), *width);
+                let padding = width.saturating_sub(cell.width());
+                format!("{}{}", cell, " ".repeat(padding))
+            })
+            .collect();
+        cells.join(Self::SEPARATOR).trim_end().to_string()
+    }
+
+    /// Display width of a cell (line breaks are shown as spaces)
+    fn text_width(text: &str) -> usize {
+        text.chars().map(|c| c.width().unwrap_or(1)).sum()
+    }
+
+    /// Shorten the text to the display width, ending with '…' if truncated
+    fn truncate(text: &str, width: usize) -> String {
+        let text: String = text
+            .chars()
+            .map(|c| if c.is_control() { ' ' } else { c })
+            .collect();
+        if text.width() <= width {
+            return text;
+        }
+        let mut truncated = String::new();
+        let mut used = 0;
+        for c in text.chars() {
+            let w = c.width().unwrap_or(0);
+            if used + w + 1 > width {
+                break;
+            }
+            truncated.push(c);
+            used += w;
+        }
+        truncated.push('…');
+        truncated
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(from: Option<u32>, to: Option<u32>) -> YearRange {
+        YearRange { from, to }
+    }
+
+    #[test]
+    fn parse_year_ranges() {
+        let parse = |s: &str| s.parse::<YearRange>();
+        assert_eq!(parse("2020"), Ok(range(Some(2020), Some(2020))));
+        assert_eq!(parse("2020..2022"), Ok(range(Some(2020), Some(2022))));
+        assert_eq!(parse("2020-2022"), Ok(range(Some(2020), Some(2022))));
+        assert_eq!(parse("2020.."), Ok(range(Some(2020), None)));
+        assert_eq!(parse("2020-"), Ok(range(Some(2020), None)));
+        assert_eq!(parse("..2019"), Ok(range(None, Some(2019))));
+        assert_eq!(parse("-2019"), Ok(range(None, Some(2019))));
+        assert_eq!(parse(" 2020 .. 2022 "), Ok(range(Some(2020), Some(2022))));
+        for invalid in [
+            "",
+            "..",
+            "-",
+            "abc",
+            "2020..abc",
+            "2022..2020",
+            "2020-21-22",
+        ] {
+            assert!(parse(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn year_range_contains() {
+        let years = range(Some(2020), Some(2022));
+        assert!(years.contains(Some(2020)) && years.contains(Some(2022)));
+        assert!(!years.contains(Some(2019)) && !years.contains(Some(2023)));
+        assert!(!years.contains(None));
+        assert!(range(None, Some(2019)).contains(Some(1800)));
+    }
+
+    fn table() -> Table {
+        let mut table = Table::new()
+            .column("ID", false)
+            .column("TITLE", true)
+            .column("YEAR", false);
+        table.push_row(vec![
+            "zhao2024".to_string(),
+            "Beam Training for Near-Field Communications".to_string(),
+            "2024".to_string(),
+        ]);
+        table.push_row(vec![
+            "李2023".to_string(),
+            "近场通信的波束训练".to_string(),
+            String::new(),
+        ]);
+        table
+    }
+
+    #[test]
+    fn render_table() {
+        assert_eq!(
+            table().render(None),
+            "ID        TITLE                                        YEAR\n\
+             --------  -------------------------------------------  ----\n\
+             zhao2024  Beam Training for Near-Field Communications  2024\n\
+             李2023    近场通信的波束训练"
+        );
+    }
+
+    #[test]
+    fn fit_table_to_width() {
+        // only the flexible title is shortened, counting the double width characters
+        let rendered = table().render(Some(30));
+        assert_eq!(
+            rendered,
+            "ID        TITLE           YEAR\n\
+             --------  --------------  ----\n\
+             zhao2024  Beam Training…  2024\n\
+             李2023    近场通信的波…"
+        );
+        assert!(rendered.lines().all(|line| line.width() <= 30));
+        // a narrow terminal cannot shorten the flexible columns below the minimum width
+        let rendered = table().render(Some(10));
+        assert_eq!(rendered.lines().nth(1), Some("--------  --------  ----"));
+    }
+
+    #[test]
+    fn truncate_cells() {
+        assert_eq!(Table::truncate("short", 8), "short");
+        assert_eq!(Table::truncate("line\nbreak", 10), "line break");
+        assert_eq!(Table::truncate("truncated", 6), "trunc…");
+        // a double width character that does not fit is left out entirely
+        assert_eq!(Table::truncate("波束训练", 6), "波束…");
+        assert_eq!(Table::truncate("波束训练", 5), "波束…");
+    }
+}
@@ -5,7 +5,7 @@ fn main() {
     // Cli::dbg_args();
     let manager = Manager::new();
     // dbg!(&manager);
-    if let Err(_) = manager.run() {
+    if manager.run().is_err() {
         std::process::exit(1);
     }
 }
@@ -1,7 +1,49 @@
 use super::{
-    database::{Database, PaperEntry, TpManage},
-    options::{Cli, Commands, Config, ConfigDatabase, PaperDir},
+    annotations,
+    arxiv::{self, ArxivId},
+    author, bibtex, cite,
+    citekey::IdPattern,
+    csl,
+    database::{
+        file_hash, normalize_doi, normalize_pages, Attachment, Database, PaperEntry, PaperID,
+        PaperList, PaperRecord, ReadingStatus, TpManage,
+    },
+    dupes::{self, Fingerprint},
+    fulltext::FullTextIndex,
+    fuzzy,
+    list::{self, Table},
+    merge::{self, Conflict, Side},
+    metadata, note,
+    options::{
+        BibliographyArgs, CiteFormat, Cli, CommandAddArgs, CommandDupesArgs, CommandImportArgs,
+        Commands, Config, ConfigCite, ConfigDatabase, ConfigId, ConfigNote, ConfigProviders,
+        ConfigViewers, ConfigWatch, ExportFormat, ImportFormat, ListColumn, MergeRule, PaperDir,
+        ShowFormat, TagAction, WatchOriginal,
+    },
+    pdf::PdfFile,
+    query::Query,
+    ris, tags,
+    viewer::ViewerCommand,
+    watch::{self, FolderWatcher, WatchQueue},
 };
+use clap::ValueEnum;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io::{IsTerminal, Write};
+
+/// Details of a paper printed by the 'show' command
+#[derive(Debug, Clone, Serialize)]
+struct PaperDetails {
+    id: String,
+    category: String,
+    #[serde(flatten)]
+    entry: PaperEntry,
+    attachments: Vec<Attachment>,
+    citation: String,
+    /// Text of the note (the file name is `note` of the entry)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    note_text: Option<String>,
+}
 
 #[derive(Debug, Clone)]
 pub struct Manager {
@@ -11,6 +53,14 @@ pub struct Manager {
 }
 impl PaperDir for Manager {}
 
+impl Default for Manager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// the commands report their errors to the user, so they only return whether they succeed
+#[allow(clippy::result_unit_err)]
 impl Manager {
     pub fn new() -> Self {
         let config = Config::from_file();
@@ -29,17 +79,28 @@ impl Manager {
         match &self.args.cmd {
             Commands::Activate(_) => self.cmd_activate(),
             Commands::Add(_) => self.cmd_add(),
+            Commands::Annotations(_) => self.cmd_annotations(),
+            Commands::Cite(_) => self.cmd_cite(),
             Commands::Config(_) => self.cmd_config(),
+            Commands::Dupes(_) => self.cmd_dupes(),
             Commands::Edit(_) => self.cmd_edit(),
+            Commands::Export(_) => self.cmd_export(),
+            Commands::Import(_) => self.cmd_import(),
             Commands::Info(_) => self.cmd_info(),
             Commands::Init(_) => self.cmd_init(),
-            // Commands::List(_) => self.cmd_list(),
+            Commands::List(_) => self.cmd_list(),
+            Commands::Merge(_) => self.cmd_merge(),
+            Commands::Note(_) => self.cmd_note(),
+            Commands::Queue(_) => self.cmd_queue(),
+            Commands::Rate(_) => self.cmd_rate(),
+            Commands::Refresh(_) => self.cmd_refresh(),
             Commands::Remove(_) => self.cmd_remove(),
-            // Commands::Open(_) => self.cmd_open(),
-            // Commands::Search(_) => self.cmd_search(),
-            _ => {
-                unimplemented!("Command not implemented: {:?}", self.args.cmd);
-            }
+            Commands::Open(_) => self.cmd_open(),
+            Commands::Search(_) => self.cmd_search(),
+            Commands::Show(_) => self.cmd_show(),
+            Commands::Status(_) => self.cmd_status(),
+            Commands::Tag(_) => self.cmd_tag(),
+            Commands::Watch(_) => self.cmd_watch(),
         }
     }
 
@@ -74,13 +135,9 @@ impl Manager {
                 Some(dir) => dir.clone(),
                 None => self.dir.clone(),
             },
-            _ => {
-                assert!(
-                    false,
-                    "Internal Error: This function should only be called with a command containing the dir field."
-                );
-                "".to_string()
-            }
+            _ => unreachable!(
+                "Internal Error: This function should only be called with a command containing the dir field."
+            ),
         };
         if !Self::_is_dir_existent(&dir) {
             // create directory if not existent
@@ -99,10 +156,148 @@ impl Manager {
             .to_string())
     }
 
+    /// Load the activated database
+    fn _get_database(&self) -> Result<Database, ()> {
+        match &self.config.activated {
+            Some(activated) => Ok(Database::new_from_index(activated.clone())),
+            None => {
+                eprintln!("Error: No database is activated.");
+                Err(())
+            }
+        }
+    }
+
+    /// Resolve the paper ID given by the user
+    ///
+    /// With `fuzzy`, a paper that does not exist is selected by fuzzy matching
+    /// its ID and title, asking the user to choose if there are multiple candidates.
+    /// With `confirm` (for commands that change or remove the paper), a fuzzy match
+    /// is only selected if the user confirms it in a terminal.
+    fn _resolve_id(
+        &self,
+        database: &Database,
+        id: &String,
+        fuzzy: bool,
+        confirm: bool,
+    ) -> Result<String, ()> {
+        if database.contains(id) {
+            return Ok(id.clone());
+        }
+        if let Some(paper) = database.resolve_alias(id) {
+            eprintln!("Info: '{}' is an alias of '{}'.", id, paper);
+            return Ok(paper);
+        }
+        if !fuzzy {
+            return Ok(id.clone());
+        }
+        let records = database.records();
+        let matches = fuzzy::matches(id, &records);
+        let candidates: Vec<&PaperRecord> = matches.iter().take(10).map(|(_, r)| *r).collect();
+        match candidates.len() {
+            0 => {
+                eprintln!("Error: No paper matches '{}'.", id);
+                Err(())
+            }
+            _ if confirm && !std::io::stdin().is_terminal() => {
+                eprintln!(
+                    "Error: '{}' is not an exact ID, and the fuzzy match cannot be confirmed \
+                    without a terminal (closest: '{}').",
+                    id, candidates[0].id
+                );
+                Err(())
+            }
+            1 if confirm => {
+                let record = candidates[0];
+                eprint!(
+                    "Select '{}' ({})? [y/N]: ",
+                    record.id,
+                    record.entry.title.clone().unwrap_or_default()
+                );
+                std::io::stderr().flush().unwrap();
+                let mut input = String::new();
+                std::io::stdin().read_line(&mut input).map_err(|_| ())?;
+                if matches!(input.trim().to_lowercase().as_str(), "y" | "yes") {
+                    Ok(record.id.clone())
+                } else {
+                    eprintln!("Error: No paper is selected.");
+                    Err(())
+                }
+            }
+            1 => {
+                eprintln!("Info: Selected '{}'.", candidates[0].id);
+                Ok(candidates[0].id.clone())
+            }
+            _ if !std::io::stdin().is_terminal() => {
+                eprintln!("Error: Multiple papers match '{}':", id);
+                for record in &candidates {
+                    eprintln!("  {}", record.id);
+                }
+                Err(())
+            }
+            _ => {
+                // the prompt is printed to stderr to keep stdout clean for the output
+                for (i, record) in candidates.iter().enumerate() {
+                    eprintln!(
+                        "{:>3}) {}  {}",
+                        i + 1,
+                        record.id,
+                        record.entry.title.clone().unwrap_or_default()
+                    );
+                }
+                // there is no default when the paper will be changed
+                if confirm {
+                    eprint!("Select a paper [1-{}]: ", candidates.len());
+                } else {
+                    eprint!("Select a paper [1-{}] (default 1): ", candidates.len());
+                }
+                std::io::stderr().flush().unwrap();
+                let mut input = String::new();
+                std::io::stdin().read_line(&mut input).map_err(|_| ())?;
+                let input = input.trim();
+                let choice = if input.is_empty() && !confirm {
+                    Some(1)
+                } else {
+                    input.parse::<usize>().ok()
+                };
+                match choice {
+                    Some(choice) if (1..=candidates.len()).contains(&choice) => {
+                        Ok(candidates[choice - 1].id.clone())
+                    }
+                    _ => {
+                        eprintln!("Error: Invalid selection '{}'.", input);
+                        Err(())
+                    }
+                }
+            }
+        }
+    }
+
+    /// Parse the query from command line arguments (joined with spaces)
+    fn _parse_query(query: &[String]) -> Result<Query, ()> {
+        Query::parse(&query.join(" ")).map_err(|e| {
+            eprintln!("Error: Invalid query: {}.", e);
+        })
+    }
+
+    /// Print paper records as a table with the columns
+    fn _print_records<'a, I>(records: I, columns: &[ListColumn])
+    where
+        I: IntoIterator<Item = &'a PaperRecord>,
+    {
+        let mut table = Table::new();
+        for column in columns {
+            table = table.column(column.header(), column.is_flexible());
+        }
+        for record in records {
+            table.push_row(columns.iter().map(|c| c.cell(record)).collect());
+        }
+        table.print();
+    }
+
     pub fn _is_initialized(&self, dir: &String) -> bool {
         if let Some(databases) = &self.config.databases {
             for database in databases {
-                if Self::_is_same_dir(&dir, &database.0) {
+                if Self::_is_same_dir(dir, database.0) {
                     return true;
                 }
             }
@@ -154,29 +349,101 @@ impl Manager {
         // 3. get the paper entry from the user input
         let args = match &self.args.cmd {
             Commands::Add(args) => args,
-            _ => {
-                assert!(
-                    false,
-                    "Internal Error: This function should only be called in the 'add' command."
-                );
-                return Err(());
-            }
+            _ => unreachable!(
+                "Internal Error: This function should only be called in the 'add' command."
+            ),
         };
         // 4. add the paper entry to the database
+        self._add_paper(&mut database, args)?;
+        // 5. save the database to the file (TODO)
+        Ok(())
+    }
+
+    /// Add a paper with its metadata from the flags, the providers and the PDF file
+    ///
+    /// This is shared by 'add' and 'watch'. Returns the ID of the added paper.
+    fn _add_paper(&self, database: &mut Database, args: &CommandAddArgs) -> Result<PaperID, ()> {
         // let mut paper = PaperEntry::new();
         // paper.file = args.file.clone();
-        let paper = PaperEntry {
-            doi: args.doi.clone(),
+        let manual = PaperEntry {
+            doi: args.doi.as_deref().and_then(normalize_doi),
             file: args.file.clone(),
             title: args.title.clone(),
             authors: args.authors.clone(),
-            year: args.year.clone(),
+            year: args.year,
+            venue: args.venue.clone(),
+            tags: match &args.tags {
+                Some(tags) => Some(Self::_normalize_tags(tags)?),
+                None => None,
+            },
+            // new papers are in the inbox unless sorted already
+            status: Some(args.status.unwrap_or(ReadingStatus::Inbox)),
+            priority: args.priority,
+            ..Self::_bibliography(&args.bibliography)
         };
-        database
-            .add(args.id.clone(), paper, args.force)
-            .map_err(|_| ())?;
-        // 5. save the database to the file (TODO)
-        Ok(())
+        // the fetched metadata is completed, or overridden, by the manual flags
+        let mut paper = match &args.doi {
+            Some(doi) if !args.no_fetch => match self._fetch_doi(doi) {
+                Ok(mut fetched) => {
+                    fetched.update_metadata(&manual);
+                    fetched.file = manual.file.clone();
+                    fetched
+                }
+                Err(()) => manual,
+            },
+            _ => manual,
+        };
+        // the arXiv metadata has the lowest precedence (e.g. the DOI metadata is preferred)
+        let mut downloaded_pdf = None;
+        if let Some(arxiv) = &args.arxiv {
+            let arxiv_id = ArxivId::parse(arxiv).map_err(|e| {
+                eprintln!("Error: Invalid arXiv identifier: {}.", e);
+            })?;
+            paper.arxiv = Some(arxiv_id.id.clone());
+            paper.arxiv_version = arxiv_id.version;
+            if !args.no_fetch {
+                if let Ok(metadata) = self._fetch_arxiv(&arxiv_id) {
+                    let mut fetched = metadata.entry;
+                    fetched.update_metadata(&paper);
+                    fetched.file = paper.file.clone();
+                    fetched.extra = paper.extra.clone().or(fetched.extra);
+                    paper = fetched;
+                    if paper.file.is_none() && !args.no_pdf {
+                        downloaded_pdf =
+                            Self::_download_pdf(database.dir(), &metadata.id, &metadata.pdf_url);
+                        paper.file = downloaded_pdf
+                            .as_ref()
+                            .map(|path| path.to_str().unwrap().to_string());
+                    }
+                }
+            }
+        }
+        // the metadata in the PDF file fills the remaining fields (offline)
+        if let Some(file) = args.file.as_ref().filter(|_| !args.no_extract) {
+            paper = Self::_extract_pdf(file, paper);
+        }
+        let id = match &args.id {
+            Some(id) => id.clone(),
+            None => {
+                let pattern = self._id_pattern()?;
+                match pattern.generate(&paper, |id| database.is_taken(id)) {
+                    Some(id) => {
+                        println!("Info: Generated the ID '{}'.", id);
+                        id
+                    }
+                    None => {
+                        eprintln!("Error: Cannot generate an ID without the authors, year or title; please give an ID.");
+                        return Err(());
+                    }
+                }
+            }
+        };
+        Self::_warn_duplicates(database, &id, &paper);
+        let result = database.add(id.clone(), paper, args.force).map_err(|_| ());
+        if let Some(path) = downloaded_pdf {
+            let _ = std::fs::remove_file(path);
+        }
+        result.map(|()| id)
     }
 
     pub fn cmd_edit(&self) -> Result<(), ()> {
@@ -193,27 +460,359 @@ impl Manager {
         // 3. get the paper entry from the user input
         let args = match &self.args.cmd {
             Commands::Edit(args) => args,
-            _ => {
-                assert!(
-                    false,
-                    "Internal Error: This function should only be called in the 'edit' command."
-                );
-                return Err(());
-            }
+            _ => unreachable!(
+                "Internal Error: This function should only be called in the 'edit' command."
+            ),
         };
         // 4. edit the paper entry from the database
+        let id = self._resolve_id(&database, &args.id, args.fuzzy, true)?;
         let paper = PaperEntry {
-            doi: args.doi.clone(),
+            doi: args.doi.as_deref().and_then(normalize_doi),
             file: args.file.clone(),
             title: args.title.clone(),
             authors: args.authors.clone(),
-            year: args.year.clone(),
+            year: args.year,
+            venue: args.venue.clone(),
+            ..Self::_bibliography(&args.bibliography)
         };
-        database.edit(args.id.clone(), paper).map_err(|_| ())?;
+        database.edit(id, paper).map_err(|_| ())?;
         // 5. save the database to the file (TODO)
         Ok(())
     }
 
+    /// TermiPaper Command: export
+    pub fn cmd_export(&self) -> Result<(), ()> {
+        let args = match &self.args.cmd {
+            Commands::Export(args) => args,
+            _ => unreachable!(
+                "Internal Error: This function should only be called in the 'export' command."
+            ),
+        };
+        let query = Self::_parse_query(&args.query)?;
+        let database = self._get_database()?;
+        let records: Vec<PaperRecord> = database
+            .records()
+            .into_iter()
+            .filter(|record| query.matches(record))
+            .collect();
+        let exported = match args.format {
+            ExportFormat::Bibtex => records
+                .iter()
+                .map(|record| bibtex::to_bibtex(&record.id, &record.entry))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            ExportFormat::CslJson => csl::to_csl_json(
+                &records
+                    .iter()
+                    .map(|record| (&record.id, &record.entry))
+                    .collect::<Vec<_>>(),
+            ),
+            ExportFormat::Ris => records
+                .iter()
+                .map(|record| ris::to_ris(&record.id, &record.entry))
+                .collect::<Vec<_>>()
+                .join("\n"),
+        };
+        match &args.output {
+            Some(output) => {
+                std::fs::write(output, exported).map_err(|e| {
+                    eprintln!("Error: Cannot write to '{}': {}", output, e);
+                })?;
+                println!("Info: Exported {} papers to '{}'.", records.len(), output);
+            }
+            None => print!("{}", exported),
+        }
+        Ok(())
+    }
+
+    /// TermiPaper Command: import
+    pub fn cmd_import(&self) -> Result<(), ()> {
+        let args = match &self.args.cmd {
+            Commands::Import(args) => args,
+            _ => unreachable!(
+                "Internal Error: This function should only be called in the 'import' command."
+            ),
+        };
+        if args.format == ImportFormat::Dir {
+            return self._import_dir(args);
+        }
+        if args.recursive || args.dry_run {
+            eprintln!("Warning: '--recursive' and '--dry-run' only apply to 'dir'.");
+        }
+        let mut database = self._get_database()?;
+        let path = std::path::Path::new(&args.path);
+        let content = std::fs::read_to_string(path).map_err(|e| {
+            eprintln!("Error: Cannot read '{}': {}", args.path, e);
+        })?;
+        let base_dir = path.parent().unwrap_or(std::path::Path::new("."));
+        let papers: PaperList = match args.format {
+            ImportFormat::Bib => {
+                let (entries, errors) = bibtex::parse_bibtex(&content);
+                for error in errors {
+                    eprintln!("Warning: Invalid BibTeX in '{}': {}.", args.path, error);
+                }
+                entries
+                    .iter()
+                    .map(|entry| (entry.key.clone(), entry.to_paper_entry(base_dir)))
+                    .collect()
+            }
+            ImportFormat::CslJson => {
+                let (papers, errors) = csl::parse_csl_json(&content).map_err(|e| {
+                    eprintln!("Error: Invalid CSL-JSON in '{}': {}.", args.path, e);
+                })?;
+                for error in errors {
+                    eprintln!("Warning: Invalid CSL-JSON in '{}': {}.", args.path, error);
+                }
+                papers
+            }
+            ImportFormat::Ris => {
+                let (records, errors) = ris::parse_ris(&content);
+                for error in errors {
+                    eprintln!("Warning: Invalid RIS in '{}': {}.", args.path, error);
+                }
+                let pattern = self._id_pattern()?;
+                let mut papers: PaperList = vec![];
+                for (i, record) in records.iter().enumerate() {
+                    let paper = record.to_paper_entry();
+                    let taken = |id: &str| {
+                        database.is_taken(id) || papers.iter().any(|(taken, _)| taken == id)
+                    };
+                    match pattern.generate(&paper, taken) {
+                        Some(id) => papers.push((id, paper)),
+                        None => eprintln!(
+                            "Warning: Skipped record {}: no author, year or title to generate an ID from.",
+                            i + 1
+                        ),
+                    }
+                }
+                papers
+            }
+            ImportFormat::Dir => unreachable!("Internal Error: 'dir' is imported separately."),
+        };
+        let mut skipped = 0;
+        let mut accepted: PaperList = vec![];
+        for (id, mut paper) in papers {
+            // colliding IDs are reported instead of overwritten
+            if database.contains(&id) || accepted.iter().any(|(taken, _)| *taken == id) {
+                eprintln!("Warning: Skipped '{}': the ID already exists.", id);
+                skipped += 1;
+                continue;
+            }
+            if let Some(file) = &paper.file {
+                if !std::path::Path::new(file).exists() {
+                    eprintln!(
+                        "Warning: The file '{}' of '{}' does not exist, imported without it.",
+                        file, id
+                    );
+                    paper.file = None;
+                }
+            }
+            accepted.push((id, paper));
+        }
+        // the index is saved once for all papers
+        let count = accepted.len();
+        let failed = database.add_all(accepted).map_err(|e| {
+            eprintln!("Error: Cannot save the index: {}", e);
+        })?;
+        let imported = count - failed.len();
+        skipped += failed.len();
+        println!(
+            "Info: Imported {} papers from '{}' ({} skipped).",
+            imported, args.path, skipped
+        );
+        Ok(())
+    }
+
+    /// Import the PDF files in a directory, with the metadata extracted from the files
+    fn _import_dir(&self, args: &CommandImportArgs) -> Result<(), ()> {
+        let mut database = self._get_database()?;
+        let dir = std::path::Path::new(&args.path);
+        let files = Self::_pdf_files(dir, args.recursive).map_err(|e| {
+            eprintln!("Error: Cannot read the directory '{}': {}", args.path, e);
+        })?;
+        // files already in the database (or earlier in the batch) are found by their hashes
+        let mut hashes = Self::_attachment_hashes(&database);
+        let pattern = self._id_pattern()?;
+        let mut table = Table::new()
+            .column("FILE", true)
+            .column("ID", false)
+            .column("TITLE", true)
+            .column("STATUS", false);
+        let (mut planned, mut skipped) = (vec![], 0);
+        let (mut failures, mut reviews) = (vec![], vec![]);
+        for path in &files {
+            let name = path.strip_prefix(dir).unwrap_or(path).display().to_string();
+            let hash = match file_hash(path) {
+                Ok(hash) => hash,
+                Err(e) => {
+                    table.push_row(vec![
+                        name.clone(),
+                        String::new(),
+                        String::new(),
+                        "failed".to_string(),
+                    ]);
+                    failures.push(format!("{}: {}", name, e));
+                    continue;
+                }
+            };
+            if let Some(existing) = hashes.get(&hash) {
+                table.push_row(vec![
+                    name,
+                    String::new(),
+                    String::new(),
+                    format!("skipped (same as '{}')", existing),
+                ]);
+                skipped += 1;
+                continue;
+            }
+            let extracted = match metadata::from_pdf(path) {
+                Ok(extracted) => extracted,
+                Err(e) => {
+                    table.push_row(vec![
+                        name.clone(),
+                        String::new(),
+                        String::new(),
+                        "failed".to_string(),
+                    ]);
+                    failures.push(format!("{}: {}", name, e));
+                    continue;
+                }
+            };
+            let taken = |id: &str| {
+                database.is_taken(id)
+                    || planned
+                        .iter()
+                        .any(|(taken, _, _): &(PaperID, _, _)| taken == id)
+            };
+            let id = match pattern.generate(&extracted.entry, taken) {
+                Some(id) => id,
+                None => {
+                    table.push_row(vec![
+                        name.clone(),
+                        String::new(),
+                        String::new(),
+                        "failed".to_string(),
+                    ]);
+                    failures.push(format!(
+                        "{}: no author, year or title to generate an ID from",
+                        name
+                    ));
+                    continue;
+                }
+            };
+            let status = if extracted.low_confidence() {
+                reviews.push(format!(
+                    "{} ({}): {}",
+                    id,
+                    name,
+                    extracted.entry.title.as_deref().unwrap_or("<no title>")
+                ));
+                "review"
+            } else {
+                "new"
+            };
+            table.push_row(vec![
+                name,
+                id.clone(),
+                extracted.entry.title.clone().unwrap_or_default(),
+                status.to_string(),
+            ]);
+            hashes.insert(hash, id.clone());
+            let mut paper = extracted.entry;
+            paper.file = Some(path.to_string_lossy().into_owned());
+            planned.push((id, paper, status));
+        }
+        if args.dry_run {
+            table.print();
+            println!(
+                "Info: Dry run: {} papers would be imported from '{}' ({} skipped, {} failed).",
+                planned.len(),
+                args.path,
+                skipped,
+                failures.len()
+            );
+            return Ok(());
+        }
+        let count = planned.len();
+        let papers = planned
+            .into_iter()
+            .map(|(id, paper, _)| (id, paper))
+            .collect();
+        let failed = database.add_all(papers).map_err(|e| {
+            eprintln!("Error: Cannot save the index: {}", e);
+        })?;
+        let imported = count - failed.len();
+        for (id, e) in failed {
+            failures.push(format!("{}: {}", id, e));
+        }
+        println!(
+            "Info: Imported {} papers from '{}' ({} skipped, {} failed).",
+            imported,
+            args.path,
+            skipped,
+            failures.len()
+        );
+        if !failures.is_empty() {
+            eprintln!("Warning: Failed to import {} files:", failures.len());
+            for failure in &failures {
+                eprintln!("  {}", failure);
+            }
+        }
+        if !reviews.is_empty() {
+            eprintln!(
+                "Warning: The metadata of {} papers are guessed; please review them:",
+                reviews.len()
+            );
+            for review in &reviews {
+                eprintln!("  {}", review);
+            }
+        }
+        Ok(())
+    }
+
+    /// IDs of the papers by the SHA-256 hashes of their attachments
+    fn _attachment_hashes(database: &Database) -> HashMap<String, PaperID> {
+        database
+            .records()
+            .iter()
+            .flat_map(|record| {
+                database
+                    .attachments(record)
+                    .into_iter()
+                    .map(|attachment| (attachment.sha256, record.id.clone()))
+            })
+            .collect()
+    }
+
+    /// PDF files in a directory (and its subdirectories if `recursive`), sorted by path
+    ///
+    /// Symbolic links to directories are not followed, so that a link loop cannot recurse forever.
+    fn _pdf_files(
+        dir: &std::path::Path,
+        recursive: bool,
+    ) -> std::io::Result<Vec<std::path::PathBuf>> {
+        let mut files = vec![];
+        for entry in std::fs::read_dir(dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            if entry.file_type()?.is_symlink() && path.is_dir() {
+                continue;
+            }
+            if path.is_dir() {
+                if recursive {
+                    files.extend(Self::_pdf_files(&path, recursive)?);
+                }
+            } else if path
+                .extension()
+                .is_some_and(|ext| ext.eq_ignore_ascii_case("pdf"))
+            {
+                files.push(path);
+            }
+        }
+        files.sort();
+        Ok(files)
+    }
+
     pub fn cmd_info(&self) -> Result<(), ()> {
         // get the activated database
         let activated = match &self.config.activated {
@@ -236,17 +835,261 @@ impl Manager {
         Ok(())
     }
 
+    /// Fetch the metadata of a DOI from the configured provider (failures are warnings)
+    fn _fetch_doi(&self, doi: &str) -> Result<PaperEntry, ()> {
+        let base_url = self
+            .config
+            .providers
+            .as_ref()
+            .and_then(|providers| providers.doi.as_deref());
+        println!("Info: Fetching the metadata of DOI '{}'...", doi);
+        metadata::fetch_doi(doi, base_url).map_err(|e| {
+            eprintln!("Warning: Cannot fetch the metadata of DOI '{}': {}", doi, e);
+        })
+    }
+
+    /// Fetch the metadata of an arXiv paper from the configured API (failures are warnings)
+    fn _fetch_arxiv(&self, id: &ArxivId) -> Result<arxiv::ArxivMetadata, ()> {
+        let base_url = self
+            .config
+            .providers
+            .as_ref()
+            .and_then(|providers| providers.arxiv.as_deref());
+        println!("Info: Fetching the metadata of arXiv paper '{}'...", id);
+        arxiv::fetch(id, base_url).map_err(|e| {
+            eprintln!(
+                "Warning: Cannot fetch the metadata of arXiv paper '{}': {}",
+                id, e
+            );
+        })
+    }
+
+    /// Complete the paper with the metadata extracted from its PDF file
+    ///
+    /// Files that are not PDF, or cannot be read, are left as they are.
+    fn _extract_pdf(file: &str, paper: PaperEntry) -> PaperEntry {
+        let path = std::path::Path::new(file);
+        if !path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("pdf"))
+        {
+            return paper;
+        }
+        let extracted = match metadata::from_pdf(path) {
+            Ok(extracted) => extracted,
+            Err(e) => {
+                eprintln!("Warning: Cannot extract the metadata of '{}': {}", file, e);
+                return paper;
+            }
+        };
+        let mut filled = vec![];
+        for (name, missing, found) in [
+            (
+                "title",
+                paper.title.is_none(),
+                extracted.entry.title.is_some(),
+            ),
+            (
+                "authors",
+                paper.authors.is_none(),
+                extracted.entry.authors.is_some(),
+            ),
+            ("year", paper.year.is_none(), extracted.entry.year.is_some()),
+            (
+                "venue",
+                paper.venue.is_none(),
+                extracted.entry.venue.is_some(),
+            ),
+            ("DOI", paper.doi.is_none(), extracted.entry.doi.is_some()),
+            (
+                "arXiv",
+                paper.arxiv.is_none(),
+                extracted.entry.arxiv.is_some(),
+            ),
+        ] {
+            if missing && found {
+                filled.push(name);
+            }
+        }
+        if filled.is_empty() {
+            return paper;
+        }
+        println!(
+            "Info: Extracted the {} from the PDF file.",
+            filled.join(", ")
+        );
+        if paper.title.is_none() && extracted.low_confidence() {
+            eprintln!(
+                "Warning: The title '{}' is guessed from the PDF file; please check it.",
+                extracted.entry.title.as_deref().unwrap_or_default()
+            );
+        }
+        let mut entry = extracted.entry;
+        // the arXiv version belongs to the arXiv identifier given manually
+        if paper.arxiv.is_some() {
+            entry.arxiv_version = None;
+        }
+        entry.update_metadata(&paper);
+        entry.file = paper.file;
+        entry.extra = paper.extra;
+        entry
+    }
+
+    /// Paper entry with the bibliographic fields of 'add' or 'edit'
+    fn _bibliography(args: &BibliographyArgs) -> PaperEntry {
+        PaperEntry {
+            entry_type: args.entry_type,
+            month: args.month,
+            volume: args.volume.clone(),
+            issue: args.issue.clone(),
+            pages: args.pages.as_deref().and_then(normalize_pages),
+            publisher: args.publisher.clone(),
+            r#abstract: args.r#abstract.clone(),
+            keywords: args.keywords.clone(),
+            url: args.url.clone(),
+            isbn: args.isbn.clone(),
+            issn: args.issn.clone(),
+            language: args.language.clone(),
+            ..PaperEntry::new()
+        }
+    }
+
+    /// Download the PDF of an arXiv paper to a temporary file (failures are warnings)
+    fn _download_pdf(
+        dir: &std::path::Path,
+        id: &ArxivId,
+        url: &Option<String>,
+    ) -> Option<std::path::PathBuf> {
+        let url = match url {
+            Some(url) => url,
+            None => {
+                eprintln!("Warning: No PDF is available for arXiv paper '{}'.", id);
+                return None;
+            }
+        };
+        let (path, mut file) = match Self::_temp_file(dir, "pdf") {
+            Ok(temp) => temp,
+            Err(e) => {
+                eprintln!(
+                    "Warning: Cannot create a temporary file in '{}': {}",
+                    dir.display(),
+                    e
+                );
+                return None;
+            }
+        };
+        println!("Info: Downloading the PDF of arXiv paper '{}'...", id);
+        match arxiv::download(url, &mut file) {
+            Ok(()) => Some(path),
+            Err(e) => {
+                let _ = std::fs::remove_file(&path);
+                eprintln!(
+                    "Warning: Cannot download the PDF of arXiv paper '{}': {}",
+                    id, e
+                );
+                None
+            }
+        }
+    }
+
+    /// Create a new file with a random name in the directory, e.g. ".termipaper-1f2e3d4c.pdf"
+    ///
+    /// The file must not exist yet, so that no other file (or a planted link) is overwritten.
+    fn _temp_file(
+        dir: &std::path::Path,
+        ext: &str,
+    ) -> std::io::Result<(std::path::PathBuf, std::fs::File)> {
+        use std::hash::{BuildHasher, Hasher};
+        loop {
+            let suffix = std::collections::hash_map::RandomState::new()
+                .build_hasher()
+                .finish();
+            let path = dir.join(format!(".termipaper-{:016x}.{}", suffix, ext));
+            match std::fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&path)
+            {
+                Ok(file) => return Ok((path, file)),
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Pattern of generated IDs from the config (the default if not configured)
+    fn _id_pattern(&self) -> Result<IdPattern, ()> {
+        match self.config.id.as_ref().and_then(|id| id.pattern.as_ref()) {
+            Some(pattern) => IdPattern::parse(pattern).map_err(|e| {
+                eprintln!("Error: Invalid ID pattern in the config: {}.", e);
+            }),
+            None => Ok(IdPattern::default()),
+        }
+    }
+
+    /// TermiPaper Command: cite
+    pub fn cmd_cite(&self) -> Result<(), ()> {
+        let args = match &self.args.cmd {
+            Commands::Cite(args) => args,
+            _ => unreachable!(
+                "Internal Error: This function should only be called in the 'cite' command."
+            ),
+        };
+        if args.list_styles {
+            for name in cite::bundled_styles() {
+                println!("{}", name);
+            }
+            return Ok(());
+        }
+        let defaults = self.config.cite.clone().unwrap_or_else(ConfigCite::new);
+        let style_name = args
+            .style
+            .clone()
+            .or(defaults.style)
+            .unwrap_or_else(|| cite::DEFAULT_STYLE.to_string());
+        let format = args.format.or(defaults.format).unwrap_or(CiteFormat::Plain);
+        let style = cite::load_style(&style_name).map_err(|e| {
+            eprintln!("Error: Cannot load the CSL style: {}.", e);
+            eprintln!("Info: Use 'termipaper cite --list-styles' to list the bundled styles.");
+        })?;
+        let database = self._get_database()?;
+        let mut records = vec![];
+        for id in &args.ids {
+            database._ck_exists(id).map_err(|_| ())?;
+            records.push(database.record(id).unwrap());
+        }
+        let papers: Vec<(&String, &PaperEntry)> = records
+            .iter()
+            .map(|record| (&record.id, &record.entry))
+            .collect();
+        let citations = cite::styled_citations(&papers, &style, format).map_err(|e| {
+            eprintln!(
+                "Error: Cannot format the citations in '{}': {}.",
+                style_name, e
+            );
+        })?;
+        match format {
+            CiteFormat::Html => {
+                println!("<div class=\"csl-bib-body\">");
+                for citation in citations {
+                    println!("  <div class=\"csl-entry\">{}</div>", citation);
+                }
+                println!("</div>");
+            }
+            CiteFormat::Plain | CiteFormat::Markdown => {
+                println!("{}", citations.join("\n\n"));
+            }
+        }
+        Ok(())
+    }
+
     /// TermiPaper Command: config
     pub fn cmd_config(&self) -> Result<(), ()> {
         let args = match &self.args.cmd {
             Commands::Config(args) => args,
-            _ => {
-                assert!(
-                    false,
-                    "Internal Error: This function should only be called in the 'config' command."
-                );
-                return Err(());
-            }
+            _ => unreachable!(
+                "Internal Error: This function should only be called in the 'config' command."
+            ),
         };
         // hidden option for development and testing
         if args.show_config_path {
@@ -308,56 +1151,582 @@ impl Manager {
                 config_edited = true;
             }
         }
-        if !has_args {
-            println!(
-                "owner.name: {}",
-                owner.clone().name.unwrap_or("<empty>".to_string())
-            );
-            println!(
-                "owner.email: {}",
-                owner.clone().email.unwrap_or("<empty>".to_string())
-            );
-            println!(
-                "owner.link: {}",
-                owner.clone().link.unwrap_or("<empty>".to_string())
-            );
-            println!(
-                "owner.affiliation: {}",
-                owner.clone().affiliation.unwrap_or("<empty>".to_string())
-            );
-        } else if config_edited {
-            let mut new_config = self.config.clone();
-            new_config.owner = Some(owner);
-            new_config.to_file();
+        let mut viewers = self.config.viewers.clone().unwrap_or_default();
+        let print_viewers = |viewers: &ConfigViewers| {
+            if viewers.is_empty() {
+                println!("viewer: <empty>");
+            }
+            let mut exts: Vec<&String> = viewers.keys().collect();
+            exts.sort();
+            for ext in exts {
+                println!("viewer.{}: {}", ext, viewers[ext]);
+            }
+        };
+        if let Some(viewer) = &args.viewer {
+            has_args = true;
+            if viewer == crate::options::CommandConfigArgs::_JUST_TO_PRINT_THIS_FIELD {
+                print_viewers(&viewers);
+            } else {
+                match viewer.split_once('=') {
+                    Some((ext, command)) => {
+                        let ext = ext.trim().trim_start_matches('.').to_lowercase();
+                        if command.trim().is_empty() {
+                            viewers.remove(&ext);
+                        } else {
+                            viewers.insert(ext, command.trim().to_string());
+                        }
+                        config_edited = true;
+                    }
+                    None => {
+                        eprintln!(
+                            "Error: Invalid viewer '{}', expected 'EXT=COMMAND'.",
+                            viewer
+                        );
+                        return Err(());
+                    }
+                }
+            }
         }
-        Ok(())
-    }
-
-    /// TermiPaper Command: init
-    ///
-    /// TODO: check whether the database is healthy (for example non-existent directories)
-    pub fn cmd_init(&self) -> Result<(), ()> {
-        // 1. Determine which directory the user wants to initialize
-        let dir_to_init = self._get_dir()?;
-        // 2. Check if the directory is the same as the activated directory
-        if let Some(activated) = &self.config.activated {
-            if Self::_is_same_dir(&dir_to_init, &activated) {
+        let mut cite = self.config.cite.clone().unwrap_or_else(ConfigCite::new);
+        if let Some(cite_style) = &args.cite_style {
+            has_args = true;
+            if cite_style == crate::options::CommandConfigArgs::_JUST_TO_PRINT_THIS_FIELD {
                 println!(
-                    "Info: The current database is already activated: {}",
-                    dir_to_init
+                    "cite.style: {}",
+                    cite.clone().style.unwrap_or("<empty>".to_string())
                 );
-                return Ok(());
+            } else {
+                if let Err(e) = cite::load_style(cite_style) {
+                    eprintln!("Error: Cannot load the CSL style: {}.", e);
+                    return Err(());
+                }
+                cite.style = Some(cite_style.clone());
+                config_edited = true;
             }
-        };
-        // 3. Check if the directory is already initialized from the databases
-        let is_initialized = self._is_initialized(&dir_to_init);
-        // 4. Initialize the directory (if not)
-        if !is_initialized {
-            let mut databases = match &self.config.databases {
-                Some(databases) => databases.clone(),
-                None => std::collections::HashMap::new(),
-            };
-            databases.insert(dir_to_init.clone(), ConfigDatabase::new());
+        }
+        if let Some(cite_format) = &args.cite_format {
+            has_args = true;
+            if cite_format == crate::options::CommandConfigArgs::_JUST_TO_PRINT_THIS_FIELD {
+                println!(
+                    "cite.format: {}",
+                    cite.format
+                        .map(|format| format!("{:?}", format).to_lowercase())
+                        .unwrap_or("<empty>".to_string())
+                );
+            } else {
+                match CiteFormat::from_str(cite_format, true) {
+                    Ok(format) => cite.format = Some(format),
+                    Err(_) => {
+                        eprintln!(
+                            "Error: Invalid citation format '{}', expected 'plain', 'markdown' or 'html'.",
+                            cite_format
+                        );
+                        return Err(());
+                    }
+                }
+                config_edited = true;
+            }
+        }
+        let mut id = self.config.id.clone().unwrap_or_else(ConfigId::new);
+        if let Some(id_pattern) = &args.id_pattern {
+            has_args = true;
+            if id_pattern == crate::options::CommandConfigArgs::_JUST_TO_PRINT_THIS_FIELD {
+                println!(
+                    "id.pattern: {}",
+                    id.clone().pattern.unwrap_or("<empty>".to_string())
+                );
+            } else if id_pattern.trim().is_empty() {
+                id.pattern = None;
+                config_edited = true;
+            } else {
+                if let Err(e) = IdPattern::parse(id_pattern) {
+                    eprintln!("Error: Invalid ID pattern: {}.", e);
+                    return Err(());
+                }
+                id.pattern = Some(id_pattern.clone());
+                config_edited = true;
+            }
+        }
+        let mut providers = self
+            .config
+            .providers
+            .clone()
+            .unwrap_or_else(ConfigProviders::new);
+        if let Some(provider_doi) = &args.provider_doi {
+            has_args = true;
+            if provider_doi == crate::options::CommandConfigArgs::_JUST_TO_PRINT_THIS_FIELD {
+                println!(
+                    "provider.doi: {}",
+                    providers.clone().doi.unwrap_or("<empty>".to_string())
+                );
+            } else {
+                providers.doi = Some(provider_doi.trim().to_string()).filter(|url| !url.is_empty());
+                config_edited = true;
+            }
+        }
+        if let Some(provider_arxiv) = &args.provider_arxiv {
+            has_args = true;
+            if provider_arxiv == crate::options::CommandConfigArgs::_JUST_TO_PRINT_THIS_FIELD {
+                println!(
+                    "provider.arxiv: {}",
+                    providers.clone().arxiv.unwrap_or("<empty>".to_string())
+                );
+            } else {
+                providers.arxiv =
+                    Some(provider_arxiv.trim().to_string()).filter(|url| !url.is_empty());
+                config_edited = true;
+            }
+        }
+        let mut watch = self.config.watch.clone().unwrap_or_else(ConfigWatch::new);
+        if let Some(watch_original) = &args.watch_original {
+            has_args = true;
+            if watch_original == crate::options::CommandConfigArgs::_JUST_TO_PRINT_THIS_FIELD {
+                println!(
+                    "watch.original: {}",
+                    watch
+                        .original
+                        .map(|original| format!("{:?}", original).to_lowercase())
+                        .unwrap_or("<empty>".to_string())
+                );
+            } else {
+                match WatchOriginal::from_str(watch_original, true) {
+                    Ok(original) => watch.original = Some(original),
+                    Err(_) => {
+                        eprintln!(
+                            "Error: Invalid value '{}' of 'watch.original', expected 'keep' or 'move'.",
+                            watch_original
+                        );
+                        return Err(());
+                    }
+                }
+                config_edited = true;
+            }
+        }
+        let mut note = self.config.note.clone().unwrap_or_else(ConfigNote::new);
+        if let Some(note_template) = &args.note_template {
+            has_args = true;
+            if note_template == crate::options::CommandConfigArgs::_JUST_TO_PRINT_THIS_FIELD {
+                println!(
+                    "note.template: {}",
+                    note.clone().template.unwrap_or("<empty>".to_string())
+                );
+            } else if note_template.trim().is_empty() {
+                note.template = None;
+                config_edited = true;
+            } else {
+                // the template is kept as an absolute path, to be found from any directory
+                let template = std::fs::canonicalize(note_template)
+                    .ok()
+                    .filter(|path| path.is_file())
+                    .ok_or_else(|| {
+                        eprintln!("Error: The template '{}' does not exist.", note_template);
+                    })?;
+                note.template = Some(template.to_str().unwrap().to_string());
+                config_edited = true;
+            }
+        }
+        if !has_args {
+            println!(
+                "owner.name: {}",
+                owner.clone().name.unwrap_or("<empty>".to_string())
+            );
+            println!(
+                "owner.email: {}",
+                owner.clone().email.unwrap_or("<empty>".to_string())
+            );
+            println!(
+                "owner.link: {}",
+                owner.clone().link.unwrap_or("<empty>".to_string())
+            );
+            println!(
+                "owner.affiliation: {}",
+                owner.clone().affiliation.unwrap_or("<empty>".to_string())
+            );
+            print_viewers(&viewers);
+            println!(
+                "cite.style: {}",
+                cite.clone().style.unwrap_or("<empty>".to_string())
+            );
+            println!(
+                "cite.format: {}",
+                cite.format
+                    .map(|format| format!("{:?}", format).to_lowercase())
+                    .unwrap_or("<empty>".to_string())
+            );
+            println!(
+                "id.pattern: {}",
+                id.clone().pattern.unwrap_or("<empty>".to_string())
+            );
+            println!(
+                "provider.doi: {}",
+                providers.clone().doi.unwrap_or("<empty>".to_string())
+            );
+            println!(
+                "provider.arxiv: {}",
+                providers.clone().arxiv.unwrap_or("<empty>".to_string())
+            );
+            println!(
+                "watch.original: {}",
+                watch
+                    .original
+                    .map(|original| format!("{:?}", original).to_lowercase())
+                    .unwrap_or("<empty>".to_string())
+            );
+            println!(
+                "note.template: {}",
+                note.clone().template.unwrap_or("<empty>".to_string())
+            );
+        } else if config_edited {
+            let mut new_config = self.config.clone();
+            new_config.owner = Some(owner);
+            new_config.viewers = if viewers.is_empty() {
+                None
+            } else {
+                Some(viewers)
+            };
+            new_config.cite = Some(cite);
+            new_config.id = Some(id);
+            new_config.providers = Some(providers);
+            new_config.watch = Some(watch);
+            new_config.note = Some(note);
+            new_config.to_file();
+        }
+        Ok(())
+    }
+
+    /// TermiPaper Command: dupes
+    pub fn cmd_dupes(&self) -> Result<(), ()> {
+        let args: &CommandDupesArgs = match &self.args.cmd {
+            Commands::Dupes(args) => args,
+            _ => unreachable!(
+                "Internal Error: This function should only be called in the 'dupes' command."
+            ),
+        };
+        if !(0.0..=1.0).contains(&args.threshold) {
+            eprintln!("Error: The threshold must be between 0 and 1.");
+            return Err(());
+        }
+        let database = self._get_database()?;
+        for id in &args.ids {
+            database._ck_exists(id).map_err(|_| ())?;
+        }
+        let mut prints = Self::_fingerprints(&database);
+        dupes::hash_files(&mut prints);
+        let duplicates: Vec<_> = dupes::find_duplicates(&prints, args.threshold)
+            .into_iter()
+            .filter(|duplicate| {
+                args.ids.is_empty()
+                    || args.ids.contains(&duplicate.ids.0)
+                    || args.ids.contains(&duplicate.ids.1)
+            })
+            .collect();
+        if duplicates.is_empty() {
+            println!("Info: No duplicates are found.");
+            return Ok(());
+        }
+        let mut table = Table::new()
+            .column("SCORE", false)
+            .column("ID", false)
+            .column("DUPLICATE", false)
+            .column("REASON", true);
+        for duplicate in &duplicates {
+            table.push_row(vec![
+                format!("{:.2}", duplicate.score),
+                duplicate.ids.0.clone(),
+                duplicate.ids.1.clone(),
+                duplicate
+                    .reasons
+                    .iter()
+                    .map(|reason| reason.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            ]);
+        }
+        table.print();
+        println!(
+            "Info: Found {} pairs of possible duplicates.",
+            duplicates.len()
+        );
+        Ok(())
+    }
+
+    /// Fingerprints of all papers for duplicate detection
+    fn _fingerprints(database: &Database) -> Vec<Fingerprint> {
+        database
+            .records()
+            .iter()
+            .map(|record| Fingerprint::new(&record.id, &record.entry, database.file_path(record)))
+            .collect()
+    }
+
+    /// Warn if the paper to add may be a duplicate of papers in the database
+    fn _warn_duplicates(database: &Database, id: &PaperID, paper: &PaperEntry) {
+        let mut prints = Self::_fingerprints(database);
+        prints.retain(|print| &print.id != id);
+        let file = paper.file.as_ref().map(std::path::PathBuf::from);
+        prints.push(Fingerprint::new(id, paper, file));
+        dupes::hash_files(&mut prints);
+        let (new, others) = prints.split_last().unwrap();
+        for other in others {
+            if let Some(duplicate) = dupes::compare(new, other, dupes::DEFAULT_THRESHOLD) {
+                eprintln!(
+                    "Warning: '{}' may be a duplicate of '{}' (score {:.2}: {}).",
+                    id,
+                    other.id,
+                    duplicate.score,
+                    duplicate
+                        .reasons
+                        .iter()
+                        .map(|reason| reason.to_string())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                );
+            }
+        }
+    }
+
+    /// TermiPaper Command: merge
+    pub fn cmd_merge(&self) -> Result<(), ()> {
+        let args = match &self.args.cmd {
+            Commands::Merge(args) => args,
+            _ => unreachable!(
+                "Internal Error: This function should only be called in the 'merge' command."
+            ),
+        };
+        let mut database = self._get_database()?;
+        let keep = self._resolve_id(&database, &args.keep, false, true)?;
+        let drop = self._resolve_id(&database, &args.drop, false, true)?;
+        database._ck_exists(&keep).map_err(|_| ())?;
+        database._ck_exists(&drop).map_err(|_| ())?;
+        if keep == drop {
+            eprintln!("Error: Cannot merge '{}' into itself.", keep);
+            return Err(());
+        }
+        if args.interactive && !std::io::stdin().is_terminal() {
+            eprintln!("Error: '--interactive' needs a terminal.");
+            return Err(());
+        }
+        let kept = database.record(&keep).unwrap().entry;
+        let dropped = database.record(&drop).unwrap().entry;
+        // the side preferred by the rule, which is also the default of the interactive choice
+        let by_rule = |conflict: &Conflict| match args.rule {
+            MergeRule::Keep => Side::Keep,
+            MergeRule::Drop => Side::Drop,
+            MergeRule::Newer if dropped.date_modified > kept.date_modified => Side::Drop,
+            MergeRule::Newer => Side::Keep,
+            MergeRule::Longer if conflict.lengths.1 > conflict.lengths.0 => Side::Drop,
+            MergeRule::Longer => Side::Keep,
+        };
+        let mut chooser = |conflict: &Conflict| {
+            let default = by_rule(conflict);
+            if !args.interactive {
+                return default;
+            }
+            // the prompt is printed to stderr, as in the fuzzy selection
+            eprintln!("Field '{}' differs:", conflict.field);
+            eprintln!("  1) {}: {}", keep, conflict.values.0);
+            eprintln!("  2) {}: {}", drop, conflict.values.1);
+            loop {
+                let default_choice = if default == Side::Keep { 1 } else { 2 };
+                eprint!("Choose [1-2] (default {}): ", default_choice);
+                std::io::stderr().flush().unwrap();
+                let mut input = String::new();
+                if std::io::stdin().read_line(&mut input).is_err() {
+                    return default;
+                }
+                match input.trim() {
+                    "" => return default,
+                    "1" => return Side::Keep,
+                    "2" => return Side::Drop,
+                    input => eprintln!("Error: Invalid choice '{}'.", input),
+                }
+            }
+        };
+        let merged = merge::merge_entries(&kept, &drop, &dropped, &mut chooser);
+        if args.dry_run {
+            let record = PaperRecord {
+                entry: merged,
+                ..database.record(&keep).unwrap()
+            };
+            Self::_print_details(&PaperDetails {
+                id: record.id.clone(),
+                category: record.category.clone(),
+                attachments: database.attachments(&record),
+                citation: cite::plain_citation(&record.entry),
+                note_text: None,
+                entry: record.entry,
+            });
+            return Ok(());
+        }
+        database.merge(&keep, &drop, merged).map_err(|e| {
+            eprintln!("Error: Cannot merge '{}' into '{}': {}", drop, keep, e);
+        })?;
+        println!(
+            "Info: Merged '{}' into '{}' ('{}' is kept as an alias).",
+            drop, keep, drop
+        );
+        Ok(())
+    }
+
+    /// TermiPaper Command: watch
+    pub fn cmd_watch(&self) -> Result<(), ()> {
+        let args = match &self.args.cmd {
+            Commands::Watch(args) => args,
+            _ => unreachable!(
+                "Internal Error: This function should only be called in the 'watch' command."
+            ),
+        };
+        let database = self._get_database()?;
+        // the queue keeps absolute paths, so it can be resumed from anywhere
+        let dir = match std::fs::canonicalize(&args.dir) {
+            Ok(dir) if dir.is_dir() => dir,
+            _ => {
+                eprintln!("Error: The directory '{}' does not exist.", args.dir);
+                return Err(());
+            }
+        };
+        let original = args
+            .original
+            .or(self.config.watch.as_ref().and_then(|watch| watch.original))
+            .unwrap_or_default();
+        let mut queue = WatchQueue::load(database.dir());
+        if !queue.files().is_empty() {
+            println!(
+                "Info: Resuming {} queued files from the last run.",
+                queue.files().len()
+            );
+        }
+        if args.existing {
+            let files = Self::_pdf_files(&dir, false).map_err(|e| {
+                eprintln!("Error: Cannot read the directory '{}': {}", args.dir, e);
+            })?;
+            for file in files {
+                queue.push(file);
+            }
+        }
+        let mut hashes = Self::_attachment_hashes(&database);
+        if args.once {
+            // the files are imported now, whether they have settled or not
+            for file in queue.files().to_vec() {
+                self._watch_import(&mut queue, &file, original, &mut hashes);
+            }
+            return Ok(());
+        }
+        // the watcher starts before the queue is processed, so no file is missed
+        let watcher = FolderWatcher::new(&dir).map_err(|e| {
+            eprintln!("Error: Cannot watch the directory '{}': {}", args.dir, e);
+        })?;
+        println!(
+            "Info: Watching '{}' for new PDF files (press Ctrl-C to stop)...",
+            args.dir
+        );
+        loop {
+            let mut queued = false;
+            for file in watcher.wait(std::time::Duration::from_secs(1)) {
+                if watch::is_pdf(&file) && queue.push(file.clone()) {
+                    println!("Info: Queued '{}'.", file.display());
+                    queued = true;
+                }
+            }
+            if queued {
+                if let Err(e) = queue.save() {
+                    eprintln!("Warning: failed to save the watch queue: {}", e);
+                }
+            }
+            for file in queue.files().to_vec() {
+                if !file.exists() || watch::is_settled(&file) {
+                    self._watch_import(&mut queue, &file, original, &mut hashes);
+                }
+            }
+        }
+    }
+
+    /// Import a queued file of 'watch' through the same path as 'add'
+    ///
+    /// The file is removed from the queue whatever the result, which is logged.
+    fn _watch_import(
+        &self,
+        queue: &mut WatchQueue,
+        file: &std::path::Path,
+        original: WatchOriginal,
+        hashes: &mut HashMap<String, PaperID>,
+    ) {
+        let name = file.display();
+        let message = if !file.exists() {
+            eprintln!("Warning: '{}' no longer exists.", name);
+            format!("missing {}", name)
+        } else {
+            match file_hash(file) {
+                Ok(hash) if hashes.contains_key(&hash) => {
+                    println!(
+                        "Info: Skipped '{}': the same file as '{}' is in the database.",
+                        name, hashes[&hash]
+                    );
+                    format!("skipped {} (same as {})", name, hashes[&hash])
+                }
+                Ok(hash) => {
+                    let args = CommandAddArgs {
+                        file: Some(file.to_string_lossy().into_owned()),
+                        ..Default::default()
+                    };
+                    let added = self
+                        ._get_database()
+                        .and_then(|mut database| self._add_paper(&mut database, &args));
+                    match added {
+                        Ok(id) => {
+                            println!("Info: Imported '{}' as '{}'.", name, id);
+                            hashes.insert(hash, id.clone());
+                            if original == WatchOriginal::Move {
+                                if let Err(e) = std::fs::remove_file(file) {
+                                    eprintln!("Warning: Cannot remove '{}': {}", name, e);
+                                }
+                            }
+                            format!("imported {} as {}", name, id)
+                        }
+                        Err(()) => {
+                            eprintln!("Warning: Failed to import '{}'.", name);
+                            format!("failed {}", name)
+                        }
+                    }
+                }
+                Err(e) => {
+                    eprintln!("Warning: Cannot read '{}': {}", name, e);
+                    format!("failed {} ({})", name, e)
+                }
+            }
+        };
+        queue.log(&message);
+        queue.remove(file);
+        if let Err(e) = queue.save() {
+            eprintln!("Warning: failed to save the watch queue: {}", e);
+        }
+    }
+
+    /// TermiPaper Command: init
+    ///
+    /// TODO: check whether the database is healthy (for example non-existent directories)
+    pub fn cmd_init(&self) -> Result<(), ()> {
+        // 1. Determine which directory the user wants to initialize
+        let dir_to_init = self._get_dir()?;
+        // 2. Check if the directory is the same as the activated directory
+        if let Some(activated) = &self.config.activated {
+            if Self::_is_same_dir(&dir_to_init, activated) {
+                println!(
+                    "Info: The current database is already activated: {}",
+                    dir_to_init
+                );
+                return Ok(());
+            }
+        };
+        // 3. Check if the directory is already initialized from the databases
+        let is_initialized = self._is_initialized(&dir_to_init);
+        // 4. Initialize the directory (if not)
+        if !is_initialized {
+            let mut databases = match &self.config.databases {
+                Some(databases) => databases.clone(),
+                None => std::collections::HashMap::new(),
+            };
+            databases.insert(dir_to_init.clone(), ConfigDatabase::new());
             let mut new_config = self.config.clone();
             new_config.databases = Some(databases);
             new_config.activated = Some(dir_to_init.clone()); // activate the new database
@@ -369,6 +1738,865 @@ impl Manager {
         Ok(())
     }
 
+    /// TermiPaper Command: list
+    pub fn cmd_list(&self) -> Result<(), ()> {
+        let args = match &self.args.cmd {
+            Commands::List(args) => args,
+            _ => unreachable!(
+                "Internal Error: This function should only be called in the 'list' command."
+            ),
+        };
+        let query = Self::_parse_query(&args.query)?;
+        let database = self._get_database()?;
+        // 1. filter the papers
+        let author = args.author.as_ref().map(|author| author.to_lowercase());
+        let mut records: Vec<PaperRecord> = database
+            .records()
+            .into_iter()
+            .filter(|record| {
+                if !query.matches(record) {
+                    return false;
+                }
+                if let Some(year) = &args.year {
+                    if !year.contains(record.entry.year) {
+                        return false;
+                    }
+                }
+                if let Some(author) = &author {
+                    let authors = record.entry.authors.clone().unwrap_or_default();
+                    if !authors
+                        .iter()
+                        .any(|a| a.to_string().to_lowercase().contains(author))
+                    {
+                        return false;
+                    }
+                }
+                if !args
+                    .tags
+                    .iter()
+                    .all(|tag| tags::has_tag(&record.entry, tag))
+                {
+                    return false;
+                }
+                if !args.status.is_empty() && !args.status.contains(&record.entry.reading_status())
+                {
+                    return false;
+                }
+                if args.priority.is_some() && record.entry.priority != args.priority {
+                    return false;
+                }
+                if let Some(min_rating) = args.min_rating {
+                    if record.entry.rating.is_none_or(|rating| rating < min_rating) {
+                        return false;
+                    }
+                }
+                if let Some(category) = &args.category {
+                    let category = category.trim_matches('/');
+                    if record.category != category
+                        && !record.category.starts_with(&format!("{}/", category))
+                    {
+                        return false;
+                    }
+                }
+                true
+            })
+            .collect();
+        // 2. sort the papers
+        records.sort_by(|a, b| args.sort.compare(a, b));
+        if args.reverse {
+            records.reverse();
+        }
+        if records.is_empty() {
+            println!("Info: No papers found.");
+            return Ok(());
+        }
+        // 3. paginate
+        let total = records.len();
+        let page_size = args.limit.filter(|limit| *limit > 0).unwrap_or(total);
+        let pages = total.div_ceil(page_size);
+        if args.page == 0 || args.page > pages {
+            eprintln!("Error: Page {} is out of range (1-{}).", args.page, pages);
+            return Err(());
+        }
+        let records = records
+            .iter()
+            .skip((args.page - 1) * page_size)
+            .take(page_size);
+        // 4. print the table
+        Self::_print_records(records, &args.columns);
+        if args.limit.is_some() {
+            println!("Page {}/{} ({} papers)", args.page, pages, total);
+        }
+        Ok(())
+    }
+
+    /// TermiPaper Command: search
+    pub fn cmd_search(&self) -> Result<(), ()> {
+        let args = match &self.args.cmd {
+            Commands::Search(args) => args,
+            _ => unreachable!(
+                "Internal Error: This function should only be called in the 'search' command."
+            ),
+        };
+        if args.fulltext {
+            return self._search_fulltext(&args.query.join(" "), args.limit);
+        }
+        let query = Self::_parse_query(&args.query)?;
+        let database = self._get_database()?;
+        let records: Vec<PaperRecord> = database
+            .records()
+            .into_iter()
+            .filter(|record| query.matches(record))
+            .collect();
+        if records.is_empty() {
+            println!("Info: No papers found.");
+            return Ok(());
+        }
+        Self::_print_records(&records, &args.columns);
+        Ok(())
+    }
+
+    /// Full-text search in the attachments (for the 'search' command)
+    fn _search_fulltext(&self, text: &str, limit: usize) -> Result<(), ()> {
+        let database = self._get_database()?;
+        let records = database.records();
+        // 1. bring the index up to date (only changed attachments and notes are extracted)
+        let mut index = FullTextIndex::load(database.dir());
+        let mut changed = false;
+        for id in index.paper_ids() {
+            if !records.iter().any(|record| record.id == id) {
+                changed |= index.remove(&id);
+            }
+        }
+        for record in &records {
+            match index.update(&record.id, Some(&record.entry)) {
+                Ok(true) => {
+                    changed = true;
+                    if self.args.verbose {
+                        println!("Info: Indexed the full text of '{}'.", record.id);
+                    }
+                }
+                Ok(false) => {}
+                Err(e) => eprintln!(
+                    "Warning: failed to index the full text of '{}': {}",
+                    record.id, e
+                ),
+            }
+        }
+        if changed {
+            if let Err(e) = index.save() {
+                eprintln!("Warning: failed to save the full-text index: {}", e);
+            }
+        }
+        // 2. search and print the results
+        let hits = index.search(text, limit);
+        if hits.is_empty() {
+            println!("Info: No papers found.");
+            return Ok(());
+        }
+        let highlight = |text: &str| {
+            if std::io::stdout().is_terminal() {
+                format!("\x1b[1;33m{}\x1b[0m", text)
+            } else {
+                format!("**{}**", text)
+            }
+        };
+        for (i, hit) in hits.iter().enumerate() {
+            let title = records
+                .iter()
+                .find(|record| record.id == hit.id)
+                .and_then(|record| record.entry.title.clone())
+                .unwrap_or_default();
+            let location = if hit.in_note {
+                "note".to_string()
+            } else {
+                format!("page {}", hit.page)
+            };
+            println!(
+                "{}. {} ({}, score {:.2})",
+                i + 1,
+                hit.id,
+                location,
+                hit.score
+            );
+            if !title.is_empty() {
+                println!("   {}", title);
+            }
+            let snippet: String = hit
+                .snippet
+                .iter()
+                .map(|(text, is_match)| {
+                    if *is_match {
+                        highlight(text)
+                    } else {
+                        text.clone()
+                    }
+                })
+                .collect();
+            println!("   {}", snippet);
+        }
+        Ok(())
+    }
+
+    /// TermiPaper Command: show
+    pub fn cmd_show(&self) -> Result<(), ()> {
+        let args = match &self.args.cmd {
+            Commands::Show(args) => args,
+            _ => unreachable!(
+                "Internal Error: This function should only be called in the 'show' command."
+            ),
+        };
+        let database = self._get_database()?;
+        let id = self._resolve_id(&database, &args.id, args.fuzzy, false)?;
+        database._ck_exists(&id).map_err(|_| ())?;
+        let record = database.record(&id).unwrap();
+        let details = PaperDetails {
+            id: record.id.clone(),
+            category: record.category.clone(),
+            attachments: database.attachments(&record),
+            citation: cite::plain_citation(&record.entry),
+            note_text: database
+                .note_path(&record)
+                .and_then(|path| std::fs::read_to_string(path).ok()),
+            entry: record.entry,
+        };
+        match args.format {
+            ShowFormat::Yaml => print!("{}", serde_yaml::to_string(&details).unwrap()),
+            ShowFormat::Json => println!("{}", serde_json::to_string_pretty(&details).unwrap()),
+            ShowFormat::Text => Self::_print_details(&details),
+        }
+        Ok(())
+    }
+
+    /// Print the paper details as human-readable text
+    fn _print_details(details: &PaperDetails) {
+        let entry = &details.entry;
+        let field = |name: &str, value: Option<String>| {
+            if let Some(value) = value {
+                println!("{:<10} {}", format!("{}:", name), value);
+            }
+        };
+        field("ID", Some(details.id.clone()));
+        field("Type", entry.entry_type.map(|t| t.to_string()));
+        field("Title", entry.title.clone());
+        field(
+            "Authors",
+            entry.authors.as_deref().map(|a| author::join(a, "; ")),
+        );
+        field("Year", entry.year.map(|y| y.to_string()));
+        field(
+            "Month",
+            entry
+                .month
+                .and_then(|month| chrono::Month::try_from(month as u8).ok())
+                .map(|month| month.name().to_string()),
+        );
+        field("Venue", entry.venue.clone());
+        field("Volume", entry.volume.clone());
+        field("Issue", entry.issue.clone());
+        field("Pages", entry.pages.clone());
+        field("Publisher", entry.publisher.clone());
+        field("DOI", entry.doi.clone());
+        field(
+            "arXiv",
+            entry.arxiv.as_ref().map(|arxiv| match entry.arxiv_version {
+                Some(version) => format!("{}v{}", arxiv, version),
+                None => arxiv.clone(),
+            }),
+        );
+        field("URL", entry.url.clone());
+        field("ISBN", entry.isbn.clone());
+        field("ISSN", entry.issn.clone());
+        field("Language", entry.language.clone());
+        field("Keywords", entry.keywords.as_ref().map(|k| k.join(", ")));
+        field("Tags", entry.tags.as_ref().map(|tags| tags.join(", ")));
+        field("Status", Some(entry.reading_status().to_string()));
+        field("Priority", entry.priority.map(|p| p.to_string()));
+        field("Rating", entry.rating.map(|rating| format!("{}/5", rating)));
+        let category = if details.category.is_empty() {
+            "(top)".to_string()
+        } else {
+            details.category.clone()
+        };
+        field("Category", Some(category));
+        field("Added", entry.date_added.clone());
+        field("Modified", entry.date_modified.clone());
+        field("Opened", entry.date_opened.clone());
+        field(
+            "Aliases",
+            entry.aliases.as_ref().map(|aliases| aliases.join(", ")),
+        );
+        if !details.attachments.is_empty() {
+            println!("Attachments:");
+            for attachment in &details.attachments {
+                println!(
+                    "  {} ({}, SHA-256: {})",
+                    attachment.file,
+                    Self::_human_size(attachment.size),
+                    attachment.sha256
+                );
+            }
+        } else if let Some(file) = &entry.file {
+            println!("Attachments:");
+            println!("  {} (missing)", file);
+        }
+        if let Some(abstract_text) = &entry.r#abstract {
+            println!("Abstract:");
+            println!("  {}", abstract_text);
+        }
+        if !details.citation.is_empty() {
+            println!("Citation:");
+            println!("  {}", details.citation);
+        }
+        if let Some(note_text) = &details.note_text {
+            println!("Note:");
+            for line in note_text.trim_end().lines() {
+                if line.is_empty() {
+                    println!();
+                } else {
+                    println!("  {}", line);
+                }
+            }
+        }
+    }
+
+    /// Human-readable file size
+    fn _human_size(size: u64) -> String {
+        const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+        let mut size = size as f64;
+        let mut unit = 0;
+        while size >= 1024.0 && unit < UNITS.len() - 1 {
+            size /= 1024.0;
+            unit += 1;
+        }
+        if unit == 0 {
+            format!("{} {}", size, UNITS[unit])
+        } else {
+            format!("{:.1} {}", size, UNITS[unit])
+        }
+    }
+
+    /// TermiPaper Command: note
+    pub fn cmd_note(&self) -> Result<(), ()> {
+        let args = match &self.args.cmd {
+            Commands::Note(args) => args,
+            _ => unreachable!(
+                "Internal Error: This function should only be called in the 'note' command."
+            ),
+        };
+        let mut database = self._get_database()?;
+        let id = self._resolve_id(&database, &args.id, args.fuzzy, false)?;
+        database._ck_exists(&id).map_err(|_| ())?;
+        // 1. create the note from the template if it does not exist
+        let path = self._ensure_note(&mut database, &id, !args.print_path)?;
+        if args.print_path {
+            println!("{}", path.to_str().unwrap());
+            return Ok(());
+        }
+        // 2. launch the editor
+        let editor = ViewerCommand::editor(&path).ok_or_else(|| {
+            eprintln!("Error: Cannot find an editor; please set $EDITOR.");
+        })?;
+        if self.args.verbose {
+            println!("Info: Running {} {}", editor.program, editor.args.join(" "));
+        }
+        editor.run(false).map_err(|e| {
+            eprintln!("Error: Failed to edit '{}': {}", path.to_str().unwrap(), e);
+        })
+    }
+
+    /// Path of the note of a paper, which is created from the template if it does not exist
+    fn _ensure_note(
+        &self,
+        database: &mut Database,
+        id: &PaperID,
+        info: bool,
+    ) -> Result<std::path::PathBuf, ()> {
+        let record = database.record(id).unwrap();
+        if let Some(path) = database.note_path(&record).filter(|path| path.exists()) {
+            return Ok(path);
+        }
+        let template = self.config.note.as_ref().and_then(|n| n.template.as_ref());
+        let template = match template {
+            Some(template) => std::fs::read_to_string(template).map_err(|e| {
+                eprintln!("Error: Cannot read the template '{}': {}", template, e);
+            })?,
+            None => note::DEFAULT_TEMPLATE.to_string(),
+        };
+        let file = note::file_name(id);
+        let path = database.dir().join(&record.category).join(&file);
+        let text = note::render(&template, id, &record.entry);
+        std::fs::write(&path, text).map_err(|e| {
+            eprintln!("Error: Cannot write to '{}': {}", path.to_str().unwrap(), e);
+        })?;
+        database
+            .modify(id, |entry| entry.note = Some(file))
+            .map_err(|e| {
+                eprintln!("Error: Cannot save the index: {}", e);
+            })?;
+        if info {
+            println!("Info: Created the note of '{}'.", id);
+        }
+        Ok(path)
+    }
+
+    /// TermiPaper Command: annotations
+    pub fn cmd_annotations(&self) -> Result<(), ()> {
+        let args = match &self.args.cmd {
+            Commands::Annotations(args) => args,
+            _ => unreachable!(
+                "Internal Error: This function should only be called in the 'annotations' command."
+            ),
+        };
+        let mut database = self._get_database()?;
+        let id = self._resolve_id(&database, &args.id, args.fuzzy, args.append)?;
+        database._ck_exists(&id).map_err(|_| ())?;
+        // 1. read the annotations from the PDF attachment
+        let record = database.record(&id).unwrap();
+        let path = match database.file_path(&record) {
+            Some(path) if path.exists() && watch::is_pdf(&path) => path,
+            Some(path) if path.exists() => {
+                eprintln!(
+                    "Error: The attachment of '{}' is not a PDF: {}",
+                    id,
+                    path.to_str().unwrap()
+                );
+                return Err(());
+            }
+            Some(path) => {
+                eprintln!(
+                    "Error: The attachment of '{}' does not exist: {}",
+                    id,
+                    path.to_str().unwrap()
+                );
+                return Err(());
+            }
+            None => {
+                eprintln!("Error: The paper '{}' has no attachment.", id);
+                return Err(());
+            }
+        };
+        let found = PdfFile::open(&path)
+            .and_then(|pdf| pdf.annotations())
+            .map_err(|e| {
+                eprintln!("Error: Cannot read '{}': {}", path.to_str().unwrap(), e);
+            })?;
+        if !args.append {
+            if found.is_empty() {
+                println!("Info: No annotations found in '{}'.", id);
+            }
+            let items: Vec<String> = found.iter().map(annotations::render).collect();
+            print!("{}", items.join("\n"));
+            return Ok(());
+        }
+        // 2. append the annotations that are not in the note yet
+        let note_path = self._ensure_note(&mut database, &id, true)?;
+        let text = std::fs::read_to_string(&note_path).map_err(|e| {
+            eprintln!(
+                "Error: Cannot read '{}': {}",
+                note_path.to_str().unwrap(),
+                e
+            );
+        })?;
+        let known = annotations::known_ids(&text);
+        let new: Vec<_> = found
+            .into_iter()
+            .filter(|annotation| !known.contains(&annotations::annotation_id(annotation)))
+            .collect();
+        if new.is_empty() {
+            println!("Info: No new annotations in '{}'.", id);
+            return Ok(());
+        }
+        std::fs::write(&note_path, annotations::append_to_note(&text, &new)).map_err(|e| {
+            eprintln!(
+                "Error: Cannot write to '{}': {}",
+                note_path.to_str().unwrap(),
+                e
+            );
+        })?;
+        println!(
+            "Info: Appended {} new annotation(s) to the note of '{}'.",
+            new.len(),
+            id
+        );
+        Ok(())
+    }
+
+    /// TermiPaper Command: status
+    pub fn cmd_status(&self) -> Result<(), ()> {
+        let args = match &self.args.cmd {
+            Commands::Status(args) => args,
+            _ => unreachable!(
+                "Internal Error: This function should only be called in the 'status' command."
+            ),
+        };
+        let mut database = self._get_database()?;
+        let changing = args.status.is_some() || args.priority.is_some() || args.clear;
+        let id = self._resolve_id(&database, &args.id, args.fuzzy, changing)?;
+        database._ck_exists(&id).map_err(|_| ())?;
+        if !changing {
+            let entry = database.record(&id).unwrap().entry;
+            let show = |value: Option<String>| value.unwrap_or_else(|| "(none)".to_string());
+            println!("Status:   {}", entry.reading_status());
+            println!("Priority: {}", show(entry.priority.map(|p| p.to_string())));
+            return Ok(());
+        }
+        database
+            .modify(&id, |entry| {
+                if args.clear {
+                    entry.status = None;
+                    entry.priority = None;
+                }
+                entry.status = args.status.or(entry.status);
+                entry.priority = args.priority.or(entry.priority);
+            })
+            .map_err(|e| {
+                eprintln!("Error: Cannot save the index: {}", e);
+            })?;
+        if args.clear {
+            println!("Info: Moved '{}' back to the inbox.", id);
+        }
+        if let Some(status) = args.status {
+            println!("Info: Marked '{}' as {}.", id, status);
+        }
+        if let Some(priority) = args.priority {
+            println!("Info: Set the priority of '{}' to {}.", id, priority);
+        }
+        Ok(())
+    }
+
+    /// TermiPaper Command: rate
+    pub fn cmd_rate(&self) -> Result<(), ()> {
+        let args = match &self.args.cmd {
+            Commands::Rate(args) => args,
+            _ => unreachable!(
+                "Internal Error: This function should only be called in the 'rate' command."
+            ),
+        };
+        let mut database = self._get_database()?;
+        let id = self._resolve_id(&database, &args.id, args.fuzzy, true)?;
+        database
+            .modify(&id, |entry| entry.rating = args.rating)
+            .map_err(|_| ())?;
+        match args.rating {
+            Some(rating) => println!("Info: Rated '{}' {}/5.", id, rating),
+            None => println!("Info: Cleared the rating of '{}'.", id),
+        }
+        Ok(())
+    }
+
+    /// TermiPaper Command: queue
+    pub fn cmd_queue(&self) -> Result<(), ()> {
+        let args = match &self.args.cmd {
+            Commands::Queue(args) => args,
+            _ => unreachable!(
+                "Internal Error: This function should only be called in the 'queue' command."
+            ),
+        };
+        let database = self._get_database()?;
+        let mut records: Vec<PaperRecord> = database
+            .records()
+            .into_iter()
+            .filter(|record| match record.entry.reading_status() {
+                ReadingStatus::Reading | ReadingStatus::ToRead => true,
+                ReadingStatus::Inbox => args.inbox,
+                _ => false,
+            })
+            .collect();
+        if records.is_empty() {
+            println!("Info: The reading queue is empty.");
+            return Ok(());
+        }
+        records.sort_by(list::compare_queue);
+        let total = records.len();
+        let limit = args.limit.unwrap_or(total).min(total);
+        Self::_print_records(&records[..limit], &args.columns);
+        if limit < total {
+            println!("Info: Showing {} of {} papers in the queue.", limit, total);
+        }
+        Ok(())
+    }
+
+    /// Normalize the tags given by the user, e.g. "method / beamforming" as "method/beamforming"
+    fn _normalize_tags(tags: &[String]) -> Result<Vec<String>, ()> {
+        tags.iter()
+            .map(|tag| {
+                tags::normalize_tag(tag).ok_or_else(|| {
+                    eprintln!("Error: Invalid tag '{}'.", tag);
+                })
+            })
+            .collect()
+    }
+
+    /// TermiPaper Command: tag
+    pub fn cmd_tag(&self) -> Result<(), ()> {
+        let args = match &self.args.cmd {
+            Commands::Tag(args) => args,
+            _ => unreachable!(
+                "Internal Error: This function should only be called in the 'tag' command."
+            ),
+        };
+        let mut database = self._get_database()?;
+        let save_error = |e: Box<dyn std::error::Error>| {
+            eprintln!("Error: Cannot save the index: {}", e);
+        };
+        match &args.action {
+            TagAction::Add { id, tags, fuzzy } | TagAction::Remove { id, tags, fuzzy } => {
+                let id = self._resolve_id(&database, id, *fuzzy, true)?;
+                database._ck_exists(&id).map_err(|_| ())?;
+                let given = Self::_normalize_tags(tags)?;
+                let adding = matches!(args.action, TagAction::Add { .. });
+                let mut count = 0;
+                database
+                    .retag(|paper, entry| {
+                        if *paper != id {
+                            return false;
+                        }
+                        count = if adding {
+                            tags::add_tags(entry, &given)
+                        } else {
+                            tags::remove_tags(entry, &given)
+                        };
+                        count > 0
+                    })
+                    .map_err(save_error)?;
+                match (adding, count) {
+                    (true, 0) => println!("Info: '{}' already has the tags.", id),
+                    (true, count) => println!("Info: Added {} tag(s) to '{}'.", count, id),
+                    (false, 0) => println!("Info: '{}' has none of the tags.", id),
+                    (false, count) => println!("Info: Removed {} tag(s) from '{}'.", count, id),
+                }
+            }
+            TagAction::Rename { old, new } => {
+                let old = Self::_normalize_tags(std::slice::from_ref(old))?.remove(0);
+                let new = Self::_normalize_tags(std::slice::from_ref(new))?.remove(0);
+                let renamed = database
+                    .retag(|_, entry| tags::rename_tag(entry, &old, &new))
+                    .map_err(save_error)?;
+                if renamed.is_empty() {
+                    eprintln!("Error: No paper has the tag '{}'.", old);
+                    return Err(());
+                }
+                println!(
+                    "Info: Renamed '{}' to '{}' in {} paper(s).",
+                    old,
+                    new,
+                    renamed.len()
+                );
+            }
+            TagAction::List => {
+                let records = database.records();
+                let counts = tags::count_tags(records.iter().map(|record| &record.entry));
+                if counts.is_empty() {
+                    println!("Info: No tags found.");
+                }
+                // the tags below another one are indented with their last level
+                for (tag, count) in counts {
+                    let mut levels = tag.rsplit(tags::SEPARATOR);
+                    let name = levels.next().unwrap();
+                    println!("{}{} ({})", "  ".repeat(levels.count()), name, count);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// TermiPaper Command: open
+    pub fn cmd_open(&self) -> Result<(), ()> {
+        let args = match &self.args.cmd {
+            Commands::Open(args) => args,
+            _ => unreachable!(
+                "Internal Error: This function should only be called in the 'open' command."
+            ),
+        };
+        let mut database = self._get_database()?;
+        let id = self._resolve_id(&database, &args.id, args.fuzzy, false)?;
+        database._ck_exists(&id).map_err(|_| ())?;
+        // 1. resolve the path of the attachment
+        let record = database.record(&id).unwrap();
+        let path = match database.file_path(&record) {
+            Some(path) if path.exists() => path,
+            Some(path) => {
+                eprintln!(
+                    "Error: The attachment of '{}' does not exist: {}",
+                    id,
+                    path.to_str().unwrap()
+                );
+                return Err(());
+            }
+            None => {
+                eprintln!("Error: The paper '{}' has no attachment.", id);
+                return Err(());
+            }
+        };
+        if args.print_path {
+            println!("{}", path.to_str().unwrap());
+            return Ok(());
+        }
+        // 2. launch the viewer
+        let viewer = match ViewerCommand::new(self.config.viewers.as_ref(), &path, args.page) {
+            Some(viewer) => viewer,
+            None => {
+                eprintln!(
+                    "Error: Cannot find a viewer for '{}'.",
+                    path.to_str().unwrap()
+                );
+                return Err(());
+            }
+        };
+        if args.page.is_some() && !viewer.with_page {
+            eprintln!(
+                "Warning: The viewer '{}' is not configured with a page ({{page}}), opening the first page.",
+                viewer.program
+            );
+        }
+        if self.args.verbose {
+            println!("Info: Running {} {}", viewer.program, viewer.args.join(" "));
+        }
+        // 3. record the time when it is opened
+        if let Err(e) = database.mark_opened(&id) {
+            eprintln!("Warning: Failed to record the opening time: {}", e);
+        }
+        viewer.run(args.background).map_err(|e| {
+            eprintln!("Error: Failed to open '{}': {}", path.to_str().unwrap(), e);
+        })
+    }
+
+    /// TermiPaper Command: refresh
+    pub fn cmd_refresh(&self) -> Result<(), ()> {
+        let args = match &self.args.cmd {
+            Commands::Refresh(args) => args,
+            _ => unreachable!(
+                "Internal Error: This function should only be called in the 'refresh' command."
+            ),
+        };
+        let mut database = self._get_database()?;
+        let records: Vec<PaperRecord> = if args.ids.is_empty() {
+            database
+                .records()
+                .into_iter()
+                .filter(|record| record.entry.arxiv.is_some())
+                .collect()
+        } else {
+            let mut records = vec![];
+            for id in &args.ids {
+                database._ck_exists(id).map_err(|_| ())?;
+                records.push(database.record(id).unwrap());
+            }
+            records
+        };
+        let (mut checked, mut updates, mut failed) = (0, 0, 0);
+        for record in &records {
+            let arxiv = match &record.entry.arxiv {
+                Some(arxiv) => arxiv,
+                None => {
+                    println!("Info: '{}' has no arXiv identifier.", record.id);
+                    continue;
+                }
+            };
+            let latest = ArxivId {
+                id: arxiv.clone(),
+                version: None,
+            };
+            let metadata = match self._fetch_arxiv(&latest) {
+                Ok(metadata) => metadata,
+                Err(()) => {
+                    failed += 1;
+                    continue;
+                }
+            };
+            checked += 1;
+            let stored = record.entry.arxiv_version.unwrap_or(0);
+            let latest_version = metadata.id.version.unwrap_or(0);
+            let new_version = latest_version > stored;
+            let new_doi = metadata
+                .entry
+                .doi
+                .clone()
+                .filter(|doi| record.entry.doi.as_ref() != Some(doi));
+            if new_version {
+                println!(
+                    "Info: '{}': arXiv v{} is available (stored: {}).",
+                    record.id,
+                    latest_version,
+                    match record.entry.arxiv_version {
+                        Some(version) => format!("v{}", version),
+                        None => "unknown version".to_string(),
+                    }
+                );
+            }
+            if let Some(doi) = &new_doi {
+                println!(
+                    "Info: '{}': the journal DOI {} has been attached to the preprint.",
+                    record.id, doi
+                );
+            }
+            if !new_version && new_doi.is_none() {
+                println!(
+                    "Info: '{}' is up to date (arXiv {}).",
+                    record.id, metadata.id
+                );
+                continue;
+            }
+            updates += 1;
+            if !args.update {
+                continue;
+            }
+            let paper = PaperEntry {
+                doi: new_doi,
+                venue: metadata.entry.venue.clone(),
+                arxiv_version: metadata.id.version,
+                ..PaperEntry::new()
+            };
+            if database.edit(record.id.clone(), paper).is_err() {
+                failed += 1;
+                continue;
+            }
+            // the new version is attached, keeping the previous file as '<id>-alt'
+            let downloaded_pdf = if new_version {
+                Self::_download_pdf(database.dir(), &metadata.id, &metadata.pdf_url)
+            } else {
+                None
+            };
+            if let Some(path) = downloaded_pdf {
+                let result = database.replace_file(&record.id, &path);
+                if path.exists() {
+                    let _ = std::fs::remove_file(path);
+                }
+                match result {
+                    Ok(Some(kept)) => println!(
+                        "Info: The previous attachment of '{}' is kept as '{}'.",
+                        record.id, kept
+                    ),
+                    Ok(None) => {}
+                    Err(e) => {
+                        eprintln!(
+                            "Error: Cannot attach the new version to '{}': {}",
+                            record.id, e
+                        );
+                        failed += 1;
+                        continue;
+                    }
+                }
+            }
+            println!("Info: Updated '{}'.", record.id);
+        }
+        println!(
+            "Info: Checked {} arXiv papers: {} with updates{}.",
+            checked,
+            updates,
+            if updates > 0 && !args.update {
+                " (use '--update' to apply them)"
+            } else {
+                ""
+            }
+        );
+        if failed > 0 {
+            return Err(());
+        }
+        Ok(())
+    }
+
     /// TermiPaper Command: remove
     pub fn cmd_remove(&self) -> Result<(), ()> {
         // 1. get the correct database directory
@@ -384,16 +2612,13 @@ impl Manager {
         // 3. get the paper entry from the user input
         let args = match &self.args.cmd {
             Commands::Remove(args) => args,
-            _ => {
-                assert!(
-                    false,
-                    "Internal Error: This function should only be called in the 'remove' command."
-                );
-                return Err(());
-            }
+            _ => unreachable!(
+                "Internal Error: This function should only be called in the 'remove' command."
+            ),
         };
         // 4. remove the paper entry from the database
-        database.remove(args.id.clone()).map_err(|_| ())?;
+        let id = self._resolve_id(&database, &args.id, args.fuzzy, true)?;
+        database.remove(id).map_err(|_| ())?;
         // 5. save the database to the file (TODO)
         Ok(())
     }
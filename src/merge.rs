@@ -0,0 +1,301 @@
+use super::author;
+use super::database::{EntryType, PaperEntry, PaperID, Priority, ReadingStatus};
+use super::tags;
+use serde_json::Value;
+
+/// Which paper a merged field is taken from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    /// The paper that is kept
+    Keep,
+    /// The paper that is merged into it and dropped
+    Drop,
+}
+
+/// Field whose values differ between the two papers
+#[derive(Debug, Clone)]
+pub struct Conflict {
+    /// Field name, e.g. "title" or "extra.volume"
+    pub field: String,
+    /// Values of the kept and the dropped papers, as shown to the user
+    pub values: (String, String),
+    /// Lengths of the values, for choosing the more complete one
+    pub lengths: (usize, usize),
+}
+
+/// Choose between the two values of a field, which are both set and different
+fn choose<T, F>(
+    field: &str,
+    keep: &mut Option<T>,
+    drop: Option<T>,
+    show: F,
+    chooser: &mut dyn FnMut(&Conflict) -> Side,
+) where
+    T: PartialEq,
+    F: Fn(&T) -> String,
+{
+    match (keep.as_ref(), drop) {
+        (None, drop) => *keep = drop,
+        (Some(kept), Some(dropped)) if *kept != dropped => {
+            let values = (show(kept), show(&dropped));
+            let conflict = Conflict {
+                field: field.to_string(),
+                lengths: (values.0.chars().count(), values.1.chars().count()),
+                values,
+            };
+            if chooser(&conflict) == Side::Drop {
+                *keep = Some(dropped);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Merge the dropped paper into the kept one
+///
+/// Fields set in only one paper are always kept, and `chooser` decides the fields set
+/// differently in both. The dates are merged as the earliest added and the latest opened,
+/// the keywords and tags of both are kept, and the ID of the dropped paper becomes an alias
+/// of the kept one.
+pub fn merge_entries(
+    keep: &PaperEntry,
+    drop_id: &PaperID,
+    drop: &PaperEntry,
+    chooser: &mut dyn FnMut(&Conflict) -> Side,
+) -> PaperEntry {
+    let mut merged = keep.clone();
+    let text = |value: &String| value.clone();
+    choose(
+        "title",
+        &mut merged.title,
+        drop.title.clone(),
+        text,
+        chooser,
+    );
+    choose(
+        "authors",
+        &mut merged.authors,
+        drop.authors.clone(),
+        |authors| author::join(authors, "; "),
+        chooser,
+    );
+    choose("year", &mut merged.year, drop.year, u32::to_string, chooser);
+    choose(
+        "venue",
+        &mut merged.venue,
+        drop.venue.clone(),
+        text,
+        chooser,
+    );
+    choose("doi", &mut merged.doi, drop.doi.clone(), text, chooser);
+    // the arXiv identifier and its version go together
+    let mut arxiv = keep.arxiv.clone().map(|id| (id, keep.arxiv_version));
+    let show_arxiv = |(id, version): &(String, Option<u32>)| match version {
+        Some(version) => format!("{}v{}", id, version),
+        None => id.clone(),
+    };
+    let dropped_arxiv = drop.arxiv.clone().map(|id| (id, drop.arxiv_version));
+    choose("arxiv", &mut arxiv, dropped_arxiv, show_arxiv, chooser);
+    (merged.arxiv, merged.arxiv_version) = match arxiv {
+        Some((id, version)) => (Some(id), version),
+        None => (None, None),
+    };
+    choose(
+        "type",
+        &mut merged.entry_type,
+        drop.entry_type,
+        EntryType::to_string,
+        chooser,
+    );
+    choose(
+        "month",
+        &mut merged.month,
+        drop.month,
+        u32::to_string,
+        chooser,
+    );
+    for (field, kept, dropped) in [
+        ("volume", &mut merged.volume, &drop.volume),
+        ("issue", &mut merged.issue, &drop.issue),
+        ("pages", &mut merged.pages, &drop.pages),
+        ("publisher", &mut merged.publisher, &drop.publisher),
+        ("abstract", &mut merged.r#abstract, &drop.r#abstract),
+        ("url", &mut merged.url, &drop.url),
+        ("isbn", &mut merged.isbn, &drop.isbn),
+        ("issn", &mut merged.issn, &drop.issn),
+        ("language", &mut merged.language, &drop.language),
+    ] {
+        choose(field, kept, dropped.clone(), text, chooser);
+    }
+    // the keywords and tags of both papers are kept
+    for keyword in drop.keywords.iter().flatten() {
+        let keywords = merged.keywords.get_or_insert_with(Vec::new);
+        if !keywords.contains(keyword) {
+            keywords.push(keyword.clone());
+        }
+    }
+    if let Some(dropped_tags) = &drop.tags {
+        tags::add_tags(&mut merged, dropped_tags);
+    }
+    choose(
+        "status",
+        &mut merged.status,
+        drop.status,
+        ReadingStatus::to_string,
+        chooser,
+    );
+    choose(
+        "priority",
+        &mut merged.priority,
+        drop.priority,
+        Priority::to_string,
+        chooser,
+    );
+    choose(
+        "rating",
+        &mut merged.rating,
+        drop.rating,
+        u8::to_string,
+        chooser,
+    );
+    choose("file", &mut merged.file, drop.file.clone(), text, chooser);
+    // the unhandled fields are merged one by one
+    if let Some(dropped_extra) = &drop.extra {
+        let extra = merged.extra.get_or_insert_with(Default::default);
+        for (key, dropped) in dropped_extra {
+            let mut value = extra.get(key).cloned();
+            let show = |value: &Value| match value {
+                Value::String(s) => s.clone(),
+                value => value.to_string(),
+            };
+            choose(
+                &format!("extra.{}", key),
+                &mut value,
+                Some(dropped.clone()),
+                show,
+                chooser,
+            );
+            if let Some(value) = value {
+                extra.insert(key.clone(), value);
+            }
+        }
+    }
+    // earliest added, latest opened (the dates are formatted to sort as strings)
+    merged.date_added = [&keep.date_added, &drop.date_added]
+        .into_iter()
+        .flatten()
+        .min()
+        .cloned();
+    merged.date_opened = [&keep.date_opened, &drop.date_opened]
+        .into_iter()
+        .flatten()
+        .max()
+        .cloned();
+    let mut aliases = keep.aliases.clone().unwrap_or_default();
+    for alias in drop.aliases.iter().flatten().chain([drop_id]) {
+        if !aliases.contains(alias) {
+            aliases.push(alias.clone());
+        }
+    }
+    merged.aliases = Some(aliases);
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn papers() -> (PaperEntry, PaperEntry) {
+        let keep = PaperEntry {
+            title: Some("Beam Training".to_string()),
+            year: Some(2024),
+            arxiv: Some("2401.01234".to_string()),
+            arxiv_version: Some(2),
+            keywords: Some(vec!["beams".to_string()]),
+            tags: Some(vec!["todo".to_string()]),
+            date_added: Some("2024-02-01 10:00:00".to_string()),
+            aliases: Some(vec!["old".to_string()]),
+            ..PaperEntry::new()
+        };
+        let drop = PaperEntry {
+            title: Some("Beam Training for Near-Field Communications".to_string()),
+            year: Some(2024),
+            venue: Some("IEEE Trans. Wireless Commun.".to_string()),
+            arxiv: Some("2401.01234".to_string()),
+            arxiv_version: Some(3),
+            keywords: Some(vec!["beams".to_string(), "near field".to_string()]),
+            tags: Some(vec!["TODO".to_string(), "method".to_string()]),
+            date_added: Some("2024-01-01 10:00:00".to_string()),
+            date_opened: Some("2024-03-01 10:00:00".to_string()),
+            ..PaperEntry::new()
+        };
+        (keep, drop)
+    }
+
+    #[test]
+    fn merge_fields() {
+        let (keep, drop) = papers();
+        let mut conflicts = vec![];
+        let merged = merge_entries(&keep, &"dropped".to_string(), &drop, &mut |conflict| {
+            conflicts.push(conflict.field.clone());
+            Side::Keep
+        });
+        // only the fields set differently in both are conflicts
+        assert_eq!(conflicts, ["title", "arxiv"]);
+        assert_eq!(merged.title, keep.title);
+        assert_eq!(merged.arxiv_version, Some(2));
+        assert_eq!(merged.venue, drop.venue);
+        assert_eq!(merged.keywords, drop.keywords);
+        assert_eq!(
+            merged.tags,
+            Some(vec!["todo".to_string(), "method".to_string()])
+        );
+        assert_eq!(merged.date_added, drop.date_added);
+        assert_eq!(merged.date_opened, drop.date_opened);
+        assert_eq!(
+            merged.aliases,
+            Some(vec!["old".to_string(), "dropped".to_string()])
+        );
+    }
+
+    #[test]
+    fn choose_dropped_values() {
+        let (keep, drop) = papers();
+        let merged = merge_entries(&keep, &"dropped".to_string(), &drop, &mut |conflict| {
+            // the more complete value (the newer version for the arXiv identifier)
+            match conflict.lengths.1 >= conflict.lengths.0 {
+                true => Side::Drop,
+                false => Side::Keep,
+            }
+        });
+        assert_eq!(merged.title, drop.title);
+        // the version goes with the identifier
+        assert_eq!(merged.arxiv.as_deref(), Some("2401.01234"));
+        assert_eq!(merged.arxiv_version, Some(3));
+    }
+
+    #[test]
+    fn merge_extra() {
+        let (mut keep, mut drop) = papers();
+        keep.extra = Some([("note".to_string(), Value::from("kept"))].into());
+        drop.extra = Some(
+            [
+                ("note".to_string(), Value::from("dropped")),
+                ("edition".to_string(), Value::from(2)),
+            ]
+            .into(),
+        );
+        let mut values = vec![];
+        let merged = merge_entries(&keep, &"dropped".to_string(), &drop, &mut |conflict| {
+            values.push((conflict.field.clone(), conflict.values.clone()));
+            Side::Drop
+        });
+        assert!(values.contains(&(
+            "extra.note".to_string(),
+            ("kept".to_string(), "dropped".to_string())
+        )));
+        let extra = merged.extra.unwrap();
+        assert_eq!(extra["note"], "dropped");
+        assert_eq!(extra["edition"], 2);
+    }
+}
@@ -0,0 +1,478 @@
+use super::arxiv::ArxivId;
+use super::author::Author;
+use super::csl;
+use super::database::{normalize_doi, PaperEntry};
+use super::pdf::PdfFile;
+use serde_json::Value;
+use std::path::Path;
+use std::time::Duration;
+
+/// Timeout of the requests to the metadata providers
+const TIMEOUT: Duration = Duration::from_secs(20);
+
+/// CSL variables of the fetched metadata that are kept in `PaperEntry::extra`
+/// (besides those with a field, like the volume)
+///
+/// Providers like Crossref return much more (e.g. the reference list), which is dropped.
+const KEPT_VARIABLES: [&str; 2] = ["type", "issued"];
+
+/// Text without the markup, e.g. the JATS tags in the abstracts of Crossref
+///
+/// Paragraphs are separated by a space, inline tags are just removed,
+/// and titles (like "Abstract") are dropped.
+fn strip_tags(text: &str) -> String {
+    let mut stripped = String::new();
+    let mut tag: Option<String> = None;
+    let mut in_title = false;
+    for c in text.chars() {
+        match (c, tag.as_mut()) {
+            ('<', None) => tag = Some(String::new()),
+            ('>', Some(tag_text)) => {
+                let closing = tag_text.starts_with('/');
+                let name = tag_text.trim_start_matches('/');
+                let name = name.split_whitespace().next().unwrap_or_default();
+                match name.rsplit(':').next().unwrap_or_default() {
+                    "title" => in_title = !closing,
+                    "p" | "sec" => stripped.push(' '),
+                    _ => {}
+                }
+                tag = None;
+            }
+            (c, Some(tag_text)) => tag_text.push(c),
+            (c, None) if !in_title => stripped.push(c),
+            _ => {}
+        }
+    }
+    collapse_whitespace(&stripped)
+}
+
+/// Paper entry of the CSL-JSON metadata returned by a provider
+fn entry_from_csl(json: &Value, doi: &str) -> Result<PaperEntry, String> {
+    // the provider may return an item without an ID, so the DOI is used instead
+    let mut item = json.clone();
+    if let Some(object) = item.as_object_mut() {
+        object.insert("id".to_string(), Value::String(doi.to_string()));
+        object.remove("citation-key");
+    }
+    let (_, mut entry) = csl::from_csl(&item)?;
+    if let Some(extra) = entry.extra.as_mut() {
+        extra.retain(|key, _| KEPT_VARIABLES.contains(&key.as_str()));
+    }
+    entry.extra = entry.extra.filter(|extra| !extra.is_empty());
+    entry.r#abstract = entry.r#abstract.as_deref().map(strip_tags);
+    entry.doi = normalize_doi(doi);
+    Ok(entry)
+}
+
+/// Fetch the metadata of a DOI (title, authors, year, venue, ...)
+///
+/// By default, the DOI is resolved at doi.org with the `doi` crate (honoring the proxy
+/// environment variables); if `base_url` is given, `<base_url>/<DOI>` is requested instead,
+/// e.g. for a local mirror or a mock server.
+/// Both are expected to return CSL-JSON by content negotiation.
+pub fn fetch_doi(doi: &str, base_url: Option<&str>) -> Result<PaperEntry, String> {
+    let doi = normalize_doi(doi).ok_or("empty DOI")?;
+    let json: Value = match base_url {
+        None => doi::DoiBuilder::new()
+            .doi(doi.as_str())
+            .env_proxy(true)
+            .build()
+            .metadata_json()
+            .map_err(|e| e.to_string())?,
+        Some(base_url) => {
+            let url = format!("{}/{}", base_url.trim_end_matches('/'), doi);
+            let body = ureq::AgentBuilder::new()
+                .timeout(TIMEOUT)
+                .build()
+                .get(&url)
+                .set("Accept", "application/vnd.citationstyles.csl+json")
+                .call()
+                .map_err(|e| e.to_string())?
+                .into_string()
+                .map_err(|e| e.to_string())?;
+            serde_json::from_str(&body).map_err(|e| format!("invalid CSL-JSON: {}", e))?
+        }
+    };
+    entry_from_csl(&json, &doi)
+}
+
+/// Number of pages searched for a DOI or an arXiv identifier
+const SCANNED_PAGES: u32 = 2;
+
+/// Metadata extracted from a PDF file
+#[derive(Debug, Clone)]
+pub struct PdfMetadata {
+    /// Paper entry with the extracted fields (title, authors, year, venue, DOI, arXiv)
+    pub entry: PaperEntry,
+    /// Whether the title is guessed from the largest font on the first page,
+    /// rather than read from the document metadata
+    pub title_guessed: bool,
+}
+
+impl PdfMetadata {
+    /// Whether the metadata should be reviewed manually
+    ///
+    /// This is the case if there is no title, or the title is only guessed without a DOI
+    /// or an arXiv identifier to confirm the paper.
+    pub fn low_confidence(&self) -> bool {
+        let entry = &self.entry;
+        entry.title.is_none()
+            || (self.title_guessed && entry.doi.is_none() && entry.arxiv.is_none())
+    }
+}
+
+/// Values of an XMP property, e.g. "dc:title" or "prism:doi"
+///
+/// Both the element form (with `rdf:li` items for lists) and the attribute form are read.
+fn xmp_values(xmp: &str, property: &str) -> Vec<String> {
+    let unescape = |text: &str| {
+        let text = quick_xml::escape::unescape(text)
+            .map(|text| text.into_owned())
+            .unwrap_or_else(|_| text.to_string());
+        collapse_whitespace(&text)
+    };
+    let mut values = vec![];
+    // attribute form: prism:doi="..." (or with single quotes)
+    for quote in ['"', '\''] {
+        let attribute = format!("{}={}", property, quote);
+        if let Some(start) = xmp.find(&attribute).map(|i| i + attribute.len()) {
+            if let Some(end) = xmp[start..].find(quote) {
+                values.push(unescape(&xmp[start..start + end]));
+            }
+        }
+    }
+    // element form: <dc:title><rdf:Alt><rdf:li xml:lang="x-default">...</rdf:li>...
+    let (open, close) = (format!("<{}", property), format!("</{}>", property));
+    let mut rest = xmp;
+    while let Some(start) = rest.find(&open) {
+        rest = &rest[start + open.len()..];
+        // skip longer names with the same prefix, e.g. "dc:titles"
+        if !rest.starts_with(['>', ' ', '\t', '\r', '\n']) {
+            continue;
+        }
+        let Some(end) = rest.find(&close) else { break };
+        let content = match rest[..end].find('>') {
+            Some(i) if !rest[..i].ends_with('/') => &rest[i + 1..end],
+            _ => "",
+        };
+        rest = &rest[end + close.len()..];
+        let mut items: Vec<&str> = content
+            .split("<rdf:li")
+            .skip(1)
+            .filter_map(|item| item.split_once('>').map(|(_, item)| item))
+            .map(|item| item.split("</rdf:li>").next().unwrap_or(""))
+            .collect();
+        if items.is_empty() {
+            items.push(content);
+        }
+        values.extend(items.into_iter().map(unescape));
+    }
+    values.retain(|value| !value.is_empty());
+    values
+}
+
+/// Collapse the whitespace, e.g. of the line breaks in a title
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Whether the text looks like a real title rather than a file name or a placeholder
+/// (e.g. "Microsoft Word - paper.docx" or "untitled")
+fn is_title(title: &str) -> bool {
+    let lower = title.to_lowercase();
+    title.split_whitespace().count() >= 2
+        && title.chars().filter(|c| c.is_alphabetic()).count() >= 4
+        && !lower.starts_with("microsoft word")
+        && !lower.starts_with("untitled")
+        && ![".pdf", ".dvi", ".doc", ".docx", ".tex", ".ps"]
+            .iter()
+            .any(|ext| lower.ends_with(ext))
+        && !title.contains(['\\', '/'])
+}
+
+/// Authors in the `/Author` field, e.g. "A. Author and B. Author" or "Author, A.; Author, B."
+fn split_authors(authors: &str) -> Vec<String> {
+    let names: Vec<&str> = if authors.contains(';') {
+        authors.split(';').collect()
+    } else {
+        let names: Vec<&str> = authors
+            .split(" and ")
+            .flat_map(|names| names.split(", "))
+            .collect();
+        // "Family, Given" is a single name rather than two
+        if names.iter().all(|name| name.trim().contains(' ')) {
+            names
+        } else {
+            authors.split(" and ").collect()
+        }
+    };
+    names
+        .into_iter()
+        .map(collapse_whitespace)
+        .map(|name| {
+            // e.g. "A. Author, B. Author, and C. Author"
+            let name = name.trim_start_matches("and ").trim_end_matches(',');
+            name.to_string()
+        })
+        .filter(|name| name.chars().any(char::is_alphabetic))
+        .collect()
+}
+
+/// First DOI in the text, e.g. "doi: 10.1109/TWC.2024.1234567."
+fn find_doi(text: &str) -> Option<String> {
+    let mut rest = text;
+    while let Some(start) = rest.find("10.") {
+        let before = &text[..text.len() - rest.len() + start];
+        let candidate = &rest[start..];
+        rest = &rest[start + 3..];
+        if before.ends_with(|c: char| c.is_ascii_alphanumeric() || c == '.') {
+            continue;
+        }
+        let end = candidate
+            .find(|c: char| c.is_whitespace() || "\"<>".contains(c))
+            .unwrap_or(candidate.len());
+        let doi = candidate[..end].trim_end_matches(['.', ',', ';', ':']);
+        // unmatched closing brackets are punctuation around the DOI
+        let doi = match doi.chars().last() {
+            Some(')') if doi.matches('(').count() < doi.matches(')').count() => {
+                &doi[..doi.len() - 1]
+            }
+            Some(']') if doi.matches('[').count() < doi.matches(']').count() => {
+                &doi[..doi.len() - 1]
+            }
+            _ => doi,
+        };
+        let Some((prefix, suffix)) = doi.split_once('/') else {
+            continue;
+        };
+        let registrant = &prefix[3..];
+        if (4..=9).contains(&registrant.len())
+            && registrant.chars().all(|c| c.is_ascii_digit() || c == '.')
+            && !suffix.is_empty()
+        {
+            return normalize_doi(doi);
+        }
+    }
+    None
+}
+
+/// First arXiv identifier in the text, e.g. "arXiv:2401.01234v2 [cs.IT] 2 Jan 2024"
+fn find_arxiv(text: &str) -> Option<ArxivId> {
+    // ASCII lowercase keeps the byte offsets, unlike e.g. 'İ' in full lowercase
+    let lower = text.to_ascii_lowercase();
+    ["arxiv:", "arxiv.org/abs/", "arxiv.org/pdf/"]
+        .iter()
+        .flat_map(|prefix| lower.match_indices(prefix).map(|(i, _)| i + prefix.len()))
+        .filter_map(|start| {
+            let candidate = text[start..].trim_start();
+            let end = candidate
+                .find(|c: char| c.is_whitespace() || ",;[]()".contains(c))
+                .unwrap_or(candidate.len());
+            ArxivId::parse(candidate[..end].trim_end_matches('.')).ok()
+        })
+        .next()
+}
+
+/// Title guessed from the text in the largest font on the first page
+fn guess_title(pdf: &PdfFile) -> Option<String> {
+    let spans: Vec<_> = pdf
+        .page_spans(1)
+        .ok()?
+        .into_iter()
+        .filter(|span| {
+            !span.rotated && span.text.chars().filter(|c| c.is_alphabetic()).count() >= 2
+        })
+        .collect();
+    let largest = spans.iter().map(|span| span.size).fold(0.0, f32::max);
+    if largest <= 0.0 {
+        return None;
+    }
+    let title = collapse_whitespace(
+        &spans
+            .iter()
+            .filter(|span| largest - span.size < 0.5)
+            .map(|span| span.text.as_str())
+            .collect::<Vec<_>>()
+            .join(" "),
+    );
+    Some(title).filter(|title| is_title(title) && title.chars().count() <= 300)
+}
+
+/// Extract the metadata of a PDF file without the network
+///
+/// The sources are, in order of precedence:
+/// 1. the XMP metadata (`dc:title`, `dc:creator`, `prism:doi`, ...);
+/// 2. the document information dictionary (`/Title` and `/Author`);
+/// 3. a DOI or an arXiv identifier in the text of the first pages;
+/// 4. the text in the largest font on the first page as the title.
+pub fn from_pdf(path: &Path) -> Result<PdfMetadata, String> {
+    let pdf = PdfFile::open(path).map_err(|e| e.to_string())?;
+    let xmp = pdf.xmp().unwrap_or_default();
+    let first = |property: &str| xmp_values(&xmp, property).into_iter().next();
+    let mut entry = PaperEntry::new();
+    let mut title_guessed = false;
+    // 1. title
+    entry.title = first("dc:title")
+        .or_else(|| pdf.info("Title").map(|title| collapse_whitespace(&title)))
+        .filter(|title| is_title(title));
+    if entry.title.is_none() {
+        entry.title = guess_title(&pdf);
+        title_guessed = entry.title.is_some();
+    }
+    // 2. authors
+    let authors = match xmp_values(&xmp, "dc:creator") {
+        creators if !creators.is_empty() => creators,
+        _ => pdf
+            .info("Author")
+            .map(|a| split_authors(&a))
+            .unwrap_or_default(),
+    };
+    let authors: Vec<Author> = authors.iter().filter_map(|a| Author::parse(a)).collect();
+    entry.authors = Some(authors).filter(|authors| !authors.is_empty());
+    // 3. DOI and arXiv identifier
+    entry.doi = first("prism:doi")
+        .and_then(|doi| normalize_doi(&doi))
+        .or_else(|| {
+            let identifiers = xmp_values(&xmp, "dc:identifier").join(" ");
+            let info = ["Subject", "Keywords"].map(|key| pdf.info(key).unwrap_or_default());
+            find_doi(&format!("{} {}", identifiers, info.join(" ")))
+        });
+    let text: String = (1..=SCANNED_PAGES.min(pdf.page_count()))
+        .map(|page| pdf.page_text(page).unwrap_or_default())
+        .collect::<Vec<_>>()
+        .join("\n");
+    if entry.doi.is_none() {
+        entry.doi = find_doi(&text);
+    }
+    let arxiv = find_arxiv(&text);
+    // 4. year and venue
+    entry.year = ["prism:coverDate", "prism:publicationDate"]
+        .iter()
+        .find_map(|property| first(property))
+        .and_then(|date| date.get(..4).and_then(|year| year.parse().ok()))
+        .or_else(|| {
+            // new-style arXiv identifiers start with the year and month, e.g. "2401"
+            let arxiv = arxiv.as_ref().filter(|arxiv| !arxiv.id.contains('/'))?;
+            arxiv.id.get(..2)?.parse::<u32>().ok().map(|yy| 2000 + yy)
+        });
+    entry.venue = first("prism:publicationName");
+    if let Some(arxiv) = arxiv {
+        entry.arxiv = Some(arxiv.id);
+        entry.arxiv_version = arxiv.version;
+    }
+    Ok(PdfMetadata {
+        entry,
+        title_guessed,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dois_in_text() {
+        let doi = |text: &str| find_doi(text);
+        assert_eq!(
+            doi("Digital Object Identifier 10.1109/TWC.2024.1234567.").as_deref(),
+            Some("10.1109/TWC.2024.1234567")
+        );
+        assert_eq!(
+            doi("(see https://doi.org/10.1145/3366423.3380296)").as_deref(),
+            Some("10.1145/3366423.3380296")
+        );
+        assert_eq!(
+            doi("doi:10.1002/(SICI)1097-4571(199806)49:8<693::AID-ASI4>3.0.CO;2-0").as_deref(),
+            Some("10.1002/(SICI)1097-4571(199806)49:8")
+        );
+        // version numbers and short registrants are not DOIs
+        assert_eq!(doi("v2.10.1/abc and 10.12/abc and 10.1109/"), None);
+    }
+
+    #[test]
+    fn arxiv_in_text() {
+        let arxiv = |text: &str| find_arxiv(text).map(|arxiv| arxiv.to_string());
+        assert_eq!(
+            arxiv("arXiv:2401.01234v2 [cs.IT] 2 Jan 2024").as_deref(),
+            Some("2401.01234v2")
+        );
+        assert_eq!(
+            arxiv("Available: https://arxiv.org/abs/2401.01234.").as_deref(),
+            Some("2401.01234")
+        );
+        assert_eq!(
+            arxiv("ARXIV: hep-th/9901001").as_deref(),
+            Some("hep-th/9901001")
+        );
+        assert_eq!(arxiv("arXiv preprint"), None);
+    }
+
+    #[test]
+    fn arxiv_after_non_ascii_case() {
+        // regression: 'İ' and the Kelvin sign change their lengths in full lowercase,
+        // which used to shift the offsets (or panic in the middle of a character)
+        assert_eq!(
+            find_arxiv("İİİ arXiv:2401.01234").map(|arxiv| arxiv.id),
+            Some("2401.01234".to_string())
+        );
+        assert_eq!(
+            find_arxiv("\u{212A}\u{212A} arXiv:2401.01234").map(|arxiv| arxiv.id),
+            Some("2401.01234".to_string())
+        );
+    }
+
+    #[test]
+    fn xmp_properties() {
+        let xmp = r#"<rdf:Description prism:doi="10.1/x" pdf:Producer='TeX'>
+            <dc:title><rdf:Alt><rdf:li xml:lang="x-default">Beam &amp;
+              Squint</rdf:li></rdf:Alt></dc:title>
+            <dc:titles>Not the title</dc:titles>
+            <dc:creator><rdf:Seq><rdf:li>A. Author</rdf:li><rdf:li>B. Author</rdf:li></rdf:Seq></dc:creator>
+            <dc:subject/>
+        </rdf:Description>"#;
+        assert_eq!(xmp_values(xmp, "prism:doi"), ["10.1/x"]);
+        assert_eq!(xmp_values(xmp, "pdf:Producer"), ["TeX"]);
+        assert_eq!(xmp_values(xmp, "dc:title"), ["Beam & Squint"]);
+        assert_eq!(xmp_values(xmp, "dc:creator"), ["A. Author", "B. Author"]);
+        assert!(xmp_values(xmp, "dc:subject").is_empty());
+    }
+
+    #[test]
+    fn titles() {
+        assert!(is_title("Beam Training for Near-Field Communications"));
+        for title in [
+            "untitled",
+            "Microsoft Word - paper.docx",
+            "main.pdf",
+            "paper",
+            "C:\\x y",
+        ] {
+            assert!(!is_title(title), "{}", title);
+        }
+    }
+
+    #[test]
+    fn author_fields() {
+        assert_eq!(
+            split_authors("A. Author and B. Author"),
+            ["A. Author", "B. Author"]
+        );
+        assert_eq!(
+            split_authors("Ann Author, Bob Author, and Cy Author"),
+            ["Ann Author", "Bob Author", "Cy Author"]
+        );
+        assert_eq!(
+            split_authors("Author, A.; Author, B."),
+            ["Author, A.", "Author, B."]
+        );
+        assert_eq!(split_authors("Author, A."), ["Author, A."]);
+    }
+
+    #[test]
+    fn tags_in_abstracts() {
+        assert_eq!(
+            strip_tags("<jats:title>Abstract</jats:title><jats:p>We study <jats:italic>beams</jats:italic>.</jats:p><jats:p>More.</jats:p>"),
+            "We study beams. More."
+        );
+    }
+}
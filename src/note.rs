@@ -0,0 +1,195 @@
+use super::author;
+use super::cite;
+use super::database::{PaperEntry, PaperID};
+
+/// Template of new notes when `note.template` is not configured
+pub const DEFAULT_TEMPLATE: &str = "# {title}
+
+- ID: {id}
+- Authors: {authors}
+- Year: {year}
+- Venue: {venue}
+- DOI: {doi}
+- Tags: {tags}
+- Created: {date}
+
+## Summary
+
+## Notes
+";
+
+/// Name of the note file of a paper, e.g. "zhao2024beam.note.md"
+///
+/// The suffix keeps it apart from an attachment in Markdown (e.g. "zhao2024beam.md").
+pub fn file_name(id: &str) -> String {
+    format!("{}.note.md", id)
+}
+
+/// Replace the references to a paper ID in the text (e.g. `@zhao2024` or `[[zhao2024]]`)
+///
+/// Only whole IDs are replaced, i.e. not followed or preceded by a letter, a digit,
+/// '-' or '_', so that "zhao2024" is not found in "zhao2024beam".
+/// Returns `None` if the text has no references.
+pub fn replace_id(text: &str, from: &str, to: &str) -> Option<String> {
+    let is_id_char = |c: char| c.is_alphanumeric() || c == '-' || c == '_';
+    let mut replaced = String::new();
+    let mut rest = text;
+    let mut found = false;
+    while let Some(start) = rest.find(from) {
+        let end = start + from.len();
+        let before = rest[..start].chars().next_back();
+        let after = rest[end..].chars().next();
+        replaced.push_str(&rest[..start]);
+        if before.is_some_and(is_id_char) || after.is_some_and(is_id_char) {
+            replaced.push_str(from);
+        } else {
+            replaced.push_str(to);
+            found = true;
+        }
+        rest = &rest[end..];
+    }
+    replaced.push_str(rest);
+    Some(replaced).filter(|_| found)
+}
+
+/// Render a note template with the metadata of a paper
+///
+/// The placeholders are `{id}`, `{title}`, `{authors}`, `{year}`, `{month}`, `{venue}`,
+/// `{doi}`, `{arxiv}`, `{url}`, `{keywords}`, `{tags}`, `{abstract}`, `{citation}`
+/// and `{date}` (today), where the fields that are not set are left empty.
+/// Other text (including unknown placeholders) is kept as it is.
+pub fn render(template: &str, id: &PaperID, entry: &PaperEntry) -> String {
+    let text = |value: &Option<String>| value.clone().unwrap_or_default();
+    let number = |value: Option<u32>| value.map(|n| n.to_string()).unwrap_or_default();
+    let list = |values: &Option<Vec<String>>| values.as_deref().unwrap_or_default().join(", ");
+    let values = [
+        ("id", id.clone()),
+        ("title", text(&entry.title)),
+        (
+            "authors",
+            entry
+                .authors
+                .as_deref()
+                .map(|authors| author::join(authors, ", "))
+                .unwrap_or_default(),
+        ),
+        ("year", number(entry.year)),
+        ("month", number(entry.month)),
+        ("venue", text(&entry.venue)),
+        ("doi", text(&entry.doi)),
+        ("arxiv", text(&entry.arxiv)),
+        ("url", text(&entry.url)),
+        ("keywords", list(&entry.keywords)),
+        ("tags", list(&entry.tags)),
+        ("abstract", text(&entry.r#abstract)),
+        ("citation", cite::plain_citation(entry)),
+        ("date", chrono::Local::now().format("%Y-%m-%d").to_string()),
+    ];
+    // the template is scanned once, so that the values are never replaced again
+    let mut note = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        note.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let value = rest.find('}').and_then(|end| {
+            let (_, value) = values.iter().find(|(name, _)| *name == &rest[1..end])?;
+            Some((end, value))
+        });
+        match value {
+            Some((end, value)) => {
+                note.push_str(value);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                note.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+    note.push_str(rest);
+    note
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::author::Author;
+
+    fn paper() -> PaperEntry {
+        PaperEntry {
+            title: Some("Beam Training".to_string()),
+            authors: Some(vec![
+                Author::parse("Yu Zhao").unwrap(),
+                Author::literal("IEEE"),
+            ]),
+            year: Some(2024),
+            tags: Some(vec!["beam".to_string(), "near-field".to_string()]),
+            ..PaperEntry::new()
+        }
+    }
+
+    #[test]
+    fn note_file_name() {
+        assert_eq!(file_name("zhao2024beam"), "zhao2024beam.note.md");
+    }
+
+    #[test]
+    fn replace_references() {
+        assert_eq!(
+            replace_id(
+                "See @zhao2024, [[zhao2024]] and zhao2024.",
+                "zhao2024",
+                "li2023"
+            ),
+            Some("See @li2023, [[li2023]] and li2023.".to_string())
+        );
+        // longer IDs are kept
+        assert_eq!(
+            replace_id(
+                "zhao2024beam zhao2024-b x_zhao2024 zhao2024",
+                "zhao2024",
+                "li"
+            ),
+            Some("zhao2024beam zhao2024-b x_zhao2024 li".to_string())
+        );
+        assert_eq!(replace_id("zhao2024beam", "zhao2024", "li"), None);
+        assert_eq!(replace_id("", "zhao2024", "li"), None);
+    }
+
+    #[test]
+    fn render_fields() {
+        let id = "zhao2024".to_string();
+        let note = render(
+            "# {title} ({id})\n{authors}, {year}/{month}\nTags: {tags}\nDOI: {doi}\n",
+            &id,
+            &paper(),
+        );
+        assert_eq!(
+            note,
+            "# Beam Training (zhao2024)\nYu Zhao, IEEE, 2024/\nTags: beam, near-field\nDOI: \n"
+        );
+        let today = chrono::Local::now().format("%Y-%m-%d").to_string();
+        assert_eq!(render("{date}", &id, &paper()), today);
+        assert_eq!(
+            render("{citation}", &id, &paper()),
+            cite::plain_citation(&paper())
+        );
+    }
+
+    #[test]
+    fn render_other_text() {
+        let id = "zhao2024".to_string();
+        // unknown placeholders and unmatched braces are kept
+        assert_eq!(
+            render("{unknown} {id {} x}{id}", &id, &paper()),
+            "{unknown} {id {} x}zhao2024"
+        );
+        // a value that looks like a placeholder is not replaced again
+        let entry = PaperEntry {
+            title: Some("{id}".to_string()),
+            ..PaperEntry::new()
+        };
+        assert_eq!(render("{title}", &id, &entry), "{id}");
+        assert!(render(DEFAULT_TEMPLATE, &id, &paper()).starts_with("# Beam Training\n"));
+    }
+}
@@ -1,4 +1,8 @@
-use clap::{Args, Parser, Subcommand};
+use super::author::Author;
+use super::database::{parse_month, EntryType, Priority, ReadingStatus};
+use super::dupes;
+use super::list::YearRange;
+use clap::{Args, Parser, Subcommand, ValueEnum};
 use directories::ProjectDirs;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -37,28 +41,148 @@ pub struct CommandActivateArgs {
 }
 
 #[derive(Args, Clone, Debug)]
+pub struct CommandDupesArgs {
+    /// Only find the duplicates of these papers
+    #[arg(index = 1)]
+    pub ids: Vec<String>,
+    /// Minimum similarity (0 to 1) of the titles of duplicates
+    #[arg(short = 't', long, default_value_t = dupes::DEFAULT_THRESHOLD)]
+    pub threshold: f64,
+}
+
+#[derive(Args, Clone, Debug, Default)]
 pub struct CommandAddArgs {
-    /// The unique id of the paper to edit
+    /// The unique id of the paper to add (generated from the metadata if not given)
     #[arg(index = 1)]
-    pub id: String,
+    pub id: Option<String>,
     /// The file of the paper to add
     #[arg(short = 'f', long)]
     pub file: Option<String>,
     /// Title of the paper
     #[arg(short = 't', long)]
     pub title: Option<String>,
-    /// Author of the paper (one by one with multiple flags)
+    /// Author of the paper (one by one with multiple flags), e.g. "Given Family" or
+    /// "Family, Given", optionally with the ORCID iD
     #[arg(short = 'a', long = "author")]
-    pub authors: Option<Vec<String>>,
+    pub authors: Option<Vec<Author>>,
     /// Year of the paper
     #[arg(short = 'y', long)]
     pub year: Option<u32>,
-    /// DOI of the paper
-    #[arg(short = 'd', long)]
+    /// Venue (journal, conference, ...) of the paper
+    #[arg(long, visible_aliases = ["journal", "booktitle"])]
+    pub venue: Option<String>,
+    /// DOI of the paper (its metadata is fetched unless '--no-fetch')
+    #[arg(long)]
     pub doi: Option<String>,
+    /// arXiv identifier of the paper, e.g. 2401.01234v2 (its metadata and PDF are fetched)
+    #[arg(long)]
+    pub arxiv: Option<String>,
+    /// Do not fetch the metadata of the DOI or arXiv identifier
+    #[arg(long)]
+    pub no_fetch: bool,
+    /// Do not download the PDF of the arXiv paper
+    #[arg(long)]
+    pub no_pdf: bool,
+    /// Do not extract the metadata from the PDF file
+    #[arg(long)]
+    pub no_extract: bool,
     /// Force to add the paper even if the id exists
     #[arg(long)]
     pub force: bool,
+    /// Tag of the paper (one by one with multiple flags), e.g. method/beamforming
+    #[arg(long = "tag")]
+    pub tags: Option<Vec<String>>,
+    /// Reading status of the paper (default: inbox)
+    #[arg(long)]
+    pub status: Option<ReadingStatus>,
+    /// Reading priority of the paper
+    #[arg(long)]
+    pub priority: Option<Priority>,
+    #[command(flatten)]
+    pub bibliography: BibliographyArgs,
+}
+
+/// Bibliographic fields of 'add' and 'edit' besides the basic ones
+#[derive(Args, Clone, Debug, Default)]
+pub struct BibliographyArgs {
+    /// Type of the paper
+    #[arg(long = "type")]
+    pub entry_type: Option<EntryType>,
+    /// Month of the paper, as a number or a name (e.g. 3 or mar)
+    #[arg(long, value_parser = month_parser)]
+    pub month: Option<u32>,
+    /// Volume of the journal (or the book series)
+    #[arg(long)]
+    pub volume: Option<String>,
+    /// Issue (or number) within the volume
+    #[arg(long)]
+    pub issue: Option<String>,
+    /// Pages (e.g. 123-130) or article number of the paper
+    #[arg(long)]
+    pub pages: Option<String>,
+    /// Publisher of the paper
+    #[arg(long)]
+    pub publisher: Option<String>,
+    /// Abstract of the paper
+    #[arg(long)]
+    pub r#abstract: Option<String>,
+    /// Keyword of the paper (one by one with multiple flags)
+    #[arg(long = "keyword")]
+    pub keywords: Option<Vec<String>>,
+    /// URL of the paper
+    #[arg(long)]
+    pub url: Option<String>,
+    /// ISBN of the book
+    #[arg(long)]
+    pub isbn: Option<String>,
+    /// ISSN of the journal (or the book series)
+    #[arg(long)]
+    pub issn: Option<String>,
+    /// Language of the paper, e.g. en
+    #[arg(long)]
+    pub language: Option<String>,
+}
+
+fn month_parser(month: &str) -> Result<u32, String> {
+    parse_month(month).ok_or_else(|| "expected a number from 1 to 12 or a month name".to_string())
+}
+
+#[derive(Args, Clone, Debug)]
+pub struct CommandAnnotationsArgs {
+    /// The unique id of the paper
+    #[arg(index = 1)]
+    pub id: String,
+    /// Select the paper by fuzzy matching the id or title
+    #[arg(long)]
+    pub fuzzy: bool,
+    /// Append the new annotations to the note of the paper (creating it if needed)
+    #[arg(short, long)]
+    pub append: bool,
+}
+
+#[derive(Args, Clone, Debug)]
+pub struct CommandCiteArgs {
+    /// The IDs of the papers to cite
+    #[arg(index = 1, num_args = 1.., required_unless_present = "list_styles")]
+    pub ids: Vec<String>,
+    /// The CSL style: a bundled style (e.g. ieee, apa, chicago-author-date) or a .csl file
+    #[arg(short = 's', long)]
+    pub style: Option<String>,
+    /// The output format
+    #[arg(short = 'F', long)]
+    pub format: Option<CiteFormat>,
+    /// List the bundled CSL styles
+    #[arg(long)]
+    pub list_styles: bool,
+}
+
+/// Output format of formatted citations
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CiteFormat {
+    Plain,
+    Markdown,
+    Html,
 }
 
 #[derive(Args, Clone, Debug)]
@@ -75,6 +199,30 @@ pub struct CommandConfigArgs {
     /// The link of the owner
     #[arg(short = 'l', long = "owner.link", name = "LINK", num_args = 0..=1, default_missing_value = Self::_JUST_TO_PRINT_THIS_FIELD)]
     pub owner_link: Option<String>,
+    /// The viewer command of a file type, e.g. 'pdf=zathura --page={page} {file}' (empty to unset)
+    #[arg(long = "viewer", name = "EXT=COMMAND", num_args = 0..=1, default_missing_value = Self::_JUST_TO_PRINT_THIS_FIELD)]
+    pub viewer: Option<String>,
+    /// The default CSL style of the 'cite' command
+    #[arg(long = "cite.style", name = "STYLE", num_args = 0..=1, default_missing_value = Self::_JUST_TO_PRINT_THIS_FIELD)]
+    pub cite_style: Option<String>,
+    /// The default output format of the 'cite' command (plain, markdown or html)
+    #[arg(long = "cite.format", name = "FORMAT", num_args = 0..=1, default_missing_value = Self::_JUST_TO_PRINT_THIS_FIELD)]
+    pub cite_format: Option<String>,
+    /// The pattern of generated IDs, e.g. '{author}{year}{title}' (empty to reset)
+    #[arg(long = "id.pattern", name = "PATTERN", num_args = 0..=1, default_missing_value = Self::_JUST_TO_PRINT_THIS_FIELD)]
+    pub id_pattern: Option<String>,
+    /// The base URL of the DOI metadata provider, e.g. 'http://localhost:8000' (empty for doi.org)
+    #[arg(long = "provider.doi", name = "URL", num_args = 0..=1, default_missing_value = Self::_JUST_TO_PRINT_THIS_FIELD)]
+    pub provider_doi: Option<String>,
+    /// The base URL of the arXiv API, e.g. 'http://localhost:8000/api' (empty for export.arxiv.org)
+    #[arg(long = "provider.arxiv", name = "ARXIV_URL", num_args = 0..=1, default_missing_value = Self::_JUST_TO_PRINT_THIS_FIELD)]
+    pub provider_arxiv: Option<String>,
+    /// The Markdown template file of new notes (empty for the default template)
+    #[arg(long = "note.template", name = "TEMPLATE", num_args = 0..=1, default_missing_value = Self::_JUST_TO_PRINT_THIS_FIELD)]
+    pub note_template: Option<String>,
+    /// What 'watch' does with the imported files (keep or move)
+    #[arg(long = "watch.original", name = "ORIGINAL", num_args = 0..=1, default_missing_value = Self::_JUST_TO_PRINT_THIS_FIELD)]
+    pub watch_original: Option<String>,
     /// Show the file path of the config file
     #[arg(long = "show-config-path", default_value = "false", hide = true)]
     pub show_config_path: bool,
@@ -89,21 +237,82 @@ pub struct CommandEditArgs {
     /// The unique id of the paper to edit
     #[arg(index = 1)]
     pub id: String,
+    /// Select the paper by fuzzy matching the id or title (to be confirmed)
+    #[arg(long)]
+    pub fuzzy: bool,
     /// The file of the paper to add
     #[arg(short = 'f', long)]
     pub file: Option<String>,
     /// Title of the paper
     #[arg(short = 't', long)]
     pub title: Option<String>,
-    /// Author of the paper (one by one with multiple flags)
+    /// Author of the paper (one by one with multiple flags), e.g. "Given Family" or
+    /// "Family, Given", optionally with the ORCID iD
     #[arg(short = 'a', long = "author")]
-    pub authors: Option<Vec<String>>,
+    pub authors: Option<Vec<Author>>,
     /// DOI of the paper
-    #[arg(short = 'd', long)]
+    #[arg(long)]
     pub doi: Option<String>,
     /// Year of the paper
     #[arg(short = 'y', long)]
     pub year: Option<u32>,
+    /// Venue (journal, conference, ...) of the paper
+    #[arg(long, visible_aliases = ["journal", "booktitle"])]
+    pub venue: Option<String>,
+    #[command(flatten)]
+    pub bibliography: BibliographyArgs,
+}
+
+#[derive(Args, Clone, Debug)]
+pub struct CommandExportArgs {
+    /// The format to export
+    #[arg(index = 1)]
+    pub format: ExportFormat,
+    /// Only export papers matching the query (all papers if not given)
+    #[arg(index = 2, num_args = 0..)]
+    pub query: Vec<String>,
+    /// The file to write (stdout if not given)
+    #[arg(short = 'o', long)]
+    pub output: Option<String>,
+}
+
+/// Format of exported papers
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExportFormat {
+    Bibtex,
+    /// CSL-JSON (e.g. for Pandoc and Zotero)
+    CslJson,
+    /// RIS
+    Ris,
+}
+
+#[derive(Args, Clone, Debug)]
+pub struct CommandImportArgs {
+    /// The format to import
+    #[arg(index = 1)]
+    pub format: ImportFormat,
+    /// The file to import (a directory for 'dir')
+    #[arg(index = 2)]
+    pub path: String,
+    /// Import the PDF files in the subdirectories too (only for 'dir')
+    #[arg(short = 'r', long)]
+    pub recursive: bool,
+    /// Preview the papers to import without adding them (only for 'dir')
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+/// Format of imported papers
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImportFormat {
+    /// BibTeX or BibLaTeX
+    Bib,
+    /// CSL-JSON (e.g. from Pandoc and Zotero)
+    CslJson,
+    /// RIS (with IDs generated from the metadata)
+    Ris,
+    /// A directory of PDF files (with IDs generated from the extracted metadata)
+    Dir,
 }
 
 #[derive(Args, Clone, Debug)]
@@ -117,24 +326,334 @@ pub struct CommandInitArgs {
 }
 
 #[derive(Args, Clone, Debug)]
-pub struct CommandListArgs {}
+pub struct CommandListArgs {
+    /// Only list papers matching the query (e.g. 'author:zhao year:2023..2024')
+    #[arg(index = 1, num_args = 0..)]
+    pub query: Vec<String>,
+    /// Columns to show (comma separated)
+    #[arg(
+        short = 'c',
+        long,
+        value_delimiter = ',',
+        default_value = "id,title,authors,year"
+    )]
+    pub columns: Vec<ListColumn>,
+    /// Sort papers by the field
+    #[arg(short = 's', long, default_value = "id")]
+    pub sort: ListColumn,
+    /// Reverse the sorting order
+    #[arg(short = 'r', long)]
+    pub reverse: bool,
+    /// Only list papers within the year range (e.g. 2023, 2023..2024, 2023.., ..2024)
+    #[arg(short = 'y', long)]
+    pub year: Option<YearRange>,
+    /// Only list papers with an author containing the name
+    #[arg(short = 'a', long)]
+    pub author: Option<String>,
+    /// Only list papers in the category
+    #[arg(long)]
+    pub category: Option<String>,
+    /// Only list papers with the tag or a tag below it (all of them if repeated)
+    #[arg(short = 't', long = "tag")]
+    pub tags: Vec<String>,
+    /// Only list papers with any of the reading statuses (comma separated)
+    #[arg(long, value_delimiter = ',')]
+    pub status: Vec<ReadingStatus>,
+    /// Only list papers with the reading priority
+    #[arg(long)]
+    pub priority: Option<Priority>,
+    /// Only list papers rated at least this (1 to 5)
+    #[arg(long, value_parser = clap::value_parser!(u8).range(1..=5))]
+    pub min_rating: Option<u8>,
+    /// Number of papers per page (all papers if not set)
+    #[arg(short = 'n', long)]
+    pub limit: Option<usize>,
+    /// Page to show (starting from 1)
+    #[arg(short = 'p', long, default_value = "1")]
+    pub page: usize,
+}
+
+/// Column (or sorting key) of the paper list
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ListColumn {
+    Id,
+    Title,
+    Authors,
+    Year,
+    Doi,
+    File,
+    Category,
+    /// Reading status (in the order of the workflow when sorting)
+    Status,
+    /// Reading priority (the highest first when sorting)
+    Priority,
+    /// Rating (the highest first when sorting)
+    Rating,
+}
+
+#[derive(Args, Clone, Debug)]
+pub struct CommandMergeArgs {
+    /// The paper to keep
+    #[arg(index = 1)]
+    pub keep: String,
+    /// The paper to merge and drop (its ID becomes an alias of the kept paper)
+    #[arg(index = 2)]
+    pub drop: String,
+    /// Which value to take when both papers have different values of a field
+    #[arg(short = 'r', long, default_value = "keep")]
+    pub rule: MergeRule,
+    /// Choose the value of each differing field interactively
+    #[arg(short = 'i', long)]
+    pub interactive: bool,
+    /// Show the merged paper without changing the database
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+/// Rule of choosing the values of the merged paper
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MergeRule {
+    /// The values of the kept paper
+    Keep,
+    /// The values of the dropped paper
+    Drop,
+    /// The values of the paper modified more recently
+    Newer,
+    /// The longer values (e.g. the full author list rather than an abbreviated one)
+    Longer,
+}
+
+#[derive(Args, Clone, Debug)]
+pub struct CommandNoteArgs {
+    /// The unique id of the paper
+    #[arg(index = 1)]
+    pub id: String,
+    /// Select the paper by fuzzy matching the id or title
+    #[arg(long)]
+    pub fuzzy: bool,
+    /// Print the path of the note (creating it if needed) instead of editing it
+    #[arg(long)]
+    pub print_path: bool,
+}
 
 #[derive(Args, Clone, Debug)]
 pub struct CommandOpenArgs {
+    /// The unique id of the paper to open
     #[arg(index = 1)]
     pub id: String,
+    /// Select the paper by fuzzy matching the id or title
+    #[arg(long)]
+    pub fuzzy: bool,
+    /// Page to open (if supported by the viewer)
+    #[arg(short = 'p', long)]
+    pub page: Option<u32>,
+    /// Print the path of the attachment instead of opening it
+    #[arg(long)]
+    pub print_path: bool,
+    /// Do not wait for the viewer to exit
+    #[arg(short = 'b', long)]
+    pub background: bool,
 }
 
 #[derive(Args, Clone, Debug)]
-pub struct CommandSearchArgs {}
+pub struct CommandQueueArgs {
+    /// Maximum number of papers to show (all papers if not set)
+    #[arg(short = 'n', long)]
+    pub limit: Option<usize>,
+    /// Also show the papers in the inbox (after the others)
+    #[arg(long)]
+    pub inbox: bool,
+    /// Columns to show (comma separated)
+    #[arg(
+        short = 'c',
+        long,
+        value_delimiter = ',',
+        default_value = "id,status,priority,title,year"
+    )]
+    pub columns: Vec<ListColumn>,
+}
 
 #[derive(Args, Clone, Debug)]
-pub struct CommandShowArgs {}
+pub struct CommandRateArgs {
+    /// The unique id of the paper to rate
+    #[arg(index = 1)]
+    pub id: String,
+    /// Rating from 1 to 5
+    #[arg(index = 2, value_parser = clap::value_parser!(u8).range(1..=5), required_unless_present = "clear")]
+    pub rating: Option<u8>,
+    /// Remove the rating
+    #[arg(long, conflicts_with = "rating")]
+    pub clear: bool,
+    /// Select the paper by fuzzy matching the id or title (to be confirmed)
+    #[arg(long)]
+    pub fuzzy: bool,
+}
+
+#[derive(Args, Clone, Debug)]
+pub struct CommandSearchArgs {
+    /// The search query, e.g. 'author:zhao year:2023..2024 title:"beam"'
+    ///
+    /// Terms are combined with AND (implicit), OR and NOT,
+    /// and can be grouped with parentheses.
+    /// A leading '-' also negates a term (put the query after '--' in this case).
+    /// Use 'field:value' to search in a field
+    /// (id, type, title, author, year, month, venue, volume, issue, pages, publisher,
+    /// abstract, keyword, url, isbn, issn, language, doi, arxiv, file, category, tag,
+    /// status, priority or rating),
+    /// and quotes for phrases.
+    /// Years can be ranges like 'year:2023..2024'.
+    ///
+    /// With '--fulltext', the query is plain text searched in the attachments and notes.
+    #[arg(index = 1, num_args = 1.., required = true)]
+    pub query: Vec<String>,
+    /// Search the full text of the attachments (PDF files) and the notes
+    #[arg(short = 'f', long)]
+    pub fulltext: bool,
+    /// Maximum number of full-text search results
+    #[arg(short = 'n', long, default_value = "10")]
+    pub limit: usize,
+    /// Columns to show (comma separated)
+    #[arg(
+        short = 'c',
+        long,
+        value_delimiter = ',',
+        default_value = "id,title,authors,year"
+    )]
+    pub columns: Vec<ListColumn>,
+}
+
+#[derive(Args, Clone, Debug)]
+pub struct CommandShowArgs {
+    /// The unique id of the paper to show
+    #[arg(index = 1)]
+    pub id: String,
+    /// Select the paper by fuzzy matching the id or title
+    #[arg(long)]
+    pub fuzzy: bool,
+    /// Output format
+    #[arg(short = 'F', long, default_value = "text")]
+    pub format: ShowFormat,
+}
+
+#[derive(Args, Clone, Debug)]
+pub struct CommandStatusArgs {
+    /// The unique id of the paper
+    #[arg(index = 1)]
+    pub id: String,
+    /// The new reading status (the current status and priority are shown if not given)
+    #[arg(index = 2)]
+    pub status: Option<ReadingStatus>,
+    /// The new reading priority
+    #[arg(short = 'p', long)]
+    pub priority: Option<Priority>,
+    /// Remove the reading status (moving the paper back to the inbox) and priority
+    #[arg(long, conflicts_with_all = ["status", "priority"])]
+    pub clear: bool,
+    /// Select the paper by fuzzy matching the id or title (to be confirmed)
+    #[arg(long)]
+    pub fuzzy: bool,
+}
+
+#[derive(Args, Clone, Debug)]
+pub struct CommandTagArgs {
+    #[command(subcommand)]
+    pub action: TagAction,
+}
+
+/// Action of the 'tag' command
+#[derive(Subcommand, Clone, Debug)]
+pub enum TagAction {
+    /// Add tags to a paper
+    Add {
+        /// The unique id of the paper
+        #[arg(index = 1)]
+        id: String,
+        /// The tags to add, e.g. method/beamforming
+        #[arg(index = 2, num_args = 1.., required = true)]
+        tags: Vec<String>,
+        /// Select the paper by fuzzy matching the id or title (to be confirmed)
+        #[arg(long)]
+        fuzzy: bool,
+    },
+    /// Remove tags (and the tags below them) from a paper
+    Remove {
+        /// The unique id of the paper
+        #[arg(index = 1)]
+        id: String,
+        /// The tags to remove
+        #[arg(index = 2, num_args = 1.., required = true)]
+        tags: Vec<String>,
+        /// Select the paper by fuzzy matching the id or title (to be confirmed)
+        #[arg(long)]
+        fuzzy: bool,
+    },
+    /// Rename a tag (and the tags below it) in all papers
+    Rename {
+        /// The tag to rename
+        #[arg(index = 1)]
+        old: String,
+        /// The new name, e.g. method/precoding
+        #[arg(index = 2)]
+        new: String,
+    },
+    /// List all tags as a tree with the numbers of papers
+    List,
+}
+
+#[derive(Args, Clone, Debug)]
+pub struct CommandWatchArgs {
+    /// The directory to watch, e.g. ~/Downloads/papers
+    #[arg(index = 1)]
+    pub dir: String,
+    /// What to do with the original files after they are imported (default: 'watch.original')
+    #[arg(long)]
+    pub original: Option<WatchOriginal>,
+    /// Also import the PDF files already in the directory
+    #[arg(long)]
+    pub existing: bool,
+    /// Import the queued files and exit, without watching
+    #[arg(long)]
+    pub once: bool,
+}
+
+/// What 'watch' does with the original file after it is imported
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WatchOriginal {
+    /// Leave the file in place
+    #[default]
+    Keep,
+    /// Move the file into the database (the original is removed)
+    Move,
+}
+
+/// Output format of the paper details
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShowFormat {
+    Text,
+    Yaml,
+    Json,
+}
+
+#[derive(Args, Clone, Debug)]
+pub struct CommandRefreshArgs {
+    /// The IDs of the papers to refresh (all papers with an arXiv identifier if not given)
+    #[arg(index = 1, num_args = 0..)]
+    pub ids: Vec<String>,
+    /// Apply the updates (new DOI and venue, and the PDF of the latest version,
+    /// keeping the previous attachment as `<id>-alt`)
+    #[arg(short = 'u', long)]
+    pub update: bool,
+}
 
 #[derive(Args, Clone, Debug)]
 pub struct CommandRemoveArgs {
+    /// The unique id of the paper to remove
     #[arg(index = 1)]
     pub id: String,
+    /// Select the paper by fuzzy matching the id or title (to be confirmed)
+    #[arg(long)]
+    pub fuzzy: bool,
 }
 
 #[derive(Subcommand, Debug, Clone)]
@@ -143,12 +662,32 @@ pub enum Commands {
     Activate(CommandActivateArgs),
     /// Add a new paper to the database
     Add(CommandAddArgs),
+    /// Extract the highlights and notes in the PDF of a paper as Markdown
+    Annotations(CommandAnnotationsArgs),
+    /// Format citations of papers in a CSL style
+    Cite(CommandCiteArgs),
     /// Configure TermiPaper
     Config(CommandConfigArgs),
+    /// Find duplicate papers in the database
+    Dupes(CommandDupesArgs),
     /// Edit a paper in the database
     Edit(CommandEditArgs),
+    /// Export papers (e.g. as BibTeX)
+    Export(CommandExportArgs),
+    /// Import papers (e.g. from BibTeX)
+    Import(CommandImportArgs),
     /// List papers in the database
     List(CommandListArgs),
+    /// Merge a duplicate paper into another one
+    Merge(CommandMergeArgs),
+    /// Edit the Markdown note of a paper in $EDITOR
+    Note(CommandNoteArgs),
+    /// Show the reading queue (papers being read and to read)
+    Queue(CommandQueueArgs),
+    /// Rate a paper from 1 to 5
+    Rate(CommandRateArgs),
+    /// Check arXiv papers for new versions and journal DOIs
+    Refresh(CommandRefreshArgs),
     /// Remove a paper from the database
     Remove(CommandRemoveArgs),
     /// Open a paper
@@ -161,6 +700,12 @@ pub enum Commands {
     Search(CommandSearchArgs),
     /// Show a paper's details
     Show(CommandShowArgs),
+    /// Show or set the reading status and priority of a paper
+    Status(CommandStatusArgs),
+    /// Manage the tags of papers
+    Tag(CommandTagArgs),
+    /// Watch a directory and import the new PDF files
+    Watch(CommandWatchArgs),
 }
 
 pub trait PaperDir {
@@ -235,8 +780,84 @@ impl ConfigOwner {
     }
 }
 
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConfigCite {
+    /// The default CSL style (a bundled style name or a .csl file)
+    pub style: Option<String>,
+    /// The default output format
+    pub format: Option<CiteFormat>,
+}
+
+impl ConfigCite {
+    pub fn new() -> Self {
+        Self {
+            style: None,
+            format: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConfigId {
+    /// The pattern of generated IDs (see `IdPattern`)
+    pub pattern: Option<String>,
+}
+
+impl ConfigId {
+    pub fn new() -> Self {
+        Self { pattern: None }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConfigProviders {
+    /// The base URL of the DOI metadata provider (doi.org if not set)
+    pub doi: Option<String>,
+    /// The base URL of the arXiv API (export.arxiv.org if not set)
+    pub arxiv: Option<String>,
+}
+
+impl ConfigProviders {
+    pub fn new() -> Self {
+        Self {
+            doi: None,
+            arxiv: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConfigNote {
+    /// The Markdown template file of new notes (see `note::render`)
+    pub template: Option<String>,
+}
+
+impl ConfigNote {
+    pub fn new() -> Self {
+        Self { template: None }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConfigWatch {
+    /// What to do with the original files after they are imported
+    pub original: Option<WatchOriginal>,
+}
+
+impl ConfigWatch {
+    pub fn new() -> Self {
+        Self { original: None }
+    }
+}
+
 pub type ConfigDatabases = HashMap<String, ConfigDatabase>;
 
+/// Viewer commands by file extension (lowercase, without the dot)
+///
+/// In a command, `{file}` is replaced by the file path (appended if absent)
+/// and `{page}` by the page number (the argument is dropped if no page is given).
+pub type ConfigViewers = HashMap<String, String>;
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Config {
     /// The databases of papers
@@ -245,6 +866,18 @@ pub struct Config {
     pub owner: Option<ConfigOwner>,
     /// The activated paper directory
     pub activated: Option<String>,
+    /// Viewer commands for opening the attachments
+    pub viewers: Option<ConfigViewers>,
+    /// Defaults of the 'cite' command
+    pub cite: Option<ConfigCite>,
+    /// Generation of paper IDs
+    pub id: Option<ConfigId>,
+    /// Metadata providers
+    pub providers: Option<ConfigProviders>,
+    /// Defaults of the 'watch' command
+    pub watch: Option<ConfigWatch>,
+    /// Notes of papers
+    pub note: Option<ConfigNote>,
 }
 
 impl PaperDir for Config {}
@@ -264,6 +897,12 @@ impl Config {
             databases: None,
             owner: None,
             activated: None,
+            viewers: None,
+            cite: None,
+            id: None,
+            providers: None,
+            watch: None,
+            note: None,
         }
     }
 
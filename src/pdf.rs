@@ -0,0 +1,507 @@
+use lopdf::content::Content;
+use lopdf::{Dictionary, Document, Encoding, Object, ObjectId};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::path::Path;
+
+/// Text shown on a page with its font size
+#[derive(Debug, Clone)]
+pub struct TextSpan {
+    /// Text of the span
+    pub text: String,
+    /// Font size in points, scaled by the text matrix
+    pub size: f32,
+    /// Whether the text is rotated (e.g. the arXiv stamp in the margin)
+    pub rotated: bool,
+}
+
+/// Subtypes of the annotations that mark up the text (their quotes are extracted)
+const MARKUP_SUBTYPES: [&str; 4] = ["Highlight", "Underline", "StrikeOut", "Squiggly"];
+
+/// Subtypes of the annotations that are comments by themselves
+const NOTE_SUBTYPES: [&str; 2] = ["Text", "FreeText"];
+
+/// Annotation on a page, e.g. a highlight or a sticky note
+#[derive(Debug, Clone)]
+pub struct Annotation {
+    /// Page of the annotation (starting from 1)
+    pub page: u32,
+    /// Subtype of the annotation, e.g. "Highlight" or "Text"
+    pub subtype: String,
+    /// Rectangle of the annotation as (x1, y1, x2, y2)
+    pub rect: [f32; 4],
+    /// Text under a markup annotation (e.g. the highlighted text)
+    pub quote: Option<String>,
+    /// Comment of the annotation
+    pub contents: Option<String>,
+    /// Unique name of the annotation on the page (`/NM`), if set by the PDF reader
+    pub name: Option<String>,
+}
+
+/// Glyph shown on a page, positioned by the origin of its baseline
+#[derive(Debug, Clone, Copy)]
+struct Glyph {
+    ch: char,
+    x: f32,
+    y: f32,
+    width: f32,
+    size: f32,
+}
+
+/// Transformation matrix `[a b c d e f]` as in PDF
+type Matrix = [f32; 6];
+
+const IDENTITY: Matrix = [1.0, 0.0, 0.0, 1.0, 0.0, 0.0];
+
+/// Matrix of applying `a` and then `b`
+fn multiply(a: &Matrix, b: &Matrix) -> Matrix {
+    [
+        a[0] * b[0] + a[1] * b[2],
+        a[0] * b[1] + a[1] * b[3],
+        a[2] * b[0] + a[3] * b[2],
+        a[2] * b[1] + a[3] * b[3],
+        a[4] * b[0] + a[5] * b[2] + b[4],
+        a[4] * b[1] + a[5] * b[3] + b[5],
+    ]
+}
+
+fn translation(x: f32, y: f32) -> Matrix {
+    [1.0, 0.0, 0.0, 1.0, x, y]
+}
+
+/// Font of a page with the widths of its glyphs
+struct PageFont<'a> {
+    encoding: Encoding<'a>,
+    first_char: i64,
+    /// Widths in thousandths of the font size (`/Widths`)
+    widths: Vec<f32>,
+}
+
+impl PageFont<'_> {
+    /// Average width of a glyph, for fonts without widths (e.g. the standard 14 fonts)
+    const DEFAULT_WIDTH: f32 = 0.5;
+
+    /// Width of the glyph of a one-byte code, in units of the font size
+    fn width(&self, code: u8) -> f32 {
+        usize::try_from(code as i64 - self.first_char)
+            .ok()
+            .and_then(|i| self.widths.get(i))
+            .map_or(Self::DEFAULT_WIDTH, |width| width / 1000.0)
+    }
+}
+
+/// PDF file of a paper
+pub struct PdfFile {
+    doc: Document,
+}
+
+impl PdfFile {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        let doc = Document::load(path)?;
+        Ok(Self { doc })
+    }
+
+    /// Number of pages
+    pub fn page_count(&self) -> u32 {
+        self.doc.get_pages().len() as u32
+    }
+
+    /// Extract the plain text of a page (starting from 1)
+    pub fn page_text(&self, page: u32) -> Result<String, Box<dyn Error>> {
+        Ok(self.doc.extract_text(&[page])?)
+    }
+
+    /// Extract the plain text of all pages
+    ///
+    /// Pages that fail to be extracted are returned as empty strings,
+    /// so the page numbers always match the positions in the list.
+    pub fn pages_text(&self) -> Vec<String> {
+        (1..=self.page_count())
+            .map(|page| self.page_text(page).unwrap_or_default())
+            .collect()
+    }
+
+    /// Text field of the document information dictionary, e.g. "Title" or "Author"
+    pub fn info(&self, key: &str) -> Option<String> {
+        let info = self.doc.trailer.get_deref(b"Info", &self.doc).ok()?;
+        let value = info
+            .as_dict()
+            .ok()?
+            .get_deref(key.as_bytes(), &self.doc)
+            .ok()?;
+        lopdf::decode_text_string(value)
+            .ok()
+            .map(|text| text.trim().to_string())
+            .filter(|text| !text.is_empty())
+    }
+
+    /// XMP metadata of the document (an XML packet)
+    pub fn xmp(&self) -> Option<String> {
+        let metadata = self
+            .doc
+            .catalog()
+            .ok()?
+            .get_deref(b"Metadata", &self.doc)
+            .ok()?;
+        let stream = metadata.as_stream().ok()?;
+        let content = stream
+            .get_plain_content()
+            .unwrap_or_else(|_| stream.content.clone());
+        Some(String::from_utf8_lossy(&content).into_owned())
+    }
+
+    /// Highlights, underlines and notes of all pages, in the reading order
+    ///
+    /// The quote of a markup annotation is the text whose glyphs are within its quadrilaterals,
+    /// where the glyph widths are estimated if the font has no widths.
+    pub fn annotations(&self) -> Result<Vec<Annotation>, Box<dyn Error>> {
+        let mut annotations = vec![];
+        for (page, page_id) in self.doc.get_pages() {
+            let mut glyphs = None;
+            for annotation in self.doc.get_page_annotations(page_id)? {
+                let subtype = match annotation.get(b"Subtype").and_then(Object::as_name) {
+                    Ok(subtype) => String::from_utf8_lossy(subtype).into_owned(),
+                    Err(_) => continue,
+                };
+                let is_markup = MARKUP_SUBTYPES.contains(&subtype.as_str());
+                if !is_markup && !NOTE_SUBTYPES.contains(&subtype.as_str()) {
+                    continue;
+                }
+                let numbers = |key: &[u8]| -> Vec<f32> {
+                    annotation
+                        .get_deref(key, &self.doc)
+                        .and_then(Object::as_array)
+                        .map(|array| array.iter().filter_map(|n| n.as_float().ok()).collect())
+                        .unwrap_or_default()
+                };
+                let text = |key: &[u8]| {
+                    annotation
+                        .get_deref(key, &self.doc)
+                        .ok()
+                        .and_then(text_string)
+                        .map(|text| text.trim().to_string())
+                        .filter(|text| !text.is_empty())
+                };
+                let rect = match numbers(b"Rect")[..] {
+                    [x1, y1, x2, y2] => [x1.min(x2), y1.min(y2), x1.max(x2), y1.max(y2)],
+                    _ => continue,
+                };
+                let quote = if is_markup {
+                    // the glyphs are only extracted for the pages with markups
+                    let glyphs = match &glyphs {
+                        Some(glyphs) => glyphs,
+                        None => glyphs.insert(self.page_glyphs(page_id).unwrap_or_default()),
+                    };
+                    let mut quads: Vec<[f32; 4]> = numbers(b"QuadPoints")
+                        .chunks_exact(8)
+                        .map(|quad| {
+                            let xs = [quad[0], quad[2], quad[4], quad[6]];
+                            let ys = [quad[1], quad[3], quad[5], quad[7]];
+                            let min = |v: [f32; 4]| v.into_iter().fold(f32::INFINITY, f32::min);
+                            let max = |v: [f32; 4]| v.into_iter().fold(f32::NEG_INFINITY, f32::max);
+                            [min(xs), min(ys), max(xs), max(ys)]
+                        })
+                        .collect();
+                    if quads.is_empty() {
+                        quads.push(rect);
+                    }
+                    Some(quote_text(glyphs, &quads)).filter(|quote| !quote.is_empty())
+                } else {
+                    None
+                };
+                annotations.push(Annotation {
+                    page,
+                    subtype,
+                    rect,
+                    quote,
+                    contents: text(b"Contents"),
+                    name: text(b"NM"),
+                });
+            }
+        }
+        // top to bottom, and then left to right on each page
+        annotations.sort_by(|a, b| {
+            a.page
+                .cmp(&b.page)
+                .then(b.rect[3].total_cmp(&a.rect[3]))
+                .then(a.rect[0].total_cmp(&b.rect[0]))
+        });
+        Ok(annotations)
+    }
+
+    /// Glyphs of a page in the content order, with their positions
+    fn page_glyphs(&self, page_id: ObjectId) -> Result<Vec<Glyph>, Box<dyn Error>> {
+        let fonts: BTreeMap<Vec<u8>, PageFont> = self
+            .doc
+            .get_page_fonts(page_id)?
+            .into_iter()
+            .filter_map(|(name, font): (Vec<u8>, &Dictionary)| {
+                let widths = font
+                    .get_deref(b"Widths", &self.doc)
+                    .and_then(Object::as_array)
+                    .map(|widths| widths.iter().filter_map(|w| w.as_float().ok()).collect())
+                    .unwrap_or_default();
+                let first_char = font
+                    .get_deref(b"FirstChar", &self.doc)
+                    .and_then(Object::as_i64)
+                    .unwrap_or(0);
+                Some((
+                    name,
+                    PageFont {
+                        encoding: font.get_font_encoding(&self.doc).ok()?,
+                        first_char,
+                        widths,
+                    },
+                ))
+            })
+            .collect();
+        let content = Content::decode(&self.doc.get_page_content(page_id))?;
+        let mut glyphs = vec![];
+        let (mut ctm, mut saved) = (IDENTITY, vec![]);
+        let (mut tm, mut tlm) = (IDENTITY, IDENTITY);
+        let mut font = None;
+        let (mut size, mut leading, mut scale, mut rise) = (0.0, 0.0, 1.0, 0.0);
+        let (mut char_spacing, mut word_spacing) = (0.0, 0.0);
+        let number = |operands: &[Object], i: usize| -> f32 {
+            operands
+                .get(i)
+                .and_then(|n| n.as_float().ok())
+                .unwrap_or(0.0)
+        };
+        for operation in &content.operations {
+            let operands = &operation.operands[..];
+            let mut shown = vec![];
+            match operation.operator.as_str() {
+                "q" => saved.push(ctm),
+                "Q" => ctm = saved.pop().unwrap_or(IDENTITY),
+                "cm" if operands.len() == 6 => {
+                    let m: Vec<f32> = (0..6).map(|i| number(operands, i)).collect();
+                    ctm = multiply(&[m[0], m[1], m[2], m[3], m[4], m[5]], &ctm);
+                }
+                "BT" => (tm, tlm) = (IDENTITY, IDENTITY),
+                "Tf" => {
+                    font = operands
+                        .first()
+                        .and_then(|name| name.as_name().ok())
+                        .and_then(|name| fonts.get(name));
+                    size = number(operands, 1);
+                }
+                "Tc" => char_spacing = number(operands, 0),
+                "Tw" => word_spacing = number(operands, 0),
+                "Tz" => scale = number(operands, 0) / 100.0,
+                "TL" => leading = number(operands, 0),
+                "Ts" => rise = number(operands, 0),
+                "Td" | "TD" => {
+                    if operation.operator == "TD" {
+                        leading = -number(operands, 1);
+                    }
+                    tlm = multiply(&translation(number(operands, 0), number(operands, 1)), &tlm);
+                    tm = tlm;
+                }
+                "Tm" if operands.len() == 6 => {
+                    let m: Vec<f32> = (0..6).map(|i| number(operands, i)).collect();
+                    tlm = [m[0], m[1], m[2], m[3], m[4], m[5]];
+                    tm = tlm;
+                }
+                "T*" | "'" | "\"" => {
+                    if operation.operator == "\"" {
+                        word_spacing = number(operands, 0);
+                        char_spacing = number(operands, 1);
+                    }
+                    tlm = multiply(&translation(0.0, -leading), &tlm);
+                    tm = tlm;
+                    shown.extend(operands.last().filter(|_| operation.operator != "T*"));
+                }
+                "Tj" => shown.extend(operands.first()),
+                "TJ" => shown.extend(
+                    operands
+                        .first()
+                        .and_then(|a| a.as_array().ok())
+                        .into_iter()
+                        .flatten(),
+                ),
+                _ => {}
+            }
+            let Some(font) = font else { continue };
+            for operand in shown {
+                let bytes = match operand {
+                    Object::String(bytes, _) => bytes,
+                    _ => {
+                        // a kerning adjustment, where a large one is a space
+                        let adjustment = operand.as_float().unwrap_or(0.0);
+                        if adjustment < -200.0 {
+                            glyphs.push(Self::glyph(' ', 0.0, size, rise, &tm, &ctm));
+                        }
+                        tm = multiply(&translation(-adjustment / 1000.0 * size * scale, 0.0), &tm);
+                        continue;
+                    }
+                };
+                let text = Document::decode_text(&font.encoding, bytes).unwrap_or_default();
+                // one-byte codes have their own widths (unlike multi-byte ones)
+                let widths: Vec<f32> = if text.chars().count() == bytes.len() {
+                    bytes.iter().map(|code| font.width(*code)).collect()
+                } else {
+                    text.chars().map(|_| PageFont::DEFAULT_WIDTH).collect()
+                };
+                for (ch, width) in text.chars().zip(widths) {
+                    let spacing = char_spacing + if ch == ' ' { word_spacing } else { 0.0 };
+                    let advance = (width * size + spacing) * scale;
+                    glyphs.push(Self::glyph(ch, advance, size, rise, &tm, &ctm));
+                    tm = multiply(&translation(advance, 0.0), &tm);
+                }
+            }
+        }
+        Ok(glyphs)
+    }
+
+    /// Glyph at the current text position, which advances it by `advance` (in text space)
+    fn glyph(ch: char, advance: f32, size: f32, rise: f32, tm: &Matrix, ctm: &Matrix) -> Glyph {
+        let matrix = multiply(&multiply(&translation(0.0, rise), tm), ctm);
+        let end = multiply(&translation(advance, 0.0), &matrix);
+        Glyph {
+            ch,
+            x: matrix[4],
+            y: matrix[5],
+            width: end[4] - matrix[4],
+            size: size * matrix[2].hypot(matrix[3]),
+        }
+    }
+
+    /// Text spans of a page (starting from 1) in the content order
+    pub fn page_spans(&self, page: u32) -> Result<Vec<TextSpan>, Box<dyn Error>> {
+        let page_id = *self
+            .doc
+            .get_pages()
+            .get(&page)
+            .ok_or_else(|| format!("page {} does not exist", page))?;
+        let encodings: BTreeMap<Vec<u8>, Encoding> = self
+            .doc
+            .get_page_fonts(page_id)?
+            .into_iter()
+            .filter_map(|(name, font): (Vec<u8>, &Dictionary)| {
+                Some((name, font.get_font_encoding(&self.doc).ok()?))
+            })
+            .collect();
+        let content = Content::decode(&self.doc.get_page_content(page_id))?;
+        let mut spans: Vec<TextSpan> = vec![];
+        let (mut encoding, mut font_size) = (None, 0.0);
+        // the (a, b, c, d) part of the text matrix
+        let mut matrix = [1.0, 0.0, 0.0, 1.0];
+        for operation in &content.operations {
+            let operands = &operation.operands;
+            match operation.operator.as_str() {
+                "BT" => matrix = [1.0, 0.0, 0.0, 1.0],
+                "Tf" => {
+                    encoding = operands
+                        .first()
+                        .and_then(|name| name.as_name().ok())
+                        .and_then(|name| encodings.get(name));
+                    font_size = operands
+                        .get(1)
+                        .and_then(|size| size.as_float().ok())
+                        .unwrap_or(0.0);
+                }
+                "Tm" if operands.len() == 6 => {
+                    for (i, value) in matrix.iter_mut().enumerate() {
+                        *value = operands[i].as_float().unwrap_or(0.0);
+                    }
+                }
+                "Tj" | "TJ" | "'" | "\"" => {
+                    let Some(encoding) = encoding else { continue };
+                    // the quote operators move to the next line first
+                    let mut text = match operation.operator.as_str() {
+                        "'" | "\"" => " ".to_string(),
+                        _ => String::new(),
+                    };
+                    collect_text(&mut text, encoding, operands);
+                    if text.trim().is_empty() {
+                        continue;
+                    }
+                    let size = font_size * matrix[2].hypot(matrix[3]);
+                    let rotated = matrix[1] != 0.0 || matrix[2] != 0.0;
+                    // text in the same font continues the previous span
+                    match spans.last_mut() {
+                        Some(last) if (last.size - size).abs() < 0.1 && last.rotated == rotated => {
+                            last.text.push_str(&text)
+                        }
+                        _ => spans.push(TextSpan {
+                            text,
+                            size,
+                            rotated,
+                        }),
+                    }
+                }
+                "Td" | "TD" | "T*" => {
+                    // a new line is separated by a space
+                    if let Some(last) = spans.last_mut() {
+                        if !last.text.ends_with(' ') {
+                            last.text.push(' ');
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(spans)
+    }
+}
+
+/// Decode the strings of a text operator, where large kerning values are spaces
+fn collect_text(text: &mut String, encoding: &Encoding, operands: &[Object]) {
+    for operand in operands {
+        match operand {
+            Object::String(bytes, _) => {
+                if let Ok(decoded) = Document::decode_text(encoding, bytes) {
+                    text.push_str(&decoded);
+                }
+            }
+            Object::Array(array) => collect_text(text, encoding, array),
+            Object::Integer(i) if *i < -200 => text.push(' '),
+            Object::Real(r) if *r < -200.0 => text.push(' '),
+            _ => {}
+        }
+    }
+}
+
+/// Text string (e.g. the comment of an annotation) with its line breaks
+///
+/// The line breaks (e.g. "\r" by Acrobat) are dropped in PDFDocEncoding by lopdf,
+/// so the lines are decoded one by one.
+fn text_string(object: &Object) -> Option<String> {
+    let bytes = object.as_str().ok()?;
+    if bytes.starts_with(b"\xFE\xFF") || bytes.starts_with(b"\xEF\xBB\xBF") {
+        let text = lopdf::decode_text_string(object).ok()?;
+        return Some(text.replace("\r\n", "\n").replace('\r', "\n"));
+    }
+    // "\r\n" and "\r" become "\n"
+    let bytes: Vec<u8> = bytes
+        .iter()
+        .enumerate()
+        .filter(|(i, byte)| **byte != b'\r' || bytes.get(i + 1) != Some(&b'\n'))
+        .map(|(_, byte)| if *byte == b'\r' { b'\n' } else { *byte })
+        .collect();
+    let lines: Option<Vec<String>> = bytes
+        .split(|byte| *byte == b'\n')
+        .map(|line| lopdf::decode_text_string(&Object::string_literal(line)).ok())
+        .collect();
+    Some(lines?.join("\n"))
+}
+
+/// Text of the glyphs within the quadrilaterals (given as bounding boxes), line by line
+///
+/// A glyph is within a quadrilateral if the middle of its lower half is,
+/// so that the neighboring lines are not included.
+fn quote_text(glyphs: &[Glyph], quads: &[[f32; 4]]) -> String {
+    let mut quote = String::new();
+    for [x1, y1, x2, y2] in quads {
+        for glyph in glyphs {
+            let x = glyph.x + glyph.width / 2.0;
+            let y = glyph.y + glyph.size * 0.25;
+            if (*x1..=*x2).contains(&x) && (*y1..=*y2).contains(&y) {
+                quote.push(glyph.ch);
+            }
+        }
+        quote.push(' ');
+    }
+    quote.split_whitespace().collect::<Vec<_>>().join(" ")
+}
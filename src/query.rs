@@ -0,0 +1,518 @@
+use super::database::PaperRecord;
+use super::list::YearRange;
+use super::tags;
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+
+/// Query for selecting papers
+///
+/// The query language is a list of terms combined with `AND`, `OR` and `NOT`
+/// (or a leading `-`), where adjacent terms are implicitly combined with `AND`
+/// and parentheses can be used for grouping.
+/// A term is either a plain word, a quoted phrase, or `field:value`, e.g.
+/// ```text
+/// author:zhao year:2023..2024 title:"beam squint" NOT doi:10.1145
+/// ```
+/// Matching is case-insensitive and ignores accents, so `author:jurgen` matches "Jürgen".
+/// A `tag:` term matches the tag and the tags below it, e.g. `tag:method` matches
+/// "method/beamforming" (but not "methodology").
+#[derive(Debug, Clone, PartialEq)]
+pub enum Query {
+    /// Matches all papers (the empty query)
+    All,
+    /// Text contained in a field (already normalized)
+    Term(QueryField, String),
+    /// Year within the range
+    Year(YearRange),
+    And(Vec<Query>),
+    Or(Vec<Query>),
+    Not(Box<Query>),
+}
+
+/// Field that a query term refers to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueryField {
+    /// Any of the fields below
+    Any,
+    Id,
+    Type,
+    Title,
+    Author,
+    Year,
+    Month,
+    Venue,
+    Volume,
+    Issue,
+    Pages,
+    Publisher,
+    Abstract,
+    Keyword,
+    Url,
+    Isbn,
+    Issn,
+    Language,
+    Doi,
+    Arxiv,
+    File,
+    Category,
+    Tag,
+    Status,
+    Priority,
+    Rating,
+}
+
+impl QueryField {
+    /// All specific fields (i.e., except `Any`)
+    const FIELDS: [QueryField; 25] = [
+        Self::Id,
+        Self::Type,
+        Self::Title,
+        Self::Author,
+        Self::Year,
+        Self::Month,
+        Self::Venue,
+        Self::Volume,
+        Self::Issue,
+        Self::Pages,
+        Self::Publisher,
+        Self::Abstract,
+        Self::Keyword,
+        Self::Url,
+        Self::Isbn,
+        Self::Issn,
+        Self::Language,
+        Self::Doi,
+        Self::Arxiv,
+        Self::File,
+        Self::Category,
+        Self::Tag,
+        Self::Status,
+        Self::Priority,
+        Self::Rating,
+    ];
+
+    fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "id" | "key" => Some(Self::Id),
+            "type" => Some(Self::Type),
+            "title" => Some(Self::Title),
+            "author" | "authors" => Some(Self::Author),
+            "year" => Some(Self::Year),
+            "month" => Some(Self::Month),
+            "venue" | "journal" | "booktitle" => Some(Self::Venue),
+            "volume" => Some(Self::Volume),
+            "issue" | "number" => Some(Self::Issue),
+            "pages" => Some(Self::Pages),
+            "publisher" => Some(Self::Publisher),
+            "abstract" => Some(Self::Abstract),
+            "keyword" | "keywords" => Some(Self::Keyword),
+            "url" => Some(Self::Url),
+            "isbn" => Some(Self::Isbn),
+            "issn" => Some(Self::Issn),
+            "language" | "lang" => Some(Self::Language),
+            "doi" => Some(Self::Doi),
+            "arxiv" => Some(Self::Arxiv),
+            "file" => Some(Self::File),
+            "category" | "cat" => Some(Self::Category),
+            "tag" | "tags" => Some(Self::Tag),
+            "status" => Some(Self::Status),
+            "priority" => Some(Self::Priority),
+            "rating" => Some(Self::Rating),
+            _ => None,
+        }
+    }
+
+    /// Whether a term must equal a value of the field (instead of being contained in it)
+    ///
+    /// E.g. `month:1` should not match October, and `status:read` should not match "reading".
+    fn is_exact(&self) -> bool {
+        matches!(
+            self,
+            Self::Month | Self::Status | Self::Priority | Self::Rating
+        )
+    }
+
+    /// Text values of the field in a paper record
+    fn values(&self, record: &PaperRecord) -> Vec<String> {
+        let entry = &record.entry;
+        match self {
+            Self::Any => Self::FIELDS
+                .iter()
+                .flat_map(|field| field.values(record))
+                .collect(),
+            Self::Id => vec![record.id.clone()],
+            Self::Type => entry.entry_type.iter().map(|t| t.to_string()).collect(),
+            Self::Title => entry.title.iter().cloned().collect(),
+            Self::Author => entry
+                .authors
+                .iter()
+                .flatten()
+                .map(|author| author.to_string())
+                .collect(),
+            Self::Year => entry.year.iter().map(|year| year.to_string()).collect(),
+            Self::Month => entry.month.iter().map(|month| month.to_string()).collect(),
+            Self::Venue => entry.venue.iter().cloned().collect(),
+            Self::Volume => entry.volume.iter().cloned().collect(),
+            Self::Issue => entry.issue.iter().cloned().collect(),
+            Self::Pages => entry.pages.iter().cloned().collect(),
+            Self::Publisher => entry.publisher.iter().cloned().collect(),
+            Self::Abstract => entry.r#abstract.iter().cloned().collect(),
+            Self::Keyword => entry.keywords.iter().flatten().cloned().collect(),
+            Self::Url => entry.url.iter().cloned().collect(),
+            Self::Isbn => entry.isbn.iter().cloned().collect(),
+            Self::Issn => entry.issn.iter().cloned().collect(),
+            Self::Language => entry.language.iter().cloned().collect(),
+            Self::Doi => entry.doi.iter().cloned().collect(),
+            Self::Arxiv => entry.arxiv.iter().cloned().collect(),
+            Self::File => entry.file.iter().cloned().collect(),
+            Self::Category => vec![record.category.clone()],
+            Self::Tag => entry.tags.iter().flatten().cloned().collect(),
+            Self::Status => vec![entry.reading_status().to_string()],
+            Self::Priority => entry.priority.iter().map(|p| p.to_string()).collect(),
+            Self::Rating => entry.rating.iter().map(|r| r.to_string()).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    LeftParen,
+    RightParen,
+    And,
+    Or,
+    Not,
+    /// A term with an optional field name
+    Word(Option<String>, String),
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::LeftParen => write!(f, "'('"),
+            Self::RightParen => write!(f, "')'"),
+            Self::And => write!(f, "AND"),
+            Self::Or => write!(f, "OR"),
+            Self::Not => write!(f, "NOT"),
+            Self::Word(Some(field), value) => write!(f, "'{}:{}'", field, value),
+            Self::Word(None, value) => write!(f, "'{}'", value),
+        }
+    }
+}
+
+impl Query {
+    /// Parse a query string
+    ///
+    /// An empty query matches all papers.
+    pub fn parse(query: &str) -> Result<Self, String> {
+        let tokens = Self::tokenize(query)?;
+        let mut pos = 0;
+        if tokens.is_empty() {
+            return Ok(Self::All);
+        }
+        let parsed = Self::parse_or(&tokens, &mut pos)?;
+        match tokens.get(pos) {
+            None => Ok(parsed),
+            Some(Token::RightParen) => Err("unmatched ')'".to_string()),
+            Some(token) => Err(format!("unexpected {}", token)),
+        }
+    }
+
+    /// Check if a paper record matches the query
+    pub fn matches(&self, record: &PaperRecord) -> bool {
+        match self {
+            Self::All => true,
+            Self::Term(QueryField::Tag, text) => tags::has_tag(&record.entry, text),
+            Self::Term(field, text) if field.is_exact() => field
+                .values(record)
+                .iter()
+                .any(|value| normalize(value) == *text),
+            Self::Term(field, text) => field
+                .values(record)
+                .iter()
+                .any(|value| normalize(value).contains(text.as_str())),
+            Self::Year(range) => range.contains(record.entry.year),
+            Self::And(queries) => queries.iter().all(|query| query.matches(record)),
+            Self::Or(queries) => queries.iter().any(|query| query.matches(record)),
+            Self::Not(query) => !query.matches(record),
+        }
+    }
+
+    fn tokenize(query: &str) -> Result<Vec<Token>, String> {
+        let mut tokens = vec![];
+        let mut chars = query.chars().peekable();
+        while let Some(&c) = chars.peek() {
+            match c {
+                c if c.is_whitespace() => {
+                    chars.next();
+                }
+                '(' => {
+                    chars.next();
+                    tokens.push(Token::LeftParen);
+                }
+                ')' => {
+                    chars.next();
+                    tokens.push(Token::RightParen);
+                }
+                '-' => {
+                    // '-' is a negation if directly followed by a term
+                    chars.next();
+                    match chars.peek() {
+                        Some(c) if !c.is_whitespace() && *c != ')' => tokens.push(Token::Not),
+                        _ => tokens.push(Token::Word(None, "-".to_string())),
+                    }
+                }
+                '"' => {
+                    chars.next();
+                    tokens.push(Token::Word(None, Self::read_quoted(&mut chars)?));
+                }
+                _ => {
+                    let mut word = String::new();
+                    while let Some(&c) = chars.peek() {
+                        if c.is_whitespace() || c == '(' || c == ')' {
+                            break;
+                        }
+                        chars.next();
+                        if c == ':' && QueryField::from_name(&word).is_some() {
+                            let value = match chars.peek() {
+                                Some('"') => {
+                                    chars.next();
+                                    Self::read_quoted(&mut chars)?
+                                }
+                                _ => {
+                                    let mut value = String::new();
+                                    while let Some(&c) = chars.peek() {
+                                        if c.is_whitespace() || c == '(' || c == ')' {
+                                            break;
+                                        }
+                                        value.push(c);
+                                        chars.next();
+                                    }
+                                    value
+                                }
+                            };
+                            if value.is_empty() {
+                                return Err(format!("empty value for field '{}'", word));
+                            }
+                            tokens.push(Token::Word(Some(word), value));
+                            word = String::new();
+                            break;
+                        }
+                        word.push(c);
+                    }
+                    match word.as_str() {
+                        "" => {}
+                        "AND" | "&&" => tokens.push(Token::And),
+                        "OR" | "||" => tokens.push(Token::Or),
+                        "NOT" => tokens.push(Token::Not),
+                        _ => tokens.push(Token::Word(None, word)),
+                    }
+                }
+            }
+        }
+        Ok(tokens)
+    }
+
+    /// Read a quoted phrase after the opening quote (`\"` is an escaped quote)
+    fn read_quoted(chars: &mut std::iter::Peekable<std::str::Chars>) -> Result<String, String> {
+        let mut phrase = String::new();
+        loop {
+            match chars.next() {
+                Some('"') => return Ok(phrase),
+                Some('\\') if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    phrase.push('"');
+                }
+                Some(c) => phrase.push(c),
+                None => return Err("unterminated quote".to_string()),
+            }
+        }
+    }
+
+    fn parse_or(tokens: &[Token], pos: &mut usize) -> Result<Self, String> {
+        let mut queries = vec![Self::parse_and(tokens, pos)?];
+        while tokens.get(*pos) == Some(&Token::Or) {
+            *pos += 1;
+            queries.push(Self::parse_and(tokens, pos)?);
+        }
+        Ok(match queries.len() {
+            1 => queries.remove(0),
+            _ => Self::Or(queries),
+        })
+    }
+
+    fn parse_and(tokens: &[Token], pos: &mut usize) -> Result<Self, String> {
+        let mut queries = vec![Self::parse_unary(tokens, pos)?];
+        loop {
+            match tokens.get(*pos) {
+                Some(Token::And) => {
+                    *pos += 1;
+                    queries.push(Self::parse_unary(tokens, pos)?);
+                }
+                // implicit AND between adjacent terms
+                Some(Token::Word(..)) | Some(Token::Not) | Some(Token::LeftParen) => {
+                    queries.push(Self::parse_unary(tokens, pos)?);
+                }
+                _ => break,
+            }
+        }
+        Ok(match queries.len() {
+            1 => queries.remove(0),
+            _ => Self::And(queries),
+        })
+    }
+
+    fn parse_unary(tokens: &[Token], pos: &mut usize) -> Result<Self, String> {
+        match tokens.get(*pos) {
+            Some(Token::Not) => {
+                *pos += 1;
+                Ok(Self::Not(Box::new(Self::parse_unary(tokens, pos)?)))
+            }
+            Some(Token::LeftParen) => {
+                *pos += 1;
+                let query = Self::parse_or(tokens, pos)?;
+                if tokens.get(*pos) != Some(&Token::RightParen) {
+                    return Err("missing ')'".to_string());
+                }
+                *pos += 1;
+                Ok(query)
+            }
+            Some(Token::Word(field, value)) => {
+                *pos += 1;
+                Self::term(field.as_deref(), value)
+            }
+            Some(token) => Err(format!("unexpected {}", token)),
+            None => Err("unexpected end of query".to_string()),
+        }
+    }
+
+    fn term(field: Option<&str>, value: &str) -> Result<Self, String> {
+        let field = match field {
+            Some(name) => QueryField::from_name(name).unwrap(), // checked by the tokenizer
+            None => QueryField::Any,
+        };
+        match field {
+            QueryField::Year => value.parse::<YearRange>().map(Self::Year),
+            _ => Ok(Self::Term(field, normalize(value))),
+        }
+    }
+}
+
+/// Normalize text for matching
+///
+/// The text is decomposed (NFKD) with combining marks removed and lowercased,
+/// so that accents, letter case and full-width forms are all ignored.
+pub fn normalize(text: &str) -> String {
+    text.nfkd()
+        .filter(|c| !is_combining_mark(*c))
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::author::Author;
+    use crate::database::{PaperEntry, ReadingStatus};
+
+    fn record() -> PaperRecord {
+        PaperRecord {
+            id: "zhao2024beam".to_string(),
+            category: "wireless/beam".to_string(),
+            entry: PaperEntry {
+                title: Some("Beam Squint in Near-Field Communications".to_string()),
+                authors: Some(vec![Author::parse("Jürgen Zhao").unwrap()]),
+                year: Some(2024),
+                month: Some(10),
+                venue: Some("IEEE Trans. Wireless Commun.".to_string()),
+                tags: Some(vec!["method/beamforming".to_string()]),
+                ..PaperEntry::new()
+            },
+        }
+    }
+
+    fn matches(query: &str) -> bool {
+        Query::parse(query).unwrap().matches(&record())
+    }
+
+    #[test]
+    fn parse_precedence() {
+        let term = |text: &str| Query::Term(QueryField::Any, text.to_string());
+        assert_eq!(Query::parse("  ").unwrap(), Query::All);
+        assert_eq!(
+            Query::parse("a b OR c").unwrap(),
+            Query::Or(vec![Query::And(vec![term("a"), term("b")]), term("c")])
+        );
+        assert_eq!(
+            Query::parse("-a (b OR c)").unwrap(),
+            Query::And(vec![
+                Query::Not(Box::new(term("a"))),
+                Query::Or(vec![term("b"), term("c")])
+            ])
+        );
+        assert_eq!(
+            Query::parse("a - b").unwrap(),
+            Query::And(vec![term("a"), term("-"), term("b")])
+        );
+    }
+
+    #[test]
+    fn parse_fields() {
+        assert_eq!(
+            Query::parse("journal:\"Wireless \\\"Commun\\\"\"").unwrap(),
+            Query::Term(QueryField::Venue, "wireless \"commun\"".to_string())
+        );
+        assert_eq!(
+            Query::parse("year:2023..").unwrap(),
+            Query::Year(YearRange {
+                from: Some(2023),
+                to: None
+            })
+        );
+        // unknown fields are plain words
+        assert_eq!(
+            Query::parse("foo:bar").unwrap(),
+            Query::Term(QueryField::Any, "foo:bar".to_string())
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        for query in ["title:", "\"beam", "(beam", "beam OR", "AND beam", ")"] {
+            assert!(Query::parse(query).is_err(), "{}", query);
+        }
+    }
+
+    #[test]
+    fn match_fields() {
+        assert!(matches("author:jurgen year:2020..2024"));
+        assert!(matches("title:\"near-field\" NOT doi:10.1145"));
+        assert!(matches("venue:wireless category:beam"));
+        assert!(!matches("author:jurgen AND year:..2023"));
+        assert!(matches("ＢＥＡＭ")); // full-width
+    }
+
+    #[test]
+    fn match_exact_fields() {
+        assert!(matches("month:10"));
+        assert!(!matches("month:1"));
+        // a paper without a status is in the inbox
+        assert!(matches("status:inbox"));
+        let mut record = record();
+        record.entry.status = Some(ReadingStatus::Reading);
+        assert!(!Query::parse("status:read").unwrap().matches(&record));
+        assert!(Query::parse("status:reading").unwrap().matches(&record));
+    }
+
+    #[test]
+    fn match_tags() {
+        assert!(matches("tag:method"));
+        assert!(matches("tag:method/beamforming"));
+        assert!(!matches("tag:methodology"));
+        assert!(!matches("tag:beamforming"));
+    }
+
+    #[test]
+    fn normalize_text() {
+        assert_eq!(normalize("Jürgen Dvořák"), "jurgen dvorak");
+        assert_eq!(normalize("ﬁeld"), "field");
+    }
+}
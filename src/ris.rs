@@ -0,0 +1,390 @@
+use super::author::Author;
+use super::csl;
+use super::database::{normalize_doi, PaperEntry, PaperID};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+
+/// RIS reference types and the CSL item types (the first RIS type of a CSL type is exported)
+const TYPES: [(&str, &str); 22] = [
+    ("JOUR", "article-journal"),
+    ("JFULL", "article-journal"),
+    ("EJOUR", "article-journal"),
+    ("MGZN", "article-magazine"),
+    ("NEWS", "article-newspaper"),
+    ("CONF", "paper-conference"),
+    ("CPAPER", "paper-conference"),
+    ("BOOK", "book"),
+    ("EBOOK", "book"),
+    ("EDBOOK", "book"),
+    ("CHAP", "chapter"),
+    ("ECHAP", "chapter"),
+    ("THES", "thesis"),
+    ("RPRT", "report"),
+    ("PAT", "patent"),
+    ("ELEC", "webpage"),
+    ("DATA", "dataset"),
+    ("COMP", "software"),
+    ("UNPB", "manuscript"),
+    ("MANSCPT", "manuscript"),
+    ("STAND", "standard"),
+    ("GEN", "document"),
+];
+
+/// RIS tags of the CSL variables
+/// (the first tag of a variable is exported, the others are read as alternatives)
+///
+/// `SN` is both the ISBN and the ISSN, which are told apart by their lengths on import.
+const VARIABLES: [(&str, &str); 10] = [
+    ("VL", "volume"),
+    ("IS", "issue"),
+    ("AB", "abstract"),
+    ("N2", "abstract"),
+    ("UR", "URL"),
+    ("SN", "ISBN"),
+    ("SN", "ISSN"),
+    ("PB", "publisher"),
+    ("CY", "publisher-place"),
+    ("LA", "language"),
+];
+
+/// Author of a RIS name, "Last, First, Suffix" (or any form that `Author::parse` accepts)
+///
+/// A name with only the last name (e.g. "World Health Organization,") is not split.
+fn ris_author(name: &str) -> Option<Author> {
+    let parts: Vec<&str> = name.split(',').map(str::trim).collect();
+    match parts.as_slice() {
+        [last, ""] => Some(Author::literal(last)).filter(|author| !author.family.is_empty()),
+        [last, first, suffix] if !suffix.is_empty() => {
+            Author::parse(&format!("{}, {}, {}", last, suffix, first))
+        }
+        _ => Author::parse(name),
+    }
+}
+
+/// RIS name of an author, "Last, First, Suffix"
+fn ris_name(author: &Author) -> String {
+    let mut name = author.family_name();
+    if author.given.is_none() && author.suffix.is_none() {
+        // the comma marks the whole name as the last name
+        return format!("{},", name);
+    }
+    if let Some(given) = &author.given {
+        name = format!("{}, {}", name, given);
+    }
+    if let Some(suffix) = &author.suffix {
+        let given = if author.given.is_none() { ", " } else { "" };
+        name = format!("{}{}, {}", name, given, suffix);
+    }
+    name
+}
+
+/// Record read from a RIS file: tags and values, in the order of the file
+#[derive(Debug, Clone, Default)]
+pub struct RisRecord {
+    pub fields: Vec<(String, String)>,
+}
+
+impl RisRecord {
+    /// First value of the tags
+    fn first(&self, tags: &[&str]) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(tag, value)| tags.contains(&tag.as_str()) && !value.is_empty())
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// All values of the tags
+    fn all(&self, tags: &[&str]) -> Vec<&str> {
+        self.fields
+            .iter()
+            .filter(|(tag, value)| tags.contains(&tag.as_str()) && !value.is_empty())
+            .map(|(_, value)| value.as_str())
+            .collect()
+    }
+
+    /// Paper entry of the record
+    ///
+    /// The fields are read as CSL variables, so that those without a field in `PaperEntry`
+    /// (e.g. the place of the publisher) are kept in `PaperEntry::extra`
+    /// and exported again (also as CSL-JSON).
+    pub fn to_paper_entry(&self) -> PaperEntry {
+        let authors: Vec<Author> = self
+            .all(&["AU", "A1"])
+            .iter()
+            .filter_map(|name| ris_author(name))
+            .collect();
+        // dates are written as "YYYY/MM/DD/other"
+        let date = self.first(&["PY", "Y1", "DA"]).unwrap_or_default();
+        let mut date_parts = date.split('/').map(|part| {
+            let digits: String = part.chars().take_while(|c| c.is_ascii_digit()).collect();
+            digits.parse::<u32>().ok()
+        });
+        let year = date_parts.next().flatten();
+        let month = date_parts
+            .next()
+            .flatten()
+            .or_else(|| {
+                let date = self.first(&["DA"])?;
+                date.split('/').nth(1)?.parse().ok()
+            })
+            .filter(|month| (1..=12).contains(month));
+        let mut extra = BTreeMap::new();
+        if let Some(ris_type) = self.first(&["TY"]) {
+            let csl_type = TYPES
+                .iter()
+                .find(|(ty, _)| *ty == ris_type)
+                .map_or("document", |(_, csl)| csl);
+            extra.insert("type".to_string(), json!(csl_type));
+        }
+        for (tag, variable) in VARIABLES {
+            if extra.contains_key(variable) || tag == "SN" {
+                continue;
+            }
+            if let Some(value) = self.first(&[tag]) {
+                extra.insert(variable.to_string(), json!(value));
+            }
+        }
+        for number in self.all(&["SN"]) {
+            // an ISSN has 8 digits, and an ISBN has 10 or 13
+            let digits = number.chars().filter(|c| c.is_ascii_alphanumeric()).count();
+            let variable = if digits == 8 { "ISSN" } else { "ISBN" };
+            extra
+                .entry(variable.to_string())
+                .or_insert_with(|| json!(number));
+        }
+        let page = match (self.first(&["SP"]), self.first(&["EP"])) {
+            (Some(start), Some(end)) => Some(format!("{}-{}", start, end)),
+            (start, end) => start.or(end).map(|page| page.to_string()),
+        };
+        if let Some(page) = page {
+            extra.insert("page".to_string(), json!(page));
+        }
+        let keywords = self.all(&["KW"]);
+        if !keywords.is_empty() {
+            extra.insert("keyword".to_string(), json!(keywords.join(", ")));
+        }
+        let mut entry = PaperEntry {
+            doi: self.first(&["DO"]).and_then(normalize_doi),
+            title: self.first(&["TI", "T1"]).map(|title| title.to_string()),
+            authors: Some(authors).filter(|authors| !authors.is_empty()),
+            year,
+            month,
+            venue: self
+                .first(&["T2", "JO", "JF", "BT"])
+                .map(|venue| venue.to_string()),
+            extra: Some(extra),
+            ..PaperEntry::new()
+        };
+        csl::promote_extra(&mut entry);
+        entry
+    }
+}
+
+/// Parse the RIS content into records
+///
+/// Each record starts with `TY` and ends with `ER`.
+/// Lines without a tag continue the value of the previous line.
+pub fn parse_ris(input: &str) -> (Vec<RisRecord>, Vec<String>) {
+    let mut records = vec![];
+    let mut errors = vec![];
+    let mut current: Option<RisRecord> = None;
+    for (i, line) in input.trim_start_matches('\u{feff}').lines().enumerate() {
+        let line = line.trim_end();
+        if line.is_empty() {
+            continue;
+        }
+        // tags are two uppercase letters or digits followed by "  -"
+        let tagged = line.len() >= 5
+            && line.is_char_boundary(2)
+            && line[..2]
+                .chars()
+                .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit())
+            && line[2..].starts_with("  -");
+        if !tagged {
+            match current.as_mut().and_then(|record| record.fields.last_mut()) {
+                Some((_, value)) => {
+                    value.push(' ');
+                    value.push_str(line.trim());
+                }
+                None => errors.push(format!("unexpected line {}", i + 1)),
+            }
+            continue;
+        }
+        let tag = &line[..2];
+        let value = line[5..].trim().to_string();
+        match tag {
+            "TY" => {
+                if current.is_some() {
+                    errors.push(format!("missing 'ER' before line {}", i + 1));
+                }
+                current = Some(RisRecord {
+                    fields: vec![(tag.to_string(), value)],
+                });
+            }
+            "ER" => match current.take() {
+                Some(record) => records.push(record),
+                None => errors.push(format!("'ER' without 'TY' at line {}", i + 1)),
+            },
+            _ => match current.as_mut() {
+                Some(record) => record.fields.push((tag.to_string(), value)),
+                None => errors.push(format!("'{}' without 'TY' at line {}", tag, i + 1)),
+            },
+        }
+    }
+    if let Some(record) = current {
+        errors.push("missing 'ER' at the end".to_string());
+        records.push(record);
+    }
+    (records, errors)
+}
+
+/// Format a paper as a RIS record
+///
+/// The fields are written from the CSL-JSON item of the paper.
+pub fn to_ris(id: &PaperID, entry: &PaperEntry) -> String {
+    let item = csl::to_csl(id, entry);
+    let variable = |key: &str| item.get(key).and_then(Value::as_str);
+    let csl_type = variable("type").unwrap_or("document");
+    let ris_type = TYPES
+        .iter()
+        .find(|(_, csl)| *csl == csl_type)
+        .map_or("GEN", |(ty, _)| ty);
+    let mut fields: Vec<(&str, String)> = vec![("TY", ris_type.to_string()), ("ID", id.clone())];
+    for author in entry.authors.iter().flatten() {
+        fields.push(("AU", ris_name(author)));
+    }
+    if let Some(title) = &entry.title {
+        fields.push(("TI", title.clone()));
+    }
+    if let Some(year) = entry.year {
+        fields.push(("PY", year.to_string()));
+        if let Some(month) = entry.month {
+            fields.push(("DA", format!("{}/{:02}//", year, month)));
+        }
+    }
+    if let Some(venue) = &entry.venue {
+        fields.push(("T2", venue.clone()));
+    }
+    if let Some(doi) = &entry.doi {
+        fields.push(("DO", doi.clone()));
+    }
+    let mut exported: Vec<&str> = vec![];
+    for (tag, name) in VARIABLES {
+        if exported.contains(&name) {
+            continue;
+        }
+        if let Some(value) = variable(name) {
+            fields.push((tag, value.to_string()));
+            exported.push(name);
+        }
+    }
+    if let Some(page) = variable("page") {
+        match page.split_once('-') {
+            Some((start, end)) => {
+                fields.push(("SP", start.trim().to_string()));
+                fields.push(("EP", end.trim_start_matches('-').trim().to_string()));
+            }
+            None => fields.push(("SP", page.to_string())),
+        }
+    }
+    for keyword in entry.keywords.iter().flatten() {
+        fields.push(("KW", keyword.clone()));
+    }
+    let mut ris = String::new();
+    for (tag, value) in fields {
+        ris.push_str(&format!("{}  - {}\n", tag, value));
+    }
+    ris.push_str("ER  - \n");
+    ris
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::EntryType;
+
+    const RIS: &str = "\u{feff}TY  - JOUR
+AU  - van Beethoven, Ludwig
+AU  - King, Martin Luther, Jr.
+AU  - World Health Organization,
+TI  - A Long Title
+  Continued
+PY  - 2024///
+DA  - 2024/05/01/
+T2  - IEEE Trans. Wireless Commun.
+VL  - 23
+SP  - 1
+EP  - 6
+SN  - 1536-1276
+CY  - New York
+KW  - beams
+KW  - near field
+DO  - https://doi.org/10.1109/TWC.2024.1
+ER  - 
+";
+
+    #[test]
+    fn parse_record() {
+        let (records, errors) = parse_ris(RIS);
+        assert!(errors.is_empty(), "{:?}", errors);
+        let entry = records[0].to_paper_entry();
+        assert_eq!(entry.entry_type, Some(EntryType::Article));
+        assert_eq!(entry.title.as_deref(), Some("A Long Title Continued"));
+        let authors: Vec<String> = entry
+            .authors
+            .iter()
+            .flatten()
+            .map(|author| author.to_string())
+            .collect();
+        assert_eq!(
+            authors,
+            [
+                "Ludwig van Beethoven",
+                "Martin Luther King, Jr.",
+                "World Health Organization"
+            ]
+        );
+        assert_eq!((entry.year, entry.month), (Some(2024), Some(5)));
+        assert_eq!(entry.venue.as_deref(), Some("IEEE Trans. Wireless Commun."));
+        assert_eq!(entry.doi.as_deref(), Some("10.1109/TWC.2024.1"));
+        assert_eq!(entry.volume.as_deref(), Some("23"));
+        assert_eq!(entry.issn.as_deref(), Some("1536-1276"));
+        assert_eq!(entry.isbn, None);
+        assert_eq!(
+            entry.keywords,
+            Some(vec!["beams".to_string(), "near field".to_string()])
+        );
+        // the place of the publisher has no field
+        assert_eq!(entry.extra.unwrap()["publisher-place"], "New York");
+    }
+
+    #[test]
+    fn parse_errors() {
+        let (records, errors) = parse_ris("AU  - Orphan\nTY  - GEN\nTI  - A\nTY  - GEN\nTI  - B\n");
+        // a record without 'ER' is dropped if another one starts, but kept at the end
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].first(&["TI"]), Some("B"));
+        assert_eq!(errors.len(), 3);
+    }
+
+    #[test]
+    fn round_trip() {
+        let (records, _) = parse_ris(RIS);
+        let entry = records[0].to_paper_entry();
+        let ris = to_ris(&"x".to_string(), &entry);
+        assert!(ris.starts_with("TY  - JOUR\nID  - x\n"));
+        assert!(ris.contains("AU  - World Health Organization,\n"));
+        assert!(ris.contains("AU  - King, Martin Luther, Jr.\n"));
+        assert!(ris.ends_with("ER  - \n"));
+        let (records, errors) = parse_ris(&ris);
+        assert!(errors.is_empty(), "{:?}", errors);
+        let parsed = records[0].to_paper_entry();
+        assert_eq!(parsed.title, entry.title);
+        assert_eq!(parsed.authors, entry.authors);
+        assert_eq!((parsed.year, parsed.month), (entry.year, entry.month));
+        assert_eq!(parsed.pages, entry.pages);
+        assert_eq!(parsed.issn, entry.issn);
+        assert_eq!(parsed.keywords, entry.keywords);
+        assert_eq!(parsed.extra, entry.extra);
+    }
+}
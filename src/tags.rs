@@ -0,0 +1,216 @@
+use super::database::PaperEntry;
+use super::query::normalize;
+use std::collections::BTreeMap;
+
+/// Separator of the levels of a hierarchical tag, e.g. "method/beamforming"
+pub const SEPARATOR: char = '/';
+
+/// Normalize a tag by trimming the levels and dropping the empty ones
+///
+/// E.g. " method / beamforming/" becomes "method/beamforming".
+/// Returns `None` if there is no level left.
+pub fn normalize_tag(tag: &str) -> Option<String> {
+    let levels: Vec<&str> = tag
+        .split(SEPARATOR)
+        .map(str::trim)
+        .filter(|level| !level.is_empty())
+        .collect();
+    Some(levels.join(&SEPARATOR.to_string())).filter(|tag| !tag.is_empty())
+}
+
+/// Whether a tag is the filter or below it (case-insensitive and ignoring accents)
+///
+/// E.g. "method" matches "method" and "method/beamforming", but not "methodology".
+pub fn matches(tag: &str, filter: &str) -> bool {
+    let (tag, filter) = (normalize(tag), normalize(filter));
+    let filter = filter.trim_end_matches(SEPARATOR);
+    tag == filter
+        || tag
+            .strip_prefix(filter)
+            .is_some_and(|rest| rest.starts_with(SEPARATOR))
+}
+
+/// Whether any tag of a paper is the filter or below it
+pub fn has_tag(entry: &PaperEntry, filter: &str) -> bool {
+    entry.tags.iter().flatten().any(|tag| matches(tag, filter))
+}
+
+/// Add the tags to a paper, returning the number of new tags
+///
+/// Tags that the paper already has (ignoring the case and accents, as in `matches`) are skipped.
+pub fn add_tags(entry: &mut PaperEntry, new_tags: &[String]) -> usize {
+    let tags = entry.tags.get_or_insert_with(Vec::new);
+    let mut added = 0;
+    for tag in new_tags {
+        if !tags.iter().any(|t| normalize(t) == normalize(tag)) {
+            tags.push(tag.clone());
+            added += 1;
+        }
+    }
+    if tags.is_empty() {
+        entry.tags = None;
+    }
+    added
+}
+
+/// Remove the tags (and the tags below them) from a paper, returning the number removed
+pub fn remove_tags(entry: &mut PaperEntry, old_tags: &[String]) -> usize {
+    let tags = entry.tags.take().unwrap_or_default();
+    let count = tags.len();
+    let kept: Vec<String> = tags
+        .into_iter()
+        .filter(|tag| !old_tags.iter().any(|old| matches(tag, old)))
+        .collect();
+    let removed = count - kept.len();
+    entry.tags = Some(kept).filter(|tags| !tags.is_empty());
+    removed
+}
+
+/// Rename a tag (and the tags below it) of a paper, returning whether any is renamed
+///
+/// E.g. renaming "method" to "approach" turns "method/beamforming" into
+/// "approach/beamforming". Duplicates after renaming are removed.
+pub fn rename_tag(entry: &mut PaperEntry, old: &str, new: &str) -> bool {
+    if !has_tag(entry, old) {
+        return false;
+    }
+    let depth = old.split(SEPARATOR).count();
+    let renamed: Vec<String> = entry
+        .tags
+        .take()
+        .unwrap_or_default()
+        .into_iter()
+        .map(|tag| {
+            if !matches(&tag, old) {
+                return tag;
+            }
+            let levels: Vec<&str> = tag.split(SEPARATOR).skip(depth).collect();
+            [new]
+                .into_iter()
+                .chain(levels)
+                .collect::<Vec<_>>()
+                .join("/")
+        })
+        .collect();
+    add_tags(entry, &renamed);
+    true
+}
+
+/// Number of papers with each tag, in the order of the tree of tags
+///
+/// A paper also counts for the tags above its tags, e.g. a paper tagged
+/// "method/beamforming" counts for both "method" and "method/beamforming".
+/// Tags differing only in case or accents are counted together (under the first spelling seen).
+pub fn count_tags<'a, I>(entries: I) -> Vec<(String, usize)>
+where
+    I: IntoIterator<Item = &'a PaperEntry>,
+{
+    // the normalized levels sort the tags as a tree, e.g. "a" < "a/b" < "a-b"
+    let mut counts: BTreeMap<Vec<String>, (String, usize)> = BTreeMap::new();
+    for entry in entries {
+        let mut keys: Vec<Vec<String>> = vec![];
+        for tag in entry.tags.iter().flatten() {
+            let levels: Vec<&str> = tag.split(SEPARATOR).collect();
+            for depth in 1..=levels.len() {
+                let key: Vec<String> = levels[..depth].iter().map(|l| normalize(l)).collect();
+                if !keys.contains(&key) {
+                    counts
+                        .entry(key.clone())
+                        .or_insert_with(|| (levels[..depth].join("/"), 0));
+                    keys.push(key);
+                }
+            }
+        }
+        for key in keys {
+            counts.get_mut(&key).unwrap().1 += 1;
+        }
+    }
+    counts.into_values().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(tags: &[&str]) -> PaperEntry {
+        PaperEntry {
+            tags: Some(tags.iter().map(|tag| tag.to_string()).collect()),
+            ..PaperEntry::new()
+        }
+    }
+
+    fn tags(entry: &PaperEntry) -> Vec<&str> {
+        entry.tags.iter().flatten().map(String::as_str).collect()
+    }
+
+    fn strings(tags: &[&str]) -> Vec<String> {
+        tags.iter().map(|tag| tag.to_string()).collect()
+    }
+
+    #[test]
+    fn normalize_tags() {
+        assert_eq!(
+            normalize_tag(" method / beamforming/").as_deref(),
+            Some("method/beamforming")
+        );
+        assert_eq!(normalize_tag(" / "), None);
+    }
+
+    #[test]
+    fn match_tags() {
+        assert!(matches("method/beamforming", "method"));
+        assert!(matches("Method/Beamforming", "method/"));
+        assert!(matches("café", "CAFE"));
+        assert!(!matches("methodology", "method"));
+        assert!(!matches("method", "method/beamforming"));
+    }
+
+    #[test]
+    fn add() {
+        let mut paper = entry(&["cafe"]);
+        assert_eq!(add_tags(&mut paper, &strings(&["Café", "new", "NEW"])), 1);
+        assert_eq!(tags(&paper), ["cafe", "new"]);
+        let mut paper = PaperEntry::new();
+        assert_eq!(add_tags(&mut paper, &[]), 0);
+        assert_eq!(paper.tags, None);
+    }
+
+    #[test]
+    fn remove() {
+        let mut paper = entry(&["method", "method/beamforming", "methodology", "todo"]);
+        assert_eq!(remove_tags(&mut paper, &strings(&["METHOD"])), 2);
+        assert_eq!(tags(&paper), ["methodology", "todo"]);
+        assert_eq!(
+            remove_tags(&mut paper, &strings(&["methodology", "todo"])),
+            2
+        );
+        assert_eq!(paper.tags, None);
+    }
+
+    #[test]
+    fn rename_with_merge() {
+        let mut paper = entry(&["method/beamforming", "approach/Beamforming", "todo"]);
+        assert!(rename_tag(&mut paper, "method", "approach"));
+        // the renamed tag is merged into the existing one
+        assert_eq!(tags(&paper), ["approach/beamforming", "todo"]);
+        assert!(!rename_tag(&mut paper, "method", "approach"));
+    }
+
+    #[test]
+    fn count() {
+        let papers = [
+            entry(&["method/beamforming", "todo"]),
+            entry(&["Method", "method/Beamforming"]),
+            entry(&["méthod-b"]),
+        ];
+        assert_eq!(
+            count_tags(&papers),
+            [
+                ("method".to_string(), 2),
+                ("method/beamforming".to_string(), 2),
+                ("méthod-b".to_string(), 1),
+                ("todo".to_string(), 1),
+            ]
+        );
+    }
+}
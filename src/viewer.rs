@@ -0,0 +1,214 @@
+use super::options::ConfigViewers;
+use std::path::Path;
+use std::process::Command;
+
+/// Command to open a file with a viewer
+#[derive(Debug, Clone)]
+pub struct ViewerCommand {
+    pub program: String,
+    pub args: Vec<String>,
+    /// Whether the page number is passed to the viewer
+    pub with_page: bool,
+}
+
+impl ViewerCommand {
+    /// Build the viewer command for the file
+    ///
+    /// The viewer is looked up in the following order:
+    /// 1. the viewer configured for the file extension;
+    /// 2. `$PDFVIEWER` (for PDF files only);
+    /// 3. the system default (`xdg-open`, `open` on macOS or `start` on Windows).
+    pub fn new(viewers: Option<&ConfigViewers>, file: &Path, page: Option<u32>) -> Option<Self> {
+        let ext = file
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.to_lowercase())
+            .unwrap_or_default();
+        let template = viewers
+            .and_then(|viewers| viewers.get(&ext).cloned())
+            .or_else(|| match ext.as_str() {
+                "pdf" => std::env::var("PDFVIEWER")
+                    .ok()
+                    .filter(|v| !v.trim().is_empty()),
+                _ => None,
+            })
+            .unwrap_or_else(|| Self::system_default().to_string());
+        let file = file.to_str()?;
+        let mut words = split_command(&template);
+        if words.is_empty() {
+            return None;
+        }
+        let mut with_page = false;
+        if !words.iter().any(|word| word.contains("{file}")) {
+            words.push("{file}".to_string());
+        }
+        let words: Vec<String> = words
+            .into_iter()
+            .filter_map(|word| {
+                if word.contains("{page}") {
+                    let page = page?;
+                    with_page = true;
+                    Some(word.replace("{page}", &page.to_string()))
+                } else {
+                    Some(word)
+                }
+            })
+            .map(|word| word.replace("{file}", file))
+            .collect();
+        Some(Self {
+            program: words[0].clone(),
+            args: words[1..].to_vec(),
+            with_page,
+        })
+    }
+
+    /// Build the editor command for a text file (e.g. a note)
+    ///
+    /// The editor is `$VISUAL`, `$EDITOR` or `vi` (`notepad` on Windows), in this order.
+    pub fn editor(file: &Path) -> Option<Self> {
+        let template = ["VISUAL", "EDITOR"]
+            .iter()
+            .find_map(|var| std::env::var(var).ok().filter(|v| !v.trim().is_empty()))
+            .unwrap_or_else(|| {
+                if cfg!(target_os = "windows") {
+                    "notepad".to_string()
+                } else {
+                    "vi".to_string()
+                }
+            });
+        let mut words = split_command(&template);
+        if words.is_empty() {
+            return None;
+        }
+        words.push(file.to_str()?.to_string());
+        Some(Self {
+            program: words[0].clone(),
+            args: words[1..].to_vec(),
+            with_page: false,
+        })
+    }
+
+    fn system_default() -> &'static str {
+        if cfg!(target_os = "macos") {
+            "open"
+        } else if cfg!(target_os = "windows") {
+            "cmd /C start \"\""
+        } else {
+            "xdg-open"
+        }
+    }
+
+    /// Run the viewer, waiting for it to exit unless `background`
+    pub fn run(&self, background: bool) -> std::io::Result<()> {
+        let mut command = Command::new(&self.program);
+        command.args(&self.args);
+        if background {
+            command
+                .stdin(std::process::Stdio::null())
+                .stdout(std::process::Stdio::null())
+                .stderr(std::process::Stdio::null())
+                .spawn()?;
+            Ok(())
+        } else {
+            let status = command.status()?;
+            if status.success() {
+                Ok(())
+            } else {
+                Err(std::io::Error::other(format!(
+                    "'{}' exited with {}",
+                    self.program, status
+                )))
+            }
+        }
+    }
+}
+
+/// Split a command line into words, respecting single and double quotes
+fn split_command(command: &str) -> Vec<String> {
+    let mut words = vec![];
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut quote: Option<char> = None;
+    for c in command.chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => word.push(c),
+            (None, '"') | (None, '\'') => {
+                quote = Some(c);
+                in_word = true;
+            }
+            (None, c) if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            (None, c) => {
+                word.push(c);
+                in_word = true;
+            }
+        }
+    }
+    if in_word {
+        words.push(word);
+    }
+    words
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command(template: &str, file: &str, page: Option<u32>) -> ViewerCommand {
+        let viewers = ConfigViewers::from([("pdf".to_string(), template.to_string())]);
+        ViewerCommand::new(Some(&viewers), Path::new(file), page).unwrap()
+    }
+
+    #[test]
+    fn split_words() {
+        assert_eq!(split_command("okular  --page 3"), ["okular", "--page", "3"]);
+        assert_eq!(
+            split_command(r#""C:\Program Files\Viewer.exe" '-a b' c"d e"f"#),
+            [r"C:\Program Files\Viewer.exe", "-a b", "cd ef"]
+        );
+        // an empty quoted argument is kept, e.g. the window title of `start`
+        assert_eq!(
+            split_command(r#"cmd /C start """#),
+            ["cmd", "/C", "start", ""]
+        );
+        assert_eq!(split_command(r#"say "it's""#), ["say", "it's"]);
+        assert!(split_command("  ").is_empty());
+    }
+
+    #[test]
+    fn substitute_file_and_page() {
+        let viewer = command("zathura --page={page} {file}", "a b.pdf", Some(3));
+        assert_eq!(viewer.program, "zathura");
+        assert_eq!(viewer.args, ["--page=3", "a b.pdf"]);
+        assert!(viewer.with_page);
+        // the page argument is dropped without a page
+        let viewer = command("zathura --page={page} {file}", "a b.pdf", None);
+        assert_eq!(viewer.args, ["a b.pdf"]);
+        assert!(!viewer.with_page);
+        // the file is appended without `{file}`, and the page is not passed without `{page}`
+        let viewer = command(r#"cmd /C start """#, "a b.pdf", Some(3));
+        assert_eq!(viewer.program, "cmd");
+        assert_eq!(viewer.args, ["/C", "start", "", "a b.pdf"]);
+        assert!(!viewer.with_page);
+        // the placeholders may be part of a quoted argument
+        let viewer = command(r#"viewer "{file}#page={page}""#, "a.pdf", Some(2));
+        assert_eq!(viewer.args, ["a.pdf#page=2"]);
+    }
+
+    #[test]
+    fn viewer_of_other_extensions() {
+        let viewers = ConfigViewers::from([("djvu".to_string(), "djview".to_string())]);
+        let viewer = ViewerCommand::new(Some(&viewers), Path::new("a.DJVU"), None).unwrap();
+        assert_eq!(
+            (viewer.program.as_str(), viewer.args),
+            ("djview", vec!["a.DJVU".to_string()])
+        );
+        let viewers = ConfigViewers::from([("pdf".to_string(), " ".to_string())]);
+        assert!(ViewerCommand::new(Some(&viewers), Path::new("a.pdf"), None).is_none());
+    }
+}
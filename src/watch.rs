@@ -0,0 +1,157 @@
+use notify::event::{AccessKind, AccessMode, ModifyKind};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver};
+use std::time::Duration;
+
+/// A file is imported once it has not been modified for this long,
+/// so that files still being downloaded are not imported half-written.
+const SETTLE_TIME: Duration = Duration::from_secs(2);
+
+/// Check if the file is a PDF file by its extension
+pub fn is_pdf(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("pdf"))
+}
+
+/// Check if the file has settled, i.e. it is not being written any more
+pub fn is_settled(path: &Path) -> bool {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|modified| modified.elapsed().ok())
+        .is_some_and(|elapsed| elapsed >= SETTLE_TIME)
+}
+
+/// Queue of files waiting to be imported by 'watch'
+///
+/// The queue is stored as `watch.termipaper.json` in the database directory,
+/// so the files detected before a restart are still imported.
+/// The results are appended to `watch.termipaper.log` next to it.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WatchQueue {
+    files: Vec<PathBuf>,
+    #[serde(skip)]
+    dir: PathBuf,
+}
+
+impl WatchQueue {
+    fn queue_file(dir: &Path) -> PathBuf {
+        dir.join("watch.termipaper.json")
+    }
+
+    fn log_file(&self) -> PathBuf {
+        self.dir.join("watch.termipaper.log")
+    }
+
+    /// Load the queue of the database directory (empty if there is none)
+    pub fn load(dir: &Path) -> Self {
+        let queue_file = Self::queue_file(dir);
+        let mut queue: Self = match std::fs::read_to_string(&queue_file) {
+            Ok(queue_str) => serde_json::from_str(&queue_str).unwrap_or_else(|_| {
+                eprintln!(
+                    "Warning: failed to parse the watch queue at '{}', starting a new one.",
+                    queue_file.to_str().unwrap()
+                );
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        };
+        queue.dir = dir.to_path_buf();
+        queue
+    }
+
+    pub fn save(&self) -> Result<(), Box<dyn Error>> {
+        let queue_file = Self::queue_file(&self.dir);
+        if self.files.is_empty() {
+            if queue_file.exists() {
+                std::fs::remove_file(queue_file)?;
+            }
+            return Ok(());
+        }
+        std::fs::write(queue_file, serde_json::to_string_pretty(&self)?)?;
+        Ok(())
+    }
+
+    /// Files in the queue, in the order they are detected
+    pub fn files(&self) -> &[PathBuf] {
+        &self.files
+    }
+
+    /// Add a file to the queue, returning whether it is new
+    pub fn push(&mut self, path: PathBuf) -> bool {
+        if self.files.contains(&path) {
+            return false;
+        }
+        self.files.push(path);
+        true
+    }
+
+    pub fn remove(&mut self, path: &Path) {
+        self.files.retain(|file| file != path);
+    }
+
+    /// Append a result to the log file with the current time
+    pub fn log(&self, message: &str) {
+        let line = format!(
+            "{} {}\n",
+            chrono::Local::now().format("%Y-%m-%d %H:%M:%S"),
+            message
+        );
+        let result = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.log_file())
+            .and_then(|mut file| file.write_all(line.as_bytes()));
+        if let Err(e) = result {
+            eprintln!("Warning: failed to write the watch log: {}", e);
+        }
+    }
+}
+
+/// Watcher of new files in a directory (with inotify on Linux)
+pub struct FolderWatcher {
+    // the watcher stops when dropped
+    _watcher: RecommendedWatcher,
+    events: Receiver<notify::Result<Event>>,
+}
+
+impl FolderWatcher {
+    pub fn new(dir: &Path) -> Result<Self, Box<dyn Error>> {
+        let (sender, events) = channel();
+        let mut watcher = notify::recommended_watcher(sender)?;
+        watcher.watch(dir, RecursiveMode::NonRecursive)?;
+        Ok(Self {
+            _watcher: watcher,
+            events,
+        })
+    }
+
+    /// Wait for the files created (or moved in, or written) within the timeout
+    pub fn wait(&self, timeout: Duration) -> Vec<PathBuf> {
+        let mut paths = vec![];
+        let mut next = self.events.recv_timeout(timeout).ok();
+        while let Some(event) = next {
+            match event {
+                Ok(event) => {
+                    let is_new = matches!(
+                        event.kind,
+                        EventKind::Create(_)
+                            | EventKind::Modify(ModifyKind::Name(_))
+                            | EventKind::Access(AccessKind::Close(AccessMode::Write))
+                    );
+                    if is_new {
+                        paths.extend(event.paths.into_iter().filter(|path| path.is_file()));
+                    }
+                }
+                Err(e) => eprintln!("Warning: failed to watch the directory: {}", e),
+            }
+            next = self.events.try_recv().ok();
+        }
+        paths.dedup();
+        paths
+    }
+}